@@ -0,0 +1,19 @@
+# Transfer function of the bonsai raycaster, reloaded when it changes
+# sample r g b a
+0 1 0.794 0.655 0
+0.062 0.962 0.48 0.578 0.062
+0.125 0.854 0.175 0.5 0.125
+0.188 0.691 0.01 0.422 0.188
+0.25 0.5 0.054 0.345 0.25
+0.312 0.309 0.291 0.273 0.312
+0.375 0.146 0.617 0.206 0.375
+0.438 0.038 0.893 0.146 0.438
+0.5 0 1 0.095 0.5
+0.562 0.038 0.893 0.054 0.562
+0.625 0.146 0.617 0.024 0.625
+0.688 0.309 0.291 0.006 0.688
+0.75 0.5 0.054 0 0.75
+0.812 0.691 0.01 0.006 0.812
+0.875 0.854 0.175 0.024 0.875
+0.938 0.962 0.48 0.054 0.938
+1 1 0.794 0.095 1
@@ -0,0 +1,203 @@
+use std::{
+    env,
+    ffi::OsStr,
+    fmt::Write,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use naga::{
+    back::spv,
+    valid::{Capabilities, ValidationFlags, Validator},
+};
+
+// The shared reflection refers to the wgpu types by the name of the crate
+extern crate wgpu_types as wgpu;
+
+#[path = "src/shared/mod.rs"]
+pub mod shared;
+
+use shared::{
+    codegen,
+    preprocessor::{Preprocessor, ShaderDefines, ShaderDir},
+    reflection::Reflection,
+    spirv_options,
+};
+
+const VARIANT_MARKER: &str = "// @variant";
+
+/// Embeds every shader of the crate so executables work without the source
+/// tree. Release builds also get the shaders precompiled to SPIR-V along with
+/// their reflection, without defines and with the defines of every
+/// `// @variant A B` line of the shader.
+///
+/// Rust types of the uniforms shared with the shaders are generated from
+/// their WGSL declarations.
+fn main() -> color_eyre::Result<()> {
+    let shader_dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("shaders");
+    println!("cargo:rerun-if-changed={}", shader_dir.display());
+
+    let mut files = vec![];
+    collect_shaders(&shader_dir, &mut files)?;
+    files.sort();
+
+    let mut sources = String::from("&[\n");
+    for path in &files {
+        let _ = writeln!(
+            sources,
+            "    ({:?}, include_str!({:?})),",
+            shader_name(&shader_dir, path),
+            path.display().to_string()
+        );
+    }
+    sources.push(']');
+
+    // Debug builds compile shaders at runtime to keep them hot reloadable
+    let bundle = if env::var_os("CARGO_CFG_DEBUG_ASSERTIONS").is_none() {
+        compile_bundle(&shader_dir, &files)
+    } else {
+        String::from("&[]")
+    };
+
+    // Part of the keys of the on-disk shader cache
+    println!("cargo:rustc-env=VOKSELIS_NAGA_VERSION={}", naga_version());
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    codegen::generate_shader_types(&shader_dir, out_dir.join("shader_types.rs"), "crate")?;
+    fs::write(out_dir.join("embedded_shaders.rs"), sources)?;
+    fs::write(out_dir.join("shader_bundle.rs"), bundle)?;
+    Ok(())
+}
+
+fn collect_shaders(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_shaders(&path, files)?;
+        } else if path.extension() == Some(OsStr::new("wgsl")) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Versions and sources of naga in the closest lock file.
+///
+/// Git dependencies all report the same version, the source includes the commit.
+fn naga_version() -> String {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let lock = manifest_dir
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|path| path.is_file());
+    let lock = match lock {
+        Some(lock) => lock,
+        None => return String::from("unknown"),
+    };
+    println!("cargo:rerun-if-changed={}", lock.display());
+
+    let lock = fs::read_to_string(lock).unwrap_or_default();
+    let versions: Vec<String> = lock
+        .split("[[package]]")
+        .filter(|package| package.lines().any(|x| x.trim() == r#"name = "naga""#))
+        .map(|package| {
+            let fields: Vec<&str> = package
+                .lines()
+                .filter_map(|x| {
+                    x.trim()
+                        .strip_prefix("version = ")
+                        .or_else(|| x.trim().strip_prefix("source = "))
+                })
+                .map(|x| x.trim_matches('"'))
+                .collect();
+            fields.join(" ")
+        })
+        .collect();
+    versions.join(", ")
+}
+
+/// Path relative to the shader folder with forward slashes.
+fn shader_name(shader_dir: &Path, path: &Path) -> String {
+    let name = path.strip_prefix(shader_dir).unwrap();
+    name.to_string_lossy().replace('\\', "/")
+}
+
+/// SPIR-V and encoded reflection of every shader variant that compiles.
+fn compile_bundle(shader_dir: &Path, files: &[PathBuf]) -> String {
+    let preprocessor = Preprocessor::new(ShaderDir(shader_dir.to_path_buf()));
+    let mut validator = Validator::new(ValidationFlags::all(), Capabilities::all());
+    let mut writer = spv::Writer::new(&spirv_options::get_options()).unwrap();
+
+    let mut out = String::from("&[\n");
+    for path in files {
+        let name = shader_name(shader_dir, path);
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                println!("cargo:warning={} is not bundled: {}", name, err);
+                continue;
+            }
+        };
+        for defines in variants(&source) {
+            let defines_list: Vec<&str> = defines.iter().collect();
+            match compile(
+                &preprocessor,
+                &mut validator,
+                &mut writer,
+                path,
+                &source,
+                &defines,
+            ) {
+                Ok((words, reflection)) => {
+                    let mut encoded = vec![];
+                    reflection.encode(&mut encoded);
+                    let _ = writeln!(
+                        out,
+                        "    ({:?}, &{:?}, &{:?}, &{:?}),",
+                        name, defines_list, words, encoded
+                    );
+                }
+                Err(err) => println!("cargo:warning={} {} is not bundled: {}", name, defines, err),
+            }
+        }
+    }
+    out.push(']');
+    out
+}
+
+/// No defines, then the defines of every variant marker.
+fn variants(source: &str) -> Vec<ShaderDefines> {
+    let mut res = vec![ShaderDefines::default()];
+    for line in source.lines().map(str::trim) {
+        match line.strip_prefix(VARIANT_MARKER) {
+            Some(rest) if rest.starts_with(' ') => {
+                res.push(rest.split_whitespace().collect());
+            }
+            _ => {}
+        }
+    }
+    res
+}
+
+fn compile(
+    preprocessor: &Preprocessor,
+    validator: &mut Validator,
+    writer: &mut spv::Writer,
+    path: &Path,
+    source: &str,
+    defines: &ShaderDefines,
+) -> Result<(Vec<u32>, Reflection), String> {
+    let source = preprocessor
+        .process(path, source, defines)
+        .map_err(|err| err.to_string())?;
+    let module =
+        naga::front::wgsl::parse_str(&source.code).map_err(|err| err.message().to_string())?;
+    let info = validator
+        .validate(&module)
+        .map_err(|err| err.as_inner().to_string())?;
+    let mut words = vec![];
+    writer
+        .write(&module, &info, None, &mut words)
+        .map_err(|err| err.to_string())?;
+    Ok((words, Reflection::from_module(&module, &info)))
+}
@@ -2,28 +2,140 @@ use std::path::Path;
 
 mod raycast;
 use raycast::RaycastPipeline;
-use vokselis::{run, Camera, Demo, PipelineHandle, VolumeTexture};
+use vokselis::{
+    run, AssetHandle, Camera, Demo, Mesh, MeshPipeline, MeshUniform, PipelineHandle, ShaderParams,
+    ShaderRoots, TransferFunction, TransferFunctionTexture, VolumeTexture,
+};
+
+#[cfg(feature = "ui")]
+use vokselis::egui;
 
 use color_eyre::eyre::Result;
-use winit::{dpi::LogicalSize, event_loop::EventLoop, window::WindowBuilder};
+use glam::{Mat4, Quat, Vec3};
+use winit::{
+    dpi::LogicalSize,
+    event::{ElementState, KeyboardInput, WindowEvent},
+    event_loop::EventLoop,
+    window::WindowBuilder,
+};
 
 struct Bonsai {
-    volume_texture: VolumeTexture,
+    volume_texture: AssetHandle<VolumeTexture>,
+    transfer_function: AssetHandle<TransferFunctionTexture>,
     pipeline: PipelineHandle<RaycastPipeline>,
+    params: AssetHandle<ShaderParams>,
+    needle: Mesh,
+    mesh_pipeline: PipelineHandle<MeshPipeline>,
 }
 
 impl Demo for Bonsai {
     fn init(ctx: &mut vokselis::Context) -> Self {
-        let volume_texture = VolumeTexture::new(&ctx.device, &ctx.queue);
-        let path = Path::new("shaders/raycast_naive.wgsl");
+        // Reloaded when the file changes, the embedded copy is used until it exists
+        let path = Path::new("bonsai_256x256x256_uint8.raw");
+        let volume_texture = VolumeTexture::from_path(&ctx.device, &ctx.queue, path)
+            .unwrap_or_else(|err| {
+                eprintln!("{err}");
+                VolumeTexture::new(&ctx.device, &ctx.queue)
+            });
+        let volume_texture = ctx.watcher.register_asset(&path, volume_texture);
+
+        let path = Path::new("assets/bonsai.tf");
+        let transfer_function = TransferFunction::load(path).unwrap_or_else(|err| {
+            eprintln!("{err}");
+            TransferFunction::parse(path, include_bytes!("../../assets/bonsai.tf")).unwrap()
+        });
+        let transfer_function =
+            TransferFunctionTexture::new(&ctx.device, &ctx.queue, &transfer_function);
+        let transfer_function = ctx.watcher.register_asset(&path, transfer_function);
+
+        let path = Path::new("raycast_naive.wgsl");
         let pipeline = RaycastPipeline::from_path(&ctx.device, path, &mut ctx.shader_compiler);
         let pipeline = ctx.watcher.register(&path, pipeline);
+
+        let roots = ctx.shader_compiler.roots();
+        let params = ShaderParams::new(&ctx.device, path, roots)
+            .unwrap_or_else(|err| {
+                eprintln!("Failed to read the params of the raycast shader: {err}");
+                ShaderParams::embedded(&ctx.device, path, roots)
+            })
+            .with_config("assets/bonsai.params");
+        println!("Raycast params, Tab to select, [ and ] to adjust:\n{params}");
+        let params = params.register(&mut ctx.watcher);
+
+        let needle = Mesh::from_obj(&ctx.device, Path::new("assets/needle.obj")).unwrap();
+        let path = Path::new("mesh.wgsl");
+        let mesh_pipeline = MeshPipeline::from_path(&ctx.device, path, &mut ctx.shader_compiler);
+        let mesh_pipeline = ctx.watcher.register(&path, mesh_pipeline);
+
         Self {
             volume_texture,
+            transfer_function,
             pipeline,
+            params,
+            needle,
+            mesh_pipeline,
+        }
+    }
+
+    fn update(&mut self, ctx: &mut vokselis::Context) {
+        // Slowly push the needle in and out of the volume
+        let depth = (ctx.global_uniform.time * 0.5).sin() * 0.15;
+        let rotation = Quat::from_rotation_z(-0.6) * Quat::from_rotation_y(0.4);
+        let translation = Vec3::new(0.5, 0.5, 0.5) + rotation * Vec3::new(depth, 0., 0.);
+        let model = Mat4::from_rotation_translation(rotation, translation);
+        self.needle.update(
+            &ctx.queue,
+            &MeshUniform {
+                model: model.to_cols_array_2d(),
+                ..Default::default()
+            },
+        );
+        self.params.borrow_mut().update(&ctx.queue);
+    }
+
+    fn update_input(&mut self, event: WindowEvent) {
+        if let WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(keycode),
+                    ..
+                },
+            ..
+        } = event
+        {
+            let mut params = self.params.borrow_mut();
+            if params.handle_key(keycode) {
+                if let Some((name, value)) = params.selected() {
+                    println!("{name} = {value}");
+                }
+            }
         }
     }
 
+    #[cfg(feature = "ui")]
+    fn ui(&mut self, ctx: &egui::Context) {
+        let mut params = self.params.borrow_mut();
+        egui::Window::new("Raycast").show(ctx, |ui| {
+            for param in params.params().to_vec() {
+                let mut values = params.get(&param.name).unwrap_or_default().to_vec();
+                let mut changed = false;
+                ui.horizontal(|ui| {
+                    for value in &mut values {
+                        let drag = egui::DragValue::new(value)
+                            .clamp_range(param.min..=param.max)
+                            .speed(param.step);
+                        changed |= ui.add(drag).changed();
+                    }
+                    ui.label(&param.name);
+                });
+                if changed {
+                    params.set(&param.name, &values);
+                }
+            }
+        });
+    }
+
     fn render(&mut self, ctx: &vokselis::Context) {
         let mut encoder = ctx
             .device
@@ -32,8 +144,9 @@ impl Demo for Bonsai {
             });
 
         {
+            let mesh_pipeline = self.mesh_pipeline.borrow();
             let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Volume Pass"),
+                label: Some("Mesh Pass"),
                 color_attachments: &[wgpu::RenderPassColorAttachment {
                     view: &ctx.render_backbuffer.texture_view,
                     resolve_target: None,
@@ -42,14 +155,48 @@ impl Demo for Bonsai {
                         store: true,
                     },
                 }],
-                depth_stencil_attachment: None,
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &ctx.depth_texture.texture_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
             });
 
-            self.pipeline.record(
+            mesh_pipeline.record(
                 &mut rpass,
                 &ctx.global_uniform_binding,
                 &ctx.camera_binding,
-                &self.volume_texture.bind_group,
+                &self.needle,
+            );
+        }
+
+        {
+            let pipeline = self.pipeline.borrow();
+            let volume_texture = self.volume_texture.borrow();
+            let transfer_function = self.transfer_function.borrow();
+            let params = self.params.borrow();
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Volume Pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: &ctx.render_backbuffer.texture_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+
+            pipeline.record(
+                &mut rpass,
+                ctx,
+                &volume_texture.bind_group,
+                &params,
+                &transfer_function,
             );
         }
 
@@ -72,5 +219,6 @@ fn main() -> Result<()> {
         (0.5, 0.5, 0.5).into(),
         window_size.width as f32 / window_size.height as f32,
     );
-    run::<Bonsai>(event_loop, window, Some(camera))
+    let shader_roots = ShaderRoots::from_args(std::env::args_os().skip(1));
+    run::<Bonsai>(event_loop, window, Some(camera), shader_roots)
 }
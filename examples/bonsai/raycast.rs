@@ -4,9 +4,10 @@ use wgpu::util::DeviceExt;
 
 use vokselis::{
     camera::CameraBinding,
-    context::{GlobalUniformBinding, HdrBackBuffer, Uniform},
+    context::{DepthTexture, HdrBackBuffer, ShaderParams, Uniform},
     shader_compiler::ShaderCompiler,
-    ReloadablePipeline,
+    Context, FallbackShader, ReloadablePipeline, ShaderDefines, ShaderLayout,
+    TransferFunctionTexture, VolumeTexture,
 };
 
 pub struct RaycastPipeline {
@@ -16,21 +17,37 @@ pub struct RaycastPipeline {
 }
 
 impl RaycastPipeline {
+    /// Bind groups set by [`record`](Self::record)
+    const BIND_GROUPS: [wgpu::BindGroupLayoutDescriptor<'static>; 6] = [
+        Uniform::DESC,
+        CameraBinding::DESC,
+        VolumeTexture::DESC,
+        DepthTexture::DESC_RENDER,
+        ShaderParams::DESC,
+        TransferFunctionTexture::DESC,
+    ];
+
     pub fn from_path(
         device: &wgpu::Device,
         path: &Path,
         shader_compiler: &mut ShaderCompiler,
     ) -> Self {
-        let shader = unsafe {
-            device.create_shader_module_spirv(&wgpu::ShaderModuleDescriptorSpirV {
-                label: path.to_str(),
-                source: shader_compiler.create_shader_module(path).unwrap().into(),
-            })
-        };
-        Self::new_with_module(device, &shader)
+        shader_compiler.pipeline_or_fallback(
+            device,
+            path,
+            &ShaderDefines::default(),
+            &FallbackShader::new()
+                .vertex("vs_main")
+                .fragment("fs_main", 1),
+            |layout, shader| Self::new_with_module(device, layout, shader),
+        )
     }
 
-    pub fn new_with_module(device: &wgpu::Device, module: &wgpu::ShaderModule) -> Self {
+    pub fn new_with_module(
+        device: &wgpu::Device,
+        layout: &ShaderLayout,
+        module: &wgpu::ShaderModule,
+    ) -> Self {
         let vertices = [
             1., 1., 0., 0., 1., 0., 1., 1., 1., 0., 1., 1., 0., 0., 1., 0., 1., 0., 0., 0., 0., 1.,
             1., 0., 1., 0., 0., 1., 1., 1., 1., 0., 1., 0., 0., 1., 1., 0., 0., 0., 0., 0.,
@@ -43,7 +60,7 @@ impl RaycastPipeline {
 
         let vertex_count = vertices.len() / 3;
 
-        let pipeline = Self::make_pipeline(device, module);
+        let pipeline = Self::make_pipeline(device, layout, module);
         Self {
             pipeline,
             vertex_buffer,
@@ -51,47 +68,27 @@ impl RaycastPipeline {
         }
     }
 
-    fn make_pipeline(device: &wgpu::Device, module: &wgpu::ShaderModule) -> wgpu::RenderPipeline {
-        let global_bind_group_layout = device.create_bind_group_layout(&Uniform::DESC);
-        let camera_bind_group_layout = device.create_bind_group_layout(&CameraBinding::DESC);
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Foot BGL"),
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D3,
-                            multisampled: false,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-            });
-        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Screen Pass Layout"),
-            bind_group_layouts: &[
-                &global_bind_group_layout,
-                &camera_bind_group_layout,
-                &texture_bind_group_layout,
-            ],
-            push_constant_ranges: &[],
-        });
+    fn make_pipeline(
+        device: &wgpu::Device,
+        layout: &ShaderLayout,
+        module: &wgpu::ShaderModule,
+    ) -> wgpu::RenderPipeline {
+        let layout = layout
+            .clone()
+            .with_bind_groups(&Self::BIND_GROUPS)
+            .create_pipeline_layout(device, Some("Screen Pass Layout"));
         device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("Raycast Pipeline"),
             layout: Some(&layout),
             fragment: Some(wgpu::FragmentState {
                 module,
                 entry_point: "fs_main",
-                targets: &[HdrBackBuffer::FORMAT.into()],
+                // Volume color is premultiplied by alpha and composited over meshes
+                targets: &[wgpu::ColorTargetState {
+                    format: HdrBackBuffer::FORMAT,
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
             }),
             vertex: wgpu::VertexState {
                 module,
@@ -118,24 +115,33 @@ impl<'a> RaycastPipeline {
     pub fn record<'pass>(
         &'a self,
         rpass: &mut wgpu::RenderPass<'pass>,
-        uniform_bind_group: &'a GlobalUniformBinding,
-        camera_bind_group: &'a CameraBinding,
+        ctx: &'a Context,
         volume_texture: &'a wgpu::BindGroup,
+        params: &'a ShaderParams,
+        transfer_function: &'a TransferFunctionTexture,
     ) where
         'a: 'pass,
     {
         rpass.set_pipeline(&self.pipeline);
         rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
 
-        rpass.set_bind_group(0, &uniform_bind_group.binding, &[]);
-        rpass.set_bind_group(1, &camera_bind_group.bind_group, &[]);
+        rpass.set_bind_group(0, &ctx.global_uniform_binding.binding, &[]);
+        rpass.set_bind_group(1, &ctx.camera_binding.bind_group, &[]);
         rpass.set_bind_group(2, &volume_texture, &[]);
+        rpass.set_bind_group(3, &ctx.depth_texture.render_bind_group, &[]);
+        rpass.set_bind_group(4, &params.bind_group, &[]);
+        rpass.set_bind_group(5, &transfer_function.bind_group, &[]);
         rpass.draw(0..self.vertex_count as _, 0..1);
     }
 }
 
 impl ReloadablePipeline for RaycastPipeline {
-    fn reload(&mut self, device: &wgpu::Device, module: &wgpu::ShaderModule) {
-        self.pipeline = Self::make_pipeline(device, module);
+    fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        module: &wgpu::ShaderModule,
+        layout: &ShaderLayout,
+    ) {
+        self.pipeline = Self::make_pipeline(device, layout, module);
     }
 }
@@ -1,7 +1,7 @@
 use std::path::Path;
 use vokselis::{
-    run, shader_compiler::ShaderCompiler, CameraBinding, Context, Demo, PipelineHandle,
-    ReloadablePipeline, Uniform,
+    run, shader_compiler::ShaderCompiler, CameraBinding, Context, Demo, FallbackShader,
+    PipelineHandle, ReloadablePipeline, ShaderDefines, ShaderLayout, ShaderRoots, Uniform,
 };
 
 use color_eyre::eyre::Result;
@@ -19,30 +19,31 @@ impl BasicPipeline {
         path: &Path,
         compiler: &mut ShaderCompiler,
     ) -> Self {
-        let shader = unsafe {
-            device.create_shader_module_spirv(&wgpu::ShaderModuleDescriptorSpirV {
-                label: path.to_str(),
-                source: compiler.create_shader_module(path).unwrap().into(),
-            })
-        };
-        Self::new_with_module(device, format, &shader)
+        compiler.pipeline_or_fallback(
+            device,
+            path,
+            &ShaderDefines::default(),
+            &FallbackShader::new()
+                .vertex("vs_main")
+                .fragment("fs_main", 1),
+            |layout, shader| Self::new_with_module(device, format, layout, shader),
+        )
     }
 
     pub fn new_with_module(
         device: &wgpu::Device,
         surface_format: wgpu::TextureFormat,
+        layout: &ShaderLayout,
         shader: &wgpu::ShaderModule,
     ) -> Self {
-        let global_bind_group_layout = device.create_bind_group_layout(&Uniform::DESC);
-        let camera_bind_group_layout = device.create_bind_group_layout(&CameraBinding::DESC);
-        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Screen Pass Layout"),
-            bind_group_layouts: &[&global_bind_group_layout, &camera_bind_group_layout],
-            push_constant_ranges: &[],
-        });
+        // Bind groups of the context set in `render`
+        let pipeline_layout = layout
+            .clone()
+            .with_bind_groups(&[Uniform::DESC, CameraBinding::DESC])
+            .create_pipeline_layout(device, Some("Screen Pass Layout"));
         let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("Render with Camera Pipeline"),
-            layout: Some(&layout),
+            layout: Some(&pipeline_layout),
             fragment: Some(wgpu::FragmentState {
                 module: shader,
                 entry_point: "fs_main",
@@ -66,8 +67,13 @@ impl BasicPipeline {
 }
 
 impl ReloadablePipeline for BasicPipeline {
-    fn reload(&mut self, device: &wgpu::Device, module: &wgpu::ShaderModule) {
-        *self = Self::new_with_module(device, self.surface_format, module);
+    fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        module: &wgpu::ShaderModule,
+        layout: &ShaderLayout,
+    ) {
+        *self = Self::new_with_module(device, self.surface_format, layout, module);
     }
 }
 
@@ -77,13 +83,10 @@ struct BasicTrig {
 
 impl Demo for BasicTrig {
     fn init(ctx: &mut Context) -> Self {
-        let path = Path::new("shaders/shader_with_camera.wgsl");
-        let pipeline = BasicPipeline::from_path(
-            &ctx.device,
-            ctx.render_backbuffer.format(),
-            path,
-            &mut ctx.shader_compiler,
-        );
+        let path = Path::new("shader_with_camera.wgsl");
+        let format = ctx.render_backbuffer.format();
+        let pipeline =
+            BasicPipeline::from_path(&ctx.device, format, path, &mut ctx.shader_compiler);
         let pipeline = ctx.watcher.register(&path, pipeline);
         Self { pipeline }
     }
@@ -96,6 +99,7 @@ impl Demo for BasicTrig {
             });
 
         {
+            let pipeline = self.pipeline.borrow();
             let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Trig Pass"),
                 color_attachments: &[wgpu::RenderPassColorAttachment {
@@ -109,7 +113,7 @@ impl Demo for BasicTrig {
                 depth_stencil_attachment: None,
             });
 
-            rpass.set_pipeline(&self.pipeline.pipeline);
+            rpass.set_pipeline(&pipeline.pipeline);
             rpass.set_bind_group(0, &ctx.global_uniform_binding.binding, &[]);
             rpass.set_bind_group(1, &ctx.camera_binding.bind_group, &[]);
             rpass.draw(0..3, 0..1);
@@ -126,5 +130,6 @@ fn main() -> Result<()> {
         .with_inner_size(LogicalSize::new(1280, 720))
         .build(&event_loop)?;
 
-    run::<BasicTrig>(event_loop, window, None)
+    let shader_roots = ShaderRoots::from_args(std::env::args_os().skip(1));
+    run::<BasicTrig>(event_loop, window, None, shader_roots)
 }
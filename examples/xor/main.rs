@@ -2,7 +2,10 @@ use std::path::Path;
 
 use bytemuck::{Pod, Zeroable};
 use color_eyre::eyre::Result;
-use vokselis::{dispatch_optimal, run, Camera, Demo, HdrBackBuffer, PipelineHandle};
+use vokselis::{
+    dispatch_optimal, run, Camera, Demo, DynamicResolution, HdrBackBuffer, PipelineHandle,
+    ShaderDefines, ShaderRoots,
+};
 use wgpu::util::DeviceExt;
 use winit::{dpi::LogicalSize, event_loop::EventLoop, window::WindowBuilder};
 
@@ -24,12 +27,7 @@ pub struct Offset {
     y: f32,
 }
 
-#[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable)]
-struct TimestampData {
-    start: u64,
-    end: u64,
-}
+vokselis::shader_struct!(Offset as "Offset" { x, y });
 
 struct Xor {
     xor_texture: PipelineHandle<xor_compute::XorCompute>,
@@ -40,39 +38,29 @@ struct Xor {
     offset_buffer_bind_group: wgpu::BindGroup,
     buffer_len: usize,
     aligned_offset: u32,
-
-    timestamp: wgpu::QuerySet,
-    timestamp_period: f32,
-    timestamp_buffer: wgpu::Buffer,
 }
 
 impl Demo for Xor {
     fn init(ctx: &mut vokselis::Context) -> Self {
-        let path = Path::new("shaders/raycast_compute.wgsl");
-        let raycast_single = ctx.watcher.register(
-            &path,
-            raycast::RaycastPipeline::from_path(
-                &ctx.device,
-                &path,
-                &mut ctx.shader_compiler,
-                "single",
-            ),
-        );
-        let path = Path::new("shaders/raycast_compute.wgsl");
-        let raycast_tile = ctx.watcher.register(
-            &path,
-            raycast::RaycastPipeline::from_path(
+        ctx.shader_compiler.register_struct::<Offset>();
+
+        let path = Path::new("raycast_compute.wgsl");
+        let mut register_raycast = |defines: &ShaderDefines| {
+            let pipeline = raycast::RaycastPipeline::from_path(
                 &ctx.device,
-                &path,
+                path,
                 &mut ctx.shader_compiler,
-                "tile",
-            ),
-        );
-        let path = Path::new("shaders/xor.wgsl");
-        let xor_texture = ctx.watcher.register(
-            &path,
-            xor_compute::XorCompute::from_path(&ctx.device, &path, &mut ctx.shader_compiler),
-        );
+                defines,
+            );
+            ctx.watcher.register_variant(&path, defines, pipeline)
+        };
+        let raycast_single = register_raycast(&ShaderDefines::new());
+        let raycast_tile = register_raycast(&ShaderDefines::new().with("TILE"));
+
+        let path = Path::new("xor.wgsl");
+        let xor_texture =
+            xor_compute::XorCompute::from_path(&ctx.device, path, &mut ctx.shader_compiler);
+        let xor_texture = ctx.watcher.register(&path, xor_texture);
 
         let (w, h) = HdrBackBuffer::DEFAULT_RESOLUTION;
         let min_align = ctx.limits.min_storage_buffer_offset_alignment;
@@ -117,32 +105,23 @@ impl Demo for Xor {
             }],
         });
 
-        let timestamp = ctx.device.create_query_set(&wgpu::QuerySetDescriptor {
-            label: None,
-            count: 2,
-            ty: wgpu::QueryType::Timestamp,
-        });
-        let timestamp_period = ctx.queue.get_timestamp_period();
-        let timestamp_buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Query Buffer"),
-            size: std::mem::size_of::<TimestampData>() as _,
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-            mapped_at_creation: false,
-        });
-
         println!("Change rendering mode on F1");
 
+        ctx.dynamic_resolution = Some(DynamicResolution::with_fps(60.));
+
         let mut encoder = ctx
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("XOR Update encoder"),
             });
 
+        let xor_compute = xor_texture.borrow();
         let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
             label: Some("XOR Update Pass"),
         });
-        xor_texture.record(&mut cpass, &ctx.global_uniform_binding);
+        xor_compute.record(&mut cpass, &ctx.global_uniform_binding);
         drop(cpass);
+        drop(xor_compute);
         ctx.queue.submit(Some(encoder.finish()));
 
         Self {
@@ -154,35 +133,22 @@ impl Demo for Xor {
             aligned_offset,
             offset_buffer_bind_group,
             buffer_len,
-
-            timestamp,
-            timestamp_period,
-            timestamp_buffer,
         }
     }
 
     fn update(&mut self, ctx: &mut vokselis::Context) {
         if ctx.global_uniform.frame % 100 == 0 {
-            let _ = self
-                .timestamp_buffer
-                .slice(..)
-                .map_async(wgpu::MapMode::Read);
-            {
-                ctx.device.poll(wgpu::Maintain::Wait);
-                let timestamp_view = self
-                    .timestamp_buffer
-                    .slice(..std::mem::size_of::<TimestampData>() as wgpu::BufferAddress)
-                    .get_mapped_range();
-                let timestamp_data: &TimestampData = bytemuck::from_bytes(&*timestamp_view);
-                let nanoseconds =
-                    (timestamp_data.end - timestamp_data.start) as f32 * self.timestamp_period;
-                let time_period = std::time::Duration::from_nanos(nanoseconds as _);
+            let profiler = ctx.profiler.borrow();
+            let raycast = profiler
+                .last_frame()
+                .and_then(|frame| frame.scopes.iter().find(|x| x.label == "Raycast"));
+            if let Some(raycast) = raycast {
                 eprintln!(
-                    "Time on raycast shader: {:?} ({:?})",
-                    time_period, self.mode
+                    "Time on raycast shader: {:.3}ms ({:?})",
+                    raycast.duration_ms(),
+                    self.mode
                 );
             }
-            self.timestamp_buffer.unmap();
         }
     }
 
@@ -214,30 +180,34 @@ impl Demo for Xor {
                 label: Some("Volume Encoder"),
             });
 
-        encoder.write_timestamp(&self.timestamp, 0);
+        let mut profiler = ctx.profiler.borrow_mut();
+        profiler.begin_scope("Raycast", &mut encoder);
 
+        let xor_texture = self.xor_texture.borrow();
+        let raycast_single = self.raycast_single.borrow();
+        let raycast_tile = self.raycast_tile.borrow();
         let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
             label: Some("Raycast Pass"),
         });
 
         match self.mode {
             Mode::SinglePass => {
-                cpass.set_pipeline(&self.raycast_single.pipeline);
+                cpass.set_pipeline(&raycast_single.pipeline);
 
                 cpass.set_bind_group(0, &ctx.global_uniform_binding.binding, &[]);
                 cpass.set_bind_group(1, &ctx.camera_binding.bind_group, &[]);
-                cpass.set_bind_group(2, &self.xor_texture.storage_bind_group, &[]);
+                cpass.set_bind_group(2, &xor_texture.storage_bind_group, &[]);
                 cpass.set_bind_group(3, &ctx.render_backbuffer.storage_bind_group, &[]);
                 cpass.set_bind_group(4, &self.offset_buffer_bind_group, &[0]);
-                let (width, height) = HdrBackBuffer::DEFAULT_RESOLUTION;
+                let (width, height) = ctx.render_backbuffer.size();
                 cpass.dispatch(dispatch_optimal(width, 8), dispatch_optimal(height, 8), 1);
             }
             Mode::Tile => {
-                cpass.set_pipeline(&self.raycast_tile.pipeline);
+                cpass.set_pipeline(&raycast_tile.pipeline);
 
                 cpass.set_bind_group(0, &ctx.global_uniform_binding.binding, &[]);
                 cpass.set_bind_group(1, &ctx.camera_binding.bind_group, &[]);
-                cpass.set_bind_group(2, &self.xor_texture.storage_bind_group, &[]);
+                cpass.set_bind_group(2, &xor_texture.storage_bind_group, &[]);
                 cpass.set_bind_group(3, &ctx.render_backbuffer.storage_bind_group, &[]);
                 for offset in 0..self.buffer_len {
                     cpass.set_bind_group(
@@ -245,18 +215,18 @@ impl Demo for Xor {
                         &self.offset_buffer_bind_group,
                         &[offset as u32 * self.aligned_offset],
                     );
-                    cpass.dispatch(
-                        dispatch_optimal(TILE_SIZE, 16),
-                        dispatch_optimal(TILE_SIZE, 16),
-                        1,
-                    );
+                    profiler.scope(&format!("Tile {offset}"), &mut cpass, |_, cpass| {
+                        cpass.dispatch(
+                            dispatch_optimal(TILE_SIZE, 16),
+                            dispatch_optimal(TILE_SIZE, 16),
+                            1,
+                        )
+                    });
                 }
             }
         }
         drop(cpass);
-
-        encoder.write_timestamp(&self.timestamp, 1);
-        encoder.resolve_query_set(&self.timestamp, 0..2, &self.timestamp_buffer, 0);
+        profiler.end_scope(&mut encoder);
 
         ctx.queue.submit(Some(encoder.finish()));
     }
@@ -277,5 +247,6 @@ fn main() -> Result<()> {
         (0., 0., 0.).into(),
         window_size.width as f32 / window_size.height as f32,
     );
-    run::<Xor>(event_loop, window, Some(camera))
+    let shader_roots = ShaderRoots::from_args(std::env::args_os().skip(1));
+    run::<Xor>(event_loop, window, Some(camera), shader_roots)
 }
@@ -6,12 +6,11 @@ use vokselis::{
     camera::CameraBinding,
     context::{HdrBackBuffer, Uniform},
     shader_compiler::ShaderCompiler,
-    NonZeroSized, ReloadablePipeline,
+    FallbackShader, NonZeroSized, ReloadablePipeline, ShaderDefines, ShaderLayout,
 };
 
 pub struct RaycastPipeline {
     pub pipeline: wgpu::ComputePipeline,
-    entry_point: String,
 }
 
 impl RaycastPipeline {
@@ -29,67 +28,64 @@ impl RaycastPipeline {
                 count: None,
             }],
         };
+    /// Bind groups set by the demo, the offsets are indexed with a dynamic offset
+    const BIND_GROUPS: [wgpu::BindGroupLayoutDescriptor<'static>; 5] = [
+        Uniform::DESC,
+        CameraBinding::DESC,
+        xor_compute::XorCompute::DESC_COMPUTE,
+        HdrBackBuffer::DESC_COMPUTE,
+        Self::OFFSET_BUFFER_DESC,
+    ];
+
     pub fn from_path(
         device: &wgpu::Device,
         path: &Path,
         shader_compiler: &mut ShaderCompiler,
-        entry_point: &str,
+        defines: &ShaderDefines,
     ) -> Self {
-        let shader = unsafe {
-            device.create_shader_module_spirv(&wgpu::ShaderModuleDescriptorSpirV {
-                label: path.to_str(),
-                source: shader_compiler.create_shader_module(path).unwrap().into(),
-            })
-        };
-        Self::new_with_module(device, &shader, entry_point)
+        shader_compiler.pipeline_or_fallback(
+            device,
+            path,
+            defines,
+            &FallbackShader::new().compute("main"),
+            |layout, shader| Self::new_with_module(device, layout, shader),
+        )
     }
 
     pub fn new_with_module(
         device: &wgpu::Device,
+        layout: &ShaderLayout,
         module: &wgpu::ShaderModule,
-        entry_point: &str,
     ) -> Self {
-        let pipeline = Self::make_pipeline(device, module, entry_point);
-        Self {
-            pipeline,
-            entry_point: entry_point.to_string(),
-        }
+        let pipeline = Self::make_pipeline(device, layout, module);
+        Self { pipeline }
     }
 
     fn make_pipeline(
         device: &wgpu::Device,
+        layout: &ShaderLayout,
         module: &wgpu::ShaderModule,
-        entry_point: &str,
     ) -> wgpu::ComputePipeline {
-        let global_bind_group_layout = device.create_bind_group_layout(&Uniform::DESC);
-        let camera_bind_group_layout = device.create_bind_group_layout(&CameraBinding::DESC);
-        let volume_bind_group_layout =
-            device.create_bind_group_layout(&xor_compute::XorCompute::DESC_COMPUTE);
-        let output_texture_bind_group_layot =
-            device.create_bind_group_layout(&HdrBackBuffer::DESC_COMPUTE);
-        let offset_buffer_bind_group = device.create_bind_group_layout(&Self::OFFSET_BUFFER_DESC);
-        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Compute Raycast Pass Layout"),
-            bind_group_layouts: &[
-                &global_bind_group_layout,
-                &camera_bind_group_layout,
-                &volume_bind_group_layout,
-                &output_texture_bind_group_layot,
-                &offset_buffer_bind_group,
-            ],
-            push_constant_ranges: &[],
-        });
+        let layout = layout
+            .clone()
+            .with_bind_groups(&Self::BIND_GROUPS)
+            .create_pipeline_layout(device, Some("Compute Raycast Pass Layout"));
         device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
             label: Some("Compute Raycast Pipeline"),
             layout: Some(&layout),
             module,
-            entry_point,
+            entry_point: "main",
         })
     }
 }
 
 impl ReloadablePipeline for RaycastPipeline {
-    fn reload(&mut self, device: &wgpu::Device, module: &wgpu::ShaderModule) {
-        self.pipeline = Self::make_pipeline(device, module, &self.entry_point);
+    fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        module: &wgpu::ShaderModule,
+        layout: &ShaderLayout,
+    ) {
+        self.pipeline = Self::make_pipeline(device, layout, module);
     }
 }
@@ -1,7 +1,8 @@
 use std::path::Path;
 
 use vokselis::{
-    shader_compiler::ShaderCompiler, GlobalUniformBinding, ReloadablePipeline, Uniform,
+    shader_compiler::ShaderCompiler, FallbackShader, GlobalUniformBinding, ReloadablePipeline,
+    ShaderDefines, ShaderLayout, Uniform,
 };
 
 #[allow(dead_code)]
@@ -81,16 +82,20 @@ impl XorCompute {
         path: &Path,
         shader_compiler: &mut ShaderCompiler,
     ) -> Self {
-        let shader = unsafe {
-            device.create_shader_module_spirv(&wgpu::ShaderModuleDescriptorSpirV {
-                label: path.to_str(),
-                source: shader_compiler.create_shader_module(path).unwrap().into(),
-            })
-        };
-        Self::new_with_module(device, &shader)
+        shader_compiler.pipeline_or_fallback(
+            device,
+            path,
+            &ShaderDefines::default(),
+            &FallbackShader::new().compute("cs_main"),
+            |layout, shader| Self::new_with_module(device, layout, shader),
+        )
     }
 
-    pub fn new_with_module(device: &wgpu::Device, module: &wgpu::ShaderModule) -> Self {
+    pub fn new_with_module(
+        device: &wgpu::Device,
+        layout: &ShaderLayout,
+        module: &wgpu::ShaderModule,
+    ) -> Self {
         let size = wgpu::Extent3d {
             width: 256,
             height: 256,
@@ -117,7 +122,7 @@ impl XorCompute {
         });
         let normal_view = normal_texture.create_view(&Default::default());
 
-        let pipeline = Self::make_pipeline(device, module);
+        let pipeline = Self::make_pipeline(device, layout, module);
         let storage_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("XOR Compute Bind Group"),
             layout: &device.create_bind_group_layout(&Self::DESC_COMPUTE),
@@ -167,14 +172,16 @@ impl XorCompute {
         }
     }
 
-    fn make_pipeline(device: &wgpu::Device, module: &wgpu::ShaderModule) -> wgpu::ComputePipeline {
-        let global_bind_group_layout = device.create_bind_group_layout(&Uniform::DESC);
-        let storage_texture_layout = device.create_bind_group_layout(&Self::DESC_COMPUTE);
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("XOR Pipeline Layout"),
-            bind_group_layouts: &[&global_bind_group_layout, &storage_texture_layout],
-            push_constant_ranges: &[],
-        });
+    fn make_pipeline(
+        device: &wgpu::Device,
+        layout: &ShaderLayout,
+        module: &wgpu::ShaderModule,
+    ) -> wgpu::ComputePipeline {
+        // Bind groups set by `record`
+        let pipeline_layout = layout
+            .clone()
+            .with_bind_groups(&[Uniform::DESC, Self::DESC_COMPUTE])
+            .create_pipeline_layout(device, Some("XOR Pipeline Layout"));
         device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
             label: Some("Generate XOR Texture"),
             layout: Some(&pipeline_layout),
@@ -201,7 +208,12 @@ impl<'a> XorCompute {
 }
 
 impl ReloadablePipeline for XorCompute {
-    fn reload(&mut self, device: &wgpu::Device, module: &wgpu::ShaderModule) {
-        self.pipeline = Self::make_pipeline(device, module);
+    fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        module: &wgpu::ShaderModule,
+        layout: &ShaderLayout,
+    ) {
+        self.pipeline = Self::make_pipeline(device, layout, module);
     }
 }
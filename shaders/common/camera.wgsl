@@ -0,0 +1,13 @@
+// Generates `vokselis::camera::CameraUniform`
+// @export CameraUniform
+struct Camera {
+    view_pos: vec4<f32>,
+    proj_view: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    // Unjittered `proj_view` of the previous frame
+    prev_proj_view: mat4x4<f32>,
+    target_pos: vec4<f32>,
+    // Subpixel offset in NDC applied to `proj_view`
+    jitter: vec2<f32>,
+    prev_jitter: vec2<f32>,
+};
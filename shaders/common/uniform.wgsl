@@ -0,0 +1,13 @@
+// Generates `vokselis::Uniform`
+// @export
+struct Uniform {
+    pos: vec3<f32>,
+    frame: u32,
+    resolution: vec2<f32>,
+    mouse: vec2<f32>,
+    mouse_pressed: u32,
+    time: f32,
+    time_delta: f32,
+    // Per frame value in `[0, 1)` for offsetting ray start positions
+    jitter: f32,
+};
@@ -0,0 +1,39 @@
+// Built into the crate, changes need a rebuild
+@group(0) @binding(0)
+var font: texture_2d<f32>;
+
+let GLYPH_SIZE: f32 = 8.0;
+let BACKGROUND: u32 = 65535u;
+
+struct Quad {
+    // Top left corner in clip space
+    @location(0) position: vec2<f32>,
+    @location(1) size: vec2<f32>,
+    @location(2) glyph: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) glyph: u32,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, quad: Quad) -> VertexOutput {
+    let uv = vec2<f32>(f32(vertex_index & 1u), f32(vertex_index >> 1u));
+    let position = quad.position + vec2<f32>(uv.x, -uv.y) * quad.size;
+    return VertexOutput(vec4<f32>(position, 0.0, 1.0), uv, quad.glyph);
+}
+
+@fragment
+fn fs_main(vin: VertexOutput) -> @location(0) vec4<f32> {
+    if (vin.glyph == BACKGROUND) {
+        return vec4<f32>(0.05, 0.0, 0.0, 0.8);
+    }
+    let pixel = min(vin.uv * GLYPH_SIZE, vec2<f32>(GLYPH_SIZE - 0.5));
+    let texel = vec2<i32>(i32(vin.glyph) * i32(GLYPH_SIZE) + i32(pixel.x), i32(pixel.y));
+    if (textureLoad(font, texel, 0).r < 0.5) {
+        discard;
+    }
+    return vec4<f32>(1.0, 0.55, 0.5, 1.0);
+}
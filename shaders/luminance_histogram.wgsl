@@ -0,0 +1,125 @@
+// @variant NO_ATOMICS
+
+#include "common/uniform.wgsl"
+
+struct Luminance {
+    average: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> un: Uniform;
+@group(1) @binding(0)
+var src_texture: texture_2d<f32>;
+@group(2) @binding(1)
+var<storage, read_write> luminance: Luminance;
+
+// Log2 luminance range covered by the histogram
+let MIN_LOG_LUM: f32 = -10.0;
+let MAX_LOG_LUM: f32 = 4.0;
+// Eye adaptation speed, bigger is faster
+let ADAPTATION_RATE: f32 = 1.5;
+let EPSILON: f32 = 0.0001;
+
+// Moves the adapted luminance towards the `average` of the current frame
+fn adapt(average: f32) {
+    let dt = clamp(un.time_delta * 0.001, 0.0, 0.1);
+    let adaptation = 1.0 - exp(-dt * ADAPTATION_RATE);
+    luminance.average = luminance.average + (average - luminance.average) * adaptation;
+}
+
+#ifdef NO_ATOMICS
+// Without atomics a single workgroup averages the log luminance of every 4th
+// pixel in both directions instead of building a histogram
+let SAMPLE_STEP: u32 = 4u;
+
+// Sum of the log luminance and the amount of lit pixels per invocation
+var<workgroup> log_sums: array<vec2<f32>, 256>;
+
+@compute @workgroup_size(256, 1, 1)
+fn average_histogram(@builtin(local_invocation_index) local_index: u32) {
+    let dims = vec2<u32>(textureDimensions(src_texture));
+    let columns = (dims.x + SAMPLE_STEP - 1u) / SAMPLE_STEP;
+    let samples = columns * ((dims.y + SAMPLE_STEP - 1u) / SAMPLE_STEP);
+    var sum = vec2<f32>(0.0);
+    for (var i = local_index; i < samples; i = i + 256u) {
+        let pixel = vec2<u32>(i % columns, i / columns) * SAMPLE_STEP;
+        let col = textureLoad(src_texture, vec2<i32>(pixel), 0).rgb;
+        let lum = dot(col, vec3<f32>(0.2126, 0.7152, 0.0722));
+        if (lum >= EPSILON) {
+            sum = sum + vec2<f32>(clamp(log2(lum), MIN_LOG_LUM, MAX_LOG_LUM), 1.0);
+        }
+    }
+    log_sums[local_index] = sum;
+    workgroupBarrier();
+
+    for (var cutoff = 128u; cutoff > 0u; cutoff = cutoff >> 1u) {
+        if (local_index < cutoff) {
+            log_sums[local_index] = log_sums[local_index] + log_sums[local_index + cutoff];
+        }
+        workgroupBarrier();
+    }
+
+    if (local_index == 0u) {
+        let total = log_sums[0];
+        // Black frames adapt to the bottom of the range, like with the histogram
+        adapt(exp2(select(MIN_LOG_LUM, total.x / total.y, total.y > 0.0)));
+    }
+}
+#else
+@group(2) @binding(0)
+var<storage, read_write> histogram: array<atomic<u32>, 256>;
+
+var<workgroup> histogram_shared: array<atomic<u32>, 256>;
+
+fn luminance_to_bin(col: vec3<f32>) -> u32 {
+    let lum = dot(col, vec3<f32>(0.2126, 0.7152, 0.0722));
+    // Bin 0 is reserved for black pixels
+    if (lum < EPSILON) {
+        return 0u;
+    }
+    let log_lum = clamp((log2(lum) - MIN_LOG_LUM) / (MAX_LOG_LUM - MIN_LOG_LUM), 0.0, 1.0);
+    return u32(log_lum * 254.0 + 1.0);
+}
+
+@compute @workgroup_size(16, 16, 1)
+fn build_histogram(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    atomicStore(&histogram_shared[local_index], 0u);
+    workgroupBarrier();
+
+    let dims = vec2<u32>(textureDimensions(src_texture));
+    if (all(global_id.xy < dims)) {
+        let col = textureLoad(src_texture, vec2<i32>(global_id.xy), 0).rgb;
+        atomicAdd(&histogram_shared[luminance_to_bin(col)], 1u);
+    }
+    workgroupBarrier();
+
+    atomicAdd(&histogram[local_index], atomicLoad(&histogram_shared[local_index]));
+}
+
+@compute @workgroup_size(256, 1, 1)
+fn average_histogram(@builtin(local_invocation_index) local_index: u32) {
+    let count = atomicLoad(&histogram[local_index]);
+    atomicStore(&histogram_shared[local_index], count * local_index);
+    // Clear the histogram for the next frame
+    atomicStore(&histogram[local_index], 0u);
+    workgroupBarrier();
+
+    for (var cutoff = 128u; cutoff > 0u; cutoff = cutoff >> 1u) {
+        if (local_index < cutoff) {
+            atomicAdd(&histogram_shared[local_index], atomicLoad(&histogram_shared[local_index + cutoff]));
+        }
+        workgroupBarrier();
+    }
+
+    if (local_index == 0u) {
+        let dims = vec2<f32>(textureDimensions(src_texture));
+        // `count` holds the amount of black pixels on the first invocation
+        let lit_pixels = max(dims.x * dims.y - f32(count), 1.0);
+        let weighted_bin = f32(atomicLoad(&histogram_shared[0])) / lit_pixels - 1.0;
+        adapt(exp2(weighted_bin / 254.0 * (MAX_LOG_LUM - MIN_LOG_LUM) + MIN_LOG_LUM));
+    }
+}
+#endif
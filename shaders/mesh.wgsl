@@ -0,0 +1,51 @@
+#include "common/uniform.wgsl"
+#include "common/camera.wgsl"
+
+// @export MeshUniform
+struct Mesh {
+    model: mat4x4<f32>,
+    color: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> un: Uniform;
+@group(1) @binding(0)
+var<uniform> cam: Camera;
+@group(2) @binding(0)
+var<uniform> mesh: Mesh;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world_pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+};
+
+@vertex
+fn vs_main(vert: VertexInput) -> VertexOutput {
+    let world_pos = mesh.model * vec4(vert.position, 1.0);
+    // Assumes the model matrix has no non-uniform scale
+    let normal = (mesh.model * vec4(vert.normal, 0.0)).xyz;
+    return VertexOutput(cam.proj_view * world_pos, world_pos.xyz, normal);
+}
+
+@fragment
+fn fs_main(vin: VertexOutput) -> @location(0) vec4<f32> {
+    let n = normalize(vin.normal);
+    let v = normalize(cam.view_pos.xyz - vin.world_pos);
+    // Headlight, slightly above the camera
+    let l = normalize(v + vec3(0.0, 0.3, 0.0));
+    let h = normalize(l + v);
+
+    let albedo = mesh.color.rgb;
+    let hemisphere = mix(vec3(0.05, 0.04, 0.03), vec3(0.15, 0.17, 0.2), n.y * 0.5 + 0.5);
+    let diffuse = max(dot(n, l), 0.0);
+    let specular = pow(max(dot(n, h), 0.0), 64.0);
+
+    let color = albedo * (hemisphere + diffuse) + vec3(specular * 0.5);
+    return vec4(color, 1.0);
+}
@@ -0,0 +1,68 @@
+#include "common/uniform.wgsl"
+
+@group(0) @binding(0)
+var<uniform> un: Uniform;
+
+@group(1) @binding(0)
+var src_texture: texture_2d<f32>;
+@group(2) @binding(0)
+var src_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    let vertex_idx = i32(in_vertex_index);
+    let uv = vec2<f32>(f32((vertex_idx << 1u) & 2), f32(vertex_idx & 2));
+    let position = vec4<f32>(uv.x * 2.0 + -1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return VertexOutput(position, uv);
+}
+
+fn tex_sample(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(src_texture, src_sampler, uv, 0.0);
+}
+
+fn luma(col: vec3<f32>) -> f32 {
+    return dot(col, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+let THRESHOLD: f32 = 1.0;
+let KNEE: f32 = 0.5;
+let INTENSITY: f32 = 0.3;
+let RADIUS: f32 = 0.04;
+let SAMPLES: i32 = 48;
+let GOLDEN_ANGLE: f32 = 2.39996323;
+
+fn bright_pass(col: vec3<f32>) -> vec3<f32> {
+    let brightness = max(col.r, max(col.g, col.b));
+    // Quadratic soft knee around the threshold
+    var soft = clamp(brightness - THRESHOLD + KNEE, 0.0, 2.0 * KNEE);
+    soft = soft * soft / (4.0 * KNEE + 0.0001);
+    let contribution = max(soft, brightness - THRESHOLD) / max(brightness, 0.0001);
+    return col * contribution;
+}
+
+@fragment
+fn fs_main(vin: VertexOutput) -> @location(0) vec4<f32> {
+    let col = tex_sample(vin.uv);
+    let dims = vec2<f32>(textureDimensions(src_texture));
+    let aspect = vec2(dims.y / dims.x, 1.0);
+
+    // Single pass approximation: a golden angle spiral of taps
+    var bloom = vec3(0.0);
+    var total_weight = 0.0;
+    for (var i = 0; i < SAMPLES; i = i + 1) {
+        let r = sqrt((f32(i) + 0.5) / f32(SAMPLES));
+        let theta = f32(i) * GOLDEN_ANGLE;
+        let offset = vec2(cos(theta), sin(theta)) * r * RADIUS * aspect;
+        let weight = exp(-4.0 * r * r);
+        bloom += bright_pass(tex_sample(vin.uv + offset).rgb) * weight;
+        total_weight += weight;
+    }
+    bloom /= vec3(total_weight);
+
+    return vec4(col.rgb + bloom * INTENSITY, col.a);
+}
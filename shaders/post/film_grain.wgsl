@@ -0,0 +1,48 @@
+#include "common/uniform.wgsl"
+
+@group(0) @binding(0)
+var<uniform> un: Uniform;
+
+@group(1) @binding(0)
+var src_texture: texture_2d<f32>;
+@group(2) @binding(0)
+var src_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    let vertex_idx = i32(in_vertex_index);
+    let uv = vec2<f32>(f32((vertex_idx << 1u) & 2), f32(vertex_idx & 2));
+    let position = vec4<f32>(uv.x * 2.0 + -1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return VertexOutput(position, uv);
+}
+
+fn tex_sample(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(src_texture, src_sampler, uv, 0.0);
+}
+
+fn luma(col: vec3<f32>) -> f32 {
+    return dot(col, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+let STRENGTH: f32 = 0.05;
+
+fn hash(p: vec3<f32>) -> f32 {
+    var p3 = fract(p * 0.1031);
+    p3 += vec3(dot(p3, p3.zyx + 31.32));
+    return fract((p3.x + p3.y) * p3.z);
+}
+
+@fragment
+fn fs_main(vin: VertexOutput) -> @location(0) vec4<f32> {
+    let col = tex_sample(vin.uv);
+    let noise = hash(vec3(vin.position.xy, f32(un.frame % 1024u))) - 0.5;
+    // Grain is more visible in the midtones
+    let response = 1.0 - abs(luma(col.rgb) * 2.0 - 1.0);
+    let grain = noise * STRENGTH * max(response, 0.25);
+    return vec4(max(col.rgb + grain, vec3(0.0)), col.a);
+}
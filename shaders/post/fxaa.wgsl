@@ -0,0 +1,78 @@
+#include "common/uniform.wgsl"
+
+@group(0) @binding(0)
+var<uniform> un: Uniform;
+
+@group(1) @binding(0)
+var src_texture: texture_2d<f32>;
+@group(2) @binding(0)
+var src_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    let vertex_idx = i32(in_vertex_index);
+    let uv = vec2<f32>(f32((vertex_idx << 1u) & 2), f32(vertex_idx & 2));
+    let position = vec4<f32>(uv.x * 2.0 + -1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return VertexOutput(position, uv);
+}
+
+fn tex_sample(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(src_texture, src_sampler, uv, 0.0);
+}
+
+fn luma(col: vec3<f32>) -> f32 {
+    return dot(col, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+let FXAA_SPAN_MAX: f32 = 8.0;
+let FXAA_REDUCE_MUL: f32 = 0.125;
+let FXAA_REDUCE_MIN: f32 = 0.0078125;
+
+// Luma of tonemapped color, so edges in bright areas are not overweighted
+fn fxaa_luma(col: vec3<f32>) -> f32 {
+    return luma(col / (1.0 + col));
+}
+
+@fragment
+fn fs_main(vin: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(src_texture));
+    let uv = vin.uv;
+
+    let col = tex_sample(uv);
+    let luma_m = fxaa_luma(col.rgb);
+    let luma_nw = fxaa_luma(tex_sample(uv + vec2(-1.0, -1.0) * texel).rgb);
+    let luma_ne = fxaa_luma(tex_sample(uv + vec2(1.0, -1.0) * texel).rgb);
+    let luma_sw = fxaa_luma(tex_sample(uv + vec2(-1.0, 1.0) * texel).rgb);
+    let luma_se = fxaa_luma(tex_sample(uv + vec2(1.0, 1.0) * texel).rgb);
+
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var dir = vec2(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN);
+    let rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * rcp_dir_min, vec2(-FXAA_SPAN_MAX), vec2(FXAA_SPAN_MAX)) * texel;
+
+    let rgb_a = 0.5 * (
+        tex_sample(uv + dir * (1.0 / 3.0 - 0.5)).rgb +
+        tex_sample(uv + dir * (2.0 / 3.0 - 0.5)).rgb
+    );
+    let rgb_b = rgb_a * 0.5 + 0.25 * (
+        tex_sample(uv + dir * -0.5).rgb +
+        tex_sample(uv + dir * 0.5).rgb
+    );
+    let luma_b = fxaa_luma(rgb_b);
+
+    if (luma_b < luma_min || luma_b > luma_max) {
+        return vec4(rgb_a, col.a);
+    }
+    return vec4(rgb_b, col.a);
+}
@@ -0,0 +1,46 @@
+#include "common/uniform.wgsl"
+
+@group(0) @binding(0)
+var<uniform> un: Uniform;
+
+@group(1) @binding(0)
+var src_texture: texture_2d<f32>;
+@group(2) @binding(0)
+var src_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    let vertex_idx = i32(in_vertex_index);
+    let uv = vec2<f32>(f32((vertex_idx << 1u) & 2), f32(vertex_idx & 2));
+    let position = vec4<f32>(uv.x * 2.0 + -1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return VertexOutput(position, uv);
+}
+
+fn tex_sample(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(src_texture, src_sampler, uv, 0.0);
+}
+
+fn luma(col: vec3<f32>) -> f32 {
+    return dot(col, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+let STRENGTH: f32 = 0.6;
+
+@fragment
+fn fs_main(vin: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(src_texture));
+    let col = tex_sample(vin.uv);
+    let blur = (
+        tex_sample(vin.uv + vec2(texel.x, 0.0)) +
+        tex_sample(vin.uv - vec2(texel.x, 0.0)) +
+        tex_sample(vin.uv + vec2(0.0, texel.y)) +
+        tex_sample(vin.uv - vec2(0.0, texel.y))
+    ) * 0.25;
+    let sharpened = col.rgb + (col.rgb - blur.rgb) * STRENGTH;
+    return vec4(max(sharpened, vec3(0.0)), col.a);
+}
@@ -1,16 +1,10 @@
+// @variant SRGB_SURFACE
+
 type float2 = vec2<f32>;
 type float3 = vec3<f32>;
 type float4 = vec4<f32>;
 
-struct Uniform {
-    pos: vec3<f32>,
-    frame: u32,
-    resolution: vec2<f32>,
-    mouse: vec2<f32>,
-    mouse_pressed: u32,
-    time: f32,
-    time_delta: f32,
-};
+#include "common/uniform.wgsl"
 
 @group(0) @binding(0)
 var<uniform> un: Uniform;
@@ -20,6 +14,29 @@ var src_texture: texture_2d<f32>;
 @group(2) @binding(0)
 var src_sampler: sampler;
 
+// @export TonemapUniform
+struct Tonemap {
+    mode: u32,
+    // Exposure compensation in EV stops
+    exposure: f32,
+    // Gamma correction applied before the sRGB encoding
+    gamma: f32,
+    // Smallest luminance mapped to pure white by the extended Reinhard and Uncharted 2 operators
+    white_point: f32,
+    auto_exposure: u32,
+};
+
+// Average scene luminance, written by the auto exposure pass
+// @export
+struct Luminance {
+    average: f32,
+};
+
+@group(3) @binding(0)
+var<uniform> tm: Tonemap;
+@group(3) @binding(1)
+var<storage, read> luminance: Luminance;
+
 fn linear_to_srgb(col: vec4<f32>) -> vec4<f32> {
     let color_linear = col.rgb;
     let selector = ceil(color_linear - 0.0031308);
@@ -34,6 +51,89 @@ fn ACESFilm(x: vec3<f32>) -> vec3<f32> {
     return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), vec3(0.0), vec3(1.0));
 }
 
+fn reinhard(x: vec3<f32>) -> vec3<f32> {
+    return x / (1.0 + x);
+}
+
+fn reinhard_extended(x: vec3<f32>, white: f32) -> vec3<f32> {
+    return x * (1.0 + x / (white * white)) / (1.0 + x);
+}
+
+// http://filmicworlds.com/blog/filmic-tonemapping-operators/
+fn uncharted2_partial(x: vec3<f32>) -> vec3<f32> {
+    let a = 0.15;
+    let b = 0.50;
+    let c = 0.10;
+    let d = 0.20;
+    let e = 0.02;
+    let f = 0.30;
+    return ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f;
+}
+
+fn uncharted2(x: vec3<f32>, white: f32) -> vec3<f32> {
+    let exposure_bias = 2.0;
+    let curr = uncharted2_partial(x * exposure_bias);
+    let white_scale = vec3(1.0) / uncharted2_partial(vec3(white));
+    return curr * white_scale;
+}
+
+// https://iolite-engine.com/blog_posts/minimal_agx_implementation
+fn agx_default_contrast_approx(x: vec3<f32>) -> vec3<f32> {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    return 15.5 * x4 * x2
+        - 40.14 * x4 * x
+        + 31.96 * x4
+        - 6.868 * x2 * x
+        + 0.4298 * x2
+        + 0.1191 * x
+        - 0.00232;
+}
+
+fn agx(x: vec3<f32>) -> vec3<f32> {
+    let agx_mat = mat3x3<f32>(
+        vec3(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+        vec3(0.0784335999999992, 0.878468636469772, 0.0784336),
+        vec3(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+    );
+    let agx_mat_inv = mat3x3<f32>(
+        vec3(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+        vec3(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+        vec3(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var col = agx_mat * x;
+    col = clamp(log2(max(col, vec3(1e-10))), vec3(min_ev), vec3(max_ev));
+    col = (col - min_ev) / (max_ev - min_ev);
+    col = agx_default_contrast_approx(col);
+    col = agx_mat_inv * col;
+    // Back to linear, the result is encoded to sRGB afterwards
+    return pow(max(col, vec3(0.0)), vec3(2.2));
+}
+
+fn tonemap_operator(x: vec3<f32>) -> vec3<f32> {
+    // Must match `TonemapOperator` on the Rust side
+    switch (tm.mode) {
+        case 1u: { return reinhard(x); }
+        case 2u: { return reinhard_extended(x, tm.white_point); }
+        case 3u: { return ACESFilm(x); }
+        case 4u: { return agx(x); }
+        case 5u: { return uncharted2(x, tm.white_point); }
+        default: { return clamp(x, vec3(0.0), vec3(1.0)); }
+    }
+}
+
+fn exposure() -> f32 {
+    var exposure = exp2(tm.exposure);
+    if (tm.auto_exposure != 0u) {
+        // Map the average luminance to middle grey
+        exposure = exposure * 0.18 / max(luminance.average, 0.0001);
+    }
+    return exposure;
+}
+
 fn tex_sample(tex: texture_2d<f32>, uv: vec2<f32>) -> float4 {
     return textureSample(tex, src_sampler, uv);
 }
@@ -108,14 +208,31 @@ struct FragmentOutput {
     @location(1) secnd: vec4<f32>,
 };
 
+fn tonemap(col: vec4<f32>) -> FragmentOutput {
+    var mapped = tonemap_operator(col.rgb * exposure());
+    mapped = pow(mapped, vec3(1.0 / tm.gamma));
+    let graded = vec4(mapped, col.a);
+    let encoded = linear_to_srgb(graded);
+#ifdef SRGB_SURFACE
+    // The surface encodes on write, the screenshot texture is Rgba8Unorm
+    return FragmentOutput(graded, encoded);
+#else
+    return FragmentOutput(encoded, encoded);
+#endif
+}
+
 @fragment
 fn fs_main(vin: VertexOutput) -> FragmentOutput {
     let col = tex_sample(src_texture, vin.uv);
     // let col = texture_quadratic(src_texture, vin.uv);
-    // let col = texture_bicubic(src_texture, vin.uv);
-    let col = vec4(ACESFilm(col.rgb), col.a);
-    let col = linear_to_srgb(col);
-    return FragmentOutput(col, col);
+    return tonemap(col);
+}
+
+// Used when the backbuffer is rendered at a lower resolution than the screen
+@fragment
+fn fs_main_bicubic(vin: VertexOutput) -> FragmentOutput {
+    let col = texture_bicubic(src_texture, vin.uv);
+    return tonemap(col);
 }
 
 @fragment
@@ -1,18 +1,7 @@
-struct Uniform {
-    pos: vec3<f32>,
-    frame: u32,
-    resolution: vec2<f32>,
-    mouse: vec2<f32>,
-    mouse_pressed: u32,
-    time: f32,
-    time_delta: f32,
-};
-
-struct Camera {
-	view_pos: vec4<f32>,
-	proj_view: mat4x4<f32>,
-	inv_proj: mat4x4<f32>,
-};
+// @variant TILE
+
+#include "common/uniform.wgsl"
+#include "common/camera.wgsl"
 
 struct Offset {
 	x: f32,
@@ -59,14 +48,19 @@ fn get_cam(eye: vec3<f32>, tar: vec3<f32>) -> mat3x3<f32> {
     return mat3x3(xaxis, yaxis, zaxis);
 }
 
-fn get_col2(eye: vec3<f32>, dir: vec3<f32>, tmin: f32, tmax: f32, clear_color: vec4<f32>) -> vec4<f32> {
+// http://www.iryoku.com/next-generation-post-processing-in-call-of-duty-advanced-warfare
+fn interleaved_gradient_noise(p: vec2<f32>) -> f32 {
+    return fract(52.9829189 * fract(dot(p, vec2(0.06711056, 0.00583715))));
+}
+
+fn get_col2(eye: vec3<f32>, dir: vec3<f32>, tmin: f32, tmax: f32, clear_color: vec4<f32>, offset: f32) -> vec4<f32> {
     var color = vec4(clear_color.rgb, 0.1);
     let light = vec3(0., -1., 0.);
     let block_size = vec3<f32>(textureDimensions(volume));
     let dt_vec = 1.0 / (block_size * abs(dir));
     let dt_scale = 1.0;
     let dt = dt_scale * max(min(dt_vec.x, min(dt_vec.y, dt_vec.z)), 0.01);
-    for (var t = tmin; t < tmax; t = t + dt) {
+    for (var t = tmin + dt * offset; t < tmax; t = t + dt) {
         var p = eye + t * dir;
         let samp = vec3<i32>((p + 1.) * (block_size / 2.));
         let vol_content = textureLoad(volume, samp);
@@ -122,7 +116,9 @@ fn render(global_id: vec2<u32>, offset_x: f32, offset_y: f32) -> vec4<f32> {
         var t_hit = intersect_box(eye, dir);
         if (t_hit.x < t_hit.y) {
             t_hit.x = max(t_hit.x, 0.0);
-            color = vec4(get_col2(eye, dir, t_hit.x, t_hit.y, clear_color).rgb, 1.);
+            // Dither the ray start to trade banding for noise, resolved by TAA
+            let offset = fract(interleaved_gradient_noise(coord) + un.jitter);
+            color = vec4(get_col2(eye, dir, t_hit.x, t_hit.y, clear_color, offset).rgb, 1.);
         } else {
             color = vec4(clear_color.rgb, 1.);
         }
@@ -130,15 +126,17 @@ fn render(global_id: vec2<u32>, offset_x: f32, offset_y: f32) -> vec4<f32> {
     return color;
 }
 
-@compute @workgroup_size(8, 8, 1)
-fn single(@builtin(global_invocation_id) global_id: vec3<u32>) {
-    let color = render(global_id.xy, 0., 0.);
-    textureStore(out_tex, global_id.xy, color);
-}
-
+#ifdef TILE
 @compute @workgroup_size(16, 16, 1)
-fn tile(@builtin(global_invocation_id) global_id: vec3<u32>) {
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let color = render(global_id.xy, dyn_offset.x, dyn_offset.y);
     let offset = vec2<u32>(vec2(dyn_offset.x, dyn_offset.y));
-    textureStore(out_tex, global_id.xy + offset, color);
+    textureStore(out_tex, vec2<i32>(global_id.xy + offset), color);
+}
+#else
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let color = render(global_id.xy, 0., 0.);
+    textureStore(out_tex, vec2<i32>(global_id.xy), color);
 }
+#endif
@@ -12,21 +12,19 @@ struct VertexOutput {
     @location(1) ray_dir: float3,
 };
 
-struct Uniform {
-    pos: vec3<f32>,
-    frame: u32,
-    resolution: vec2<f32>,
-    mouse: vec2<f32>,
-    mouse_pressed: u32,
-    time: f32,
-    time_delta: f32,
+// Tweaked at runtime, see `ShaderParams`
+struct Params {
+    // Ray step relative to the voxel size
+    dt_scale: f32, // @param min=0.25 max=4 default=1
+    smooth_min: f32, // @param min=0 max=2 default=0.1
+    smooth_max: f32, // @param min=0 max=2 default=1.2
+    alpha_exponent: f32, // @param min=0.5 max=8 default=2 step=0.1
+    // @param min=0 max=1 default=0.1,0.2,0.3,0.01
+    background: float4,
 };
 
-struct Camera {
-	view_pos: vec4<f32>,
-	proj_view: mat4x4<f32>,
-	inv_proj: mat4x4<f32>,
-};
+#include "common/uniform.wgsl"
+#include "common/camera.wgsl"
 
 @group(0) @binding(0)
 var<uniform> un: Uniform;
@@ -36,6 +34,15 @@ var<uniform> cam: Camera;
 var volume: texture_3d<f32>;
 @group(2) @binding(1)
 var tex_sampler: sampler;
+@group(3) @binding(0)
+var depth_texture: texture_depth_2d;
+@group(4) @binding(0)
+var<uniform> params: Params;
+// Color and opacity by sample, see `TransferFunctionTexture`
+@group(5) @binding(0)
+var transfer_function: texture_2d<f32>;
+@group(5) @binding(1)
+var tf_sampler: sampler;
 
 @vertex
 fn vs_main(vert: VertexInput) -> VertexOutput {
@@ -60,6 +67,11 @@ fn intersect_box(orig: float3, dir: float3) -> float2 {
     return float2(t0, t1);
 }
 
+// http://www.iryoku.com/next-generation-post-processing-in-call-of-duty-advanced-warfare
+fn interleaved_gradient_noise(p: float2) -> f32 {
+    return fract(52.9829189 * fract(dot(p, float2(0.06711056, 0.00583715))));
+}
+
 fn linear_to_srgb(x: f32) -> f32 {
     if (x <= 0.0031308) {
         return 12.92 * x;
@@ -67,17 +79,14 @@ fn linear_to_srgb(x: f32) -> f32 {
     return 1.055 * pow(x, 1.0 / 2.4) - 0.055;
 }
 
-let TAU: f32 = 6.28318;
-fn palette(t: f32, a: float3, b: float3, c: float3, d: float3) -> float3 {
-    return a + b * cos(TAU * (c * t + d));
-}
-
-fn vertigo(t: f32) -> float3 {
-    let a = vec3<f32>(0.5);
-    let b = vec3<f32>(0.5);
-    let c = vec3<f32>(1.0, 1.7, 0.4);
-    let d = vec3<f32>(0.0, 0.15, 0.20);
-    return palette(t, a, b, c, d);
+// Distance along the ray to the opaque geometry in the depth buffer
+fn depth_distance(frag_coord: float2, eye: float3) -> f32 {
+    let dims = vec2<f32>(textureDimensions(depth_texture));
+    let depth = textureLoad(depth_texture, vec2<i32>(frag_coord), 0);
+    let uv = frag_coord / dims;
+    let ndc = float4(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world = cam.inv_proj * ndc;
+    return distance(eye, world.xyz / world.w);
 }
 
 @fragment
@@ -85,30 +94,33 @@ fn fs_main(vin: VertexOutput) -> @location(0) float4 {
     var ray_dir = normalize(vin.ray_dir);
     let eye = vin.transformed_eye;
 
-    let background = vec4<f32>(0.1, 0.2, 0.3, 0.01);
+    let background = params.background;
 
     var t_hit = intersect_box(eye, ray_dir);
+    t_hit.y = min(t_hit.y, depth_distance(vin.position.xy, eye));
     if (t_hit.x > t_hit.y) {
-        return vec4<f32>(0., 0., 0., 1.);
+        return vec4<f32>(0.);
     }
     t_hit.x = max(t_hit.x, 0.0);
 
     var color = vec4<f32>(0.0);
     let dt_vec = 1.0 / (vec3<f32>(256.0) * abs(ray_dir));
-    let dt_scale = 1.0;
-    let dt = dt_scale * min(dt_vec.x, min(dt_vec.y, dt_vec.z));
-    var p = eye + t_hit.x * ray_dir;
-    for (var t = t_hit.x; t < t_hit.y; t = t + dt) {
+    let dt = params.dt_scale * min(dt_vec.x, min(dt_vec.y, dt_vec.z));
+    // Dither the ray start to trade banding for noise, resolved by TAA
+    let offset = fract(interleaved_gradient_noise(vin.position.xy) + un.jitter);
+    let t_start = t_hit.x + dt * offset;
+    var p = eye + t_start * ray_dir;
+    for (var t = t_start; t < t_hit.y; t = t + dt) {
         let tex_content = textureSampleLevel(volume, tex_sampler, p, 0.0);
         var val = tex_content.rgb;
-        let val_alpha = pow(tex_content.a, 2.0);
+        let val_alpha = pow(tex_content.a, params.alpha_exponent);
 
         val = clamp(vec3<f32>(0.4), vec3<f32>(.9), val);
-        val = smoothstep(vec3<f32>(0.10), vec3<f32>(1.2), val);
-        var val_color = vec4<f32>(vertigo(val.r), val.r);
+        val = smoothstep(vec3<f32>(params.smooth_min), vec3<f32>(params.smooth_max), val);
+        var val_color = textureSampleLevel(transfer_function, tf_sampler, float2(val.r, 0.5), 0.0);
 
 		// Opacity correction
-        // val_color.a = 1.0 - pow(1.0 - val_color.a, dt_scale);
+        // val_color.a = 1.0 - pow(1.0 - val_color.a, params.dt_scale);
         var tmp = color.rgb + (1.0 - color.a) * val_color.a * val_color.xyz + background.rgb * background.a * (1. - val_alpha);
         color = vec4<f32>(tmp, color.a);
         color.a = color.a + (1.0 - color.a) * val_color.a;
@@ -121,5 +133,5 @@ fn fs_main(vin: VertexOutput) -> @location(0) float4 {
     color.r = linear_to_srgb(color.r);
     color.g = linear_to_srgb(color.g);
     color.b = linear_to_srgb(color.b);
-    return vec4<f32>(color.rgb, 1.);
+    return color;
 }
@@ -1,12 +1,4 @@
-struct Uniform {
-    pos: vec3<f32>,
-    frame: u32,
-    resolution: vec2<f32>,
-    mouse: vec2<f32>,
-    mouse_pressed: u32,
-    time: f32,
-    time_delta: f32,
-};
+#include "common/uniform.wgsl"
 
 @group(0) @binding(0)
 var<uniform> un: Uniform;
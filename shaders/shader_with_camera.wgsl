@@ -1,18 +1,5 @@
-struct Uniform {
-    pos: vec3<f32>,
-    frame: u32,
-    resolution: vec2<f32>,
-    mouse: vec2<f32>,
-    mouse_pressed: u32,
-    time: f32,
-    time_delta: f32,
-};
-
-struct Camera {
-	view_pos: vec3<f32>,
-	proj_view: mat4x4<f32>,
-	inv_proj: mat4x4<f32>,
-};
+#include "common/uniform.wgsl"
+#include "common/camera.wgsl"
 
 @group(0) @binding(0)
 var<uniform> un: Uniform;
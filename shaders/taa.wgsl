@@ -0,0 +1,92 @@
+#include "common/uniform.wgsl"
+#include "common/camera.wgsl"
+
+@group(0) @binding(0)
+var<uniform> un: Uniform;
+@group(1) @binding(0)
+var<uniform> cam: Camera;
+@group(2) @binding(0)
+var current_texture: texture_2d<f32>;
+@group(3) @binding(0)
+var history_texture: texture_2d<f32>;
+@group(3) @binding(1)
+var history_sampler: sampler;
+
+// @export TaaUniform
+struct Taa {
+    // 0 on the first frame after a reset, which only takes the current sample
+    history_valid: u32,
+};
+
+@group(3) @binding(2)
+var<uniform> taa: Taa;
+
+// Weight of the current frame in the accumulated result
+let BLEND_FACTOR: f32 = 0.1;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    let vertex_idx = i32(in_vertex_index);
+    let uv = vec2<f32>(f32((vertex_idx << 1u) & 2), f32(vertex_idx & 2));
+    let position = vec4<f32>(uv.x * 2.0 + -1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return VertexOutput(position, uv);
+}
+
+fn rgb_to_ycocg(c: vec3<f32>) -> vec3<f32> {
+    return vec3(
+        0.25 * c.r + 0.5 * c.g + 0.25 * c.b,
+        0.5 * c.r - 0.5 * c.b,
+        -0.25 * c.r + 0.5 * c.g - 0.25 * c.b,
+    );
+}
+
+fn ycocg_to_rgb(c: vec3<f32>) -> vec3<f32> {
+    return vec3(c.x + c.y - c.z, c.x + c.z, c.x - c.y - c.z);
+}
+
+// Volumes have no depth, so the world position is reconstructed
+// on the sphere through the camera target
+fn reproject(uv: vec2<f32>) -> vec2<f32> {
+    let ndc = vec2(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    let near = cam.inv_proj * vec4(ndc, 0.0, 1.0);
+    let eye = cam.view_pos.xyz;
+    let dir = normalize(near.xyz / near.w - eye);
+    let world = eye + dir * distance(eye, cam.target_pos.xyz);
+
+    let prev_clip = cam.prev_proj_view * vec4(world, 1.0);
+    let prev_ndc = prev_clip.xy / prev_clip.w;
+    return vec2(prev_ndc.x * 0.5 + 0.5, 0.5 - prev_ndc.y * 0.5);
+}
+
+@fragment
+fn fs_main(vin: VertexOutput) -> @location(0) vec4<f32> {
+    let dims = textureDimensions(current_texture);
+    let coord = vec2<i32>(vin.position.xy);
+    let current = textureLoad(current_texture, coord, 0);
+
+    // Neighbourhood clamping to reject stale history
+    var color_min = vec3(1e10);
+    var color_max = vec3(-1e10);
+    for (var y = -1; y <= 1; y = y + 1) {
+        for (var x = -1; x <= 1; x = x + 1) {
+            let p = clamp(coord + vec2(x, y), vec2(0), dims - 1);
+            let c = rgb_to_ycocg(textureLoad(current_texture, p, 0).rgb);
+            color_min = min(color_min, c);
+            color_max = max(color_max, c);
+        }
+    }
+
+    let prev_uv = reproject(vin.uv);
+    if (taa.history_valid == 0u || any(prev_uv < vec2(0.0)) || any(prev_uv > vec2(1.0))) {
+        return current;
+    }
+
+    let history = textureSampleLevel(history_texture, history_sampler, prev_uv, 0.0);
+    let history_rgb = ycocg_to_rgb(clamp(rgb_to_ycocg(history.rgb), color_min, color_max));
+    return vec4(mix(history_rgb, current.rgb, BLEND_FACTOR), current.a);
+}
@@ -1,12 +1,4 @@
-struct Uniform {
-    pos: vec3<f32>,
-    frame: u32,
-    resolution: vec2<f32>,
-    mouse: vec2<f32>,
-    mouse_pressed: u32,
-    time: f32,
-    time_delta: f32,
-};
+#include "common/uniform.wgsl"
 
 @group(0) @binding(0)
 var<uniform> un: Uniform;
@@ -73,6 +65,6 @@ fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let vol = noise_volume(coord);
     let normal = gradient(coord, 0.0001);
 
-    textureStore(xor_tex, global_id, vec4<f32>(vol.rgb / 2., vol.a));
-    textureStore(normal_tex, global_id, vec4<f32>(normal, length(normal)));
+    textureStore(xor_tex, vec3<i32>(global_id), vec4<f32>(vol.rgb / 2., vol.a));
+    textureStore(normal_tex, vec3<i32>(global_id), vec4<f32>(normal, length(normal)));
 }
@@ -0,0 +1,240 @@
+//! Validates shaders without opening a window.
+//!
+//! Every `.wgsl` file under the given directories is preprocessed, parsed and
+//! validated like the demos do at runtime. The process exits with a non-zero
+//! code if any of them fails, so it can run from pre-commit hooks.
+
+use std::{
+    ffi::OsStr,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use naga::{back, valid::ModuleInfo, Module, ShaderStage};
+use vokselis::{
+    context::register_shader_structs,
+    shader_compiler::{CompilerError, ShaderCompiler},
+    ShaderDefines, ShaderRoots,
+};
+
+const USAGE: &str = "\
+Validates every shader under the given directories, `shaders` by default.
+
+USAGE:
+    vokselis-shaderc [OPTIONS] [PATH]...
+
+OPTIONS:
+    -D, --define <NAME>     Compiles the variant with NAME defined, may be repeated
+        --shaders <DIR>     Resolves includes in DIR as well, may be repeated
+        --spirv <DIR>       Writes SPIR-V of every shader to DIR
+        --glsl <DIR>        Writes GLSL of every entry point to DIR
+        --msl <DIR>         Writes MSL of every shader to DIR
+    -h, --help              Prints this message
+
+Files passed directly are resolved against the shader roots like the demos do.";
+
+enum Failure {
+    Compile(CompilerError),
+    /// Translating or writing the output failed
+    Output(String),
+}
+
+#[derive(Default)]
+struct Args {
+    paths: Vec<PathBuf>,
+    roots: Vec<PathBuf>,
+    defines: ShaderDefines,
+    spirv: Option<PathBuf>,
+    glsl: Option<PathBuf>,
+    msl: Option<PathBuf>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut res = Self::default();
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value)),
+                _ => (arg.clone(), None),
+            };
+            let mut value = || match inline {
+                Some(value) => Ok(value.to_string()),
+                None => args.next().ok_or_else(|| format!("{flag} expects a value")),
+            };
+            match flag.as_str() {
+                "-h" | "--help" => return Err(String::new()),
+                "-D" | "--define" => {
+                    res.defines.insert(value()?);
+                }
+                ShaderRoots::CLI_FLAG => res.roots.push(value()?.into()),
+                "--spirv" => res.spirv = Some(value()?.into()),
+                "--glsl" => res.glsl = Some(value()?.into()),
+                "--msl" => res.msl = Some(value()?.into()),
+                _ if flag.starts_with("-D") && flag.len() > 2 => {
+                    res.defines.insert(&flag[2..]);
+                }
+                _ if flag.starts_with('-') => return Err(format!("Unknown option {flag}")),
+                _ => res.paths.push(arg.into()),
+            }
+        }
+        if res.paths.is_empty() {
+            res.paths.push(PathBuf::from("shaders"));
+        }
+        Ok(res)
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) if err.is_empty() => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let roots = ShaderRoots::from_env(&args.roots);
+    let mut checked = 0;
+    let mut failed = 0;
+    for input in &args.paths {
+        // Directories are roots of their own, shaders are named relative to them
+        let (mut compiler, shaders) = if input.is_dir() {
+            let mut shaders = vec![];
+            if let Err(err) = collect_shaders(input, input, &mut shaders) {
+                eprintln!("Failed to read {}: {err}", input.display());
+                failed += 1;
+                continue;
+            }
+            shaders.sort();
+            let mut dir_roots = ShaderRoots::new([input]);
+            for root in roots.roots() {
+                dir_roots.push(root);
+            }
+            (ShaderCompiler::with_roots(dir_roots), shaders)
+        } else {
+            let compiler = ShaderCompiler::with_roots(roots.clone());
+            (compiler, vec![input.clone()])
+        };
+        // Output has to reflect the sources on disk
+        compiler.set_disk_cache(None);
+        register_shader_structs(&mut compiler);
+
+        for path in shaders {
+            checked += 1;
+            if let Err(failure) = check(&mut compiler, &path, &args) {
+                match failure {
+                    Failure::Compile(err) => err.emit_to_stderr(),
+                    Failure::Output(err) => eprintln!("{err}"),
+                }
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        eprintln!("{failed} of {checked} shaders failed");
+        ExitCode::FAILURE
+    } else {
+        println!("{checked} shaders are valid");
+        ExitCode::SUCCESS
+    }
+}
+
+/// Validates the shader at `path` and writes the requested translations.
+fn check(compiler: &mut ShaderCompiler, path: &Path, args: &Args) -> Result<(), Failure> {
+    let (module, info) = compiler
+        .validate(path, &args.defines)
+        .map_err(Failure::Compile)?;
+    let name = path.strip_prefix("./").unwrap_or(path);
+    let fail = |what: &str, err: &dyn Display| {
+        Failure::Output(format!("{}: {what}: {err}", path.display()))
+    };
+
+    if let Some(ref dir) = args.spirv {
+        let words = compiler
+            .write_spirv(&module, &info)
+            .map_err(|err| fail("SPIR-V", &err))?;
+        write(
+            &dir.join(name).with_extension("spv"),
+            bytemuck::cast_slice(&words),
+        )
+        .map_err(|err| fail("SPIR-V", &err))?;
+    }
+    if let Some(ref dir) = args.glsl {
+        for (entry_point, stage, code) in glsl(&module, &info).map_err(|err| fail("GLSL", &err))? {
+            let extension = match stage {
+                ShaderStage::Vertex => "vert",
+                ShaderStage::Fragment => "frag",
+                ShaderStage::Compute => "comp",
+            };
+            let out = dir
+                .join(name)
+                .with_extension(format!("{entry_point}.{extension}"));
+            write(&out, code.as_bytes()).map_err(|err| fail("GLSL", &err))?;
+        }
+    }
+    if let Some(ref dir) = args.msl {
+        let options = back::msl::Options::default();
+        let (code, _) = back::msl::write_string(&module, &info, &options, &Default::default())
+            .map_err(|err| fail("MSL", &err))?;
+        write(&dir.join(name).with_extension("metal"), code.as_bytes())
+            .map_err(|err| fail("MSL", &err))?;
+    }
+    Ok(())
+}
+
+/// GLSL ES of every entry point, the dialect of wgpu's GL backend.
+fn glsl(
+    module: &Module,
+    info: &ModuleInfo,
+) -> Result<Vec<(String, ShaderStage, String)>, back::glsl::Error> {
+    let options = back::glsl::Options::default();
+    module
+        .entry_points
+        .iter()
+        .map(|entry_point| {
+            let pipeline_options = back::glsl::PipelineOptions {
+                shader_stage: entry_point.stage,
+                entry_point: entry_point.name.clone(),
+                multiview: None,
+            };
+            let mut code = String::new();
+            let mut writer = back::glsl::Writer::new(
+                &mut code,
+                module,
+                info,
+                &options,
+                &pipeline_options,
+                Default::default(),
+            )?;
+            writer.write()?;
+            Ok((entry_point.name.clone(), entry_point.stage, code))
+        })
+        .collect()
+}
+
+/// Paths of the shaders under `dir`, relative to `root`.
+fn collect_shaders(root: &Path, dir: &Path, shaders: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_shaders(root, &path, shaders)?;
+        } else if path.extension() == Some(OsStr::new("wgsl")) {
+            shaders.push(path.strip_prefix(root).unwrap().to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+fn write(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)
+}
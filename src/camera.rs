@@ -1,21 +1,19 @@
 use crate::utils::NonZeroSized;
-use glam::{Mat4, Vec3};
+use glam::{Mat4, Vec2, Vec3};
 use wgpu::util::DeviceExt;
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct CameraUniform {
-    pub view_position: [f32; 4],
-    pub proj_view: [[f32; 4]; 4],
-    pub inv_proj: [[f32; 4]; 4],
-}
+pub use crate::shader_types::CameraUniform;
 
 impl Default for CameraUniform {
     fn default() -> Self {
         Self {
-            view_position: [0.0; 4],
+            view_pos: [0.0; 4],
             proj_view: Mat4::IDENTITY.to_cols_array_2d(),
             inv_proj: Mat4::IDENTITY.to_cols_array_2d(),
+            prev_proj_view: Mat4::IDENTITY.to_cols_array_2d(),
+            target_pos: [0.0; 4],
+            jitter: [0.0; 2],
+            prev_jitter: [0.0; 2],
         }
     }
 }
@@ -23,6 +21,9 @@ impl Default for CameraUniform {
 pub struct CameraBinding {
     pub buffer: wgpu::Buffer,
     pub bind_group: wgpu::BindGroup,
+    uniform: CameraUniform,
+    prev_proj_view: Mat4,
+    prev_jitter: Vec2,
 }
 
 impl CameraBinding {
@@ -56,16 +57,26 @@ impl CameraBinding {
             }],
         });
 
-        Self { buffer, bind_group }
+        Self {
+            buffer,
+            bind_group,
+            uniform: CameraUniform::default(),
+            prev_proj_view: Mat4::IDENTITY,
+            prev_jitter: Vec2::ZERO,
+        }
     }
 
+    /// Must be called once per frame to keep the previous frame matrices valid.
     pub fn update(&mut self, queue: &wgpu::Queue, camera: &mut Camera) {
-        if camera.updated {
-            queue.write_buffer(
-                &self.buffer,
-                0,
-                bytemuck::bytes_of(&camera.get_proj_view_matrix()),
-            );
+        let mut uniform = camera.get_proj_view_matrix();
+        uniform.prev_proj_view = self.prev_proj_view.to_cols_array_2d();
+        uniform.prev_jitter = self.prev_jitter.to_array();
+        self.prev_proj_view = camera.build_unjittered_projection_view_matrix();
+        self.prev_jitter = camera.jitter;
+
+        if camera.updated || bytemuck::bytes_of(&uniform) != bytemuck::bytes_of(&self.uniform) {
+            queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+            self.uniform = uniform;
             camera.updated = false;
         }
     }
@@ -80,6 +91,8 @@ pub struct Camera {
     pub yaw: f32,
     pub up: Vec3,
     pub aspect: f32,
+    /// Subpixel projection offset in NDC
+    pub jitter: Vec2,
 
     updated: bool,
 }
@@ -99,6 +112,7 @@ impl Camera {
             target,
             up: Self::UP,
             aspect,
+            jitter: Vec2::ZERO,
 
             updated: false,
         };
@@ -107,11 +121,23 @@ impl Camera {
     }
 
     pub fn build_projection_view_matrix(&self) -> Mat4 {
+        Mat4::from_translation(self.jitter.extend(0.))
+            * self.build_unjittered_projection_view_matrix()
+    }
+
+    pub fn build_unjittered_projection_view_matrix(&self) -> Mat4 {
         let view = Mat4::look_at_rh(self.eye, self.target, self.up);
         let proj = Mat4::perspective_rh(Self::FOVY, self.aspect, Self::ZNEAR, Self::ZFAR);
         proj * view
     }
 
+    pub fn set_jitter(&mut self, jitter: Vec2) {
+        if self.jitter != jitter {
+            self.jitter = jitter;
+            self.updated = true;
+        }
+    }
+
     pub fn set_zoom(&mut self, zoom: f32) {
         self.zoom = zoom.clamp(0.3, Self::ZFAR / 2.);
         self.fix_eye();
@@ -164,9 +190,12 @@ impl Camera {
     pub fn get_proj_view_matrix(&self) -> CameraUniform {
         let proj_view = self.build_projection_view_matrix();
         CameraUniform {
-            view_position: [self.eye.x, self.eye.y, self.eye.z, 1.0],
+            view_pos: [self.eye.x, self.eye.y, self.eye.z, 1.0],
             proj_view: proj_view.to_cols_array_2d(),
             inv_proj: proj_view.inverse().to_cols_array_2d(),
+            target_pos: [self.target.x, self.target.y, self.target.z, 1.0],
+            jitter: self.jitter.to_array(),
+            ..Default::default()
         }
     }
 }
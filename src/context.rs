@@ -1,39 +1,78 @@
 use std::{
+    cell::RefCell,
     path::{Path, PathBuf},
     rc::Rc,
     sync::Arc,
     time::Instant,
 };
 
-use color_eyre::eyre::{eyre, Result};
+use color_eyre::eyre::{bail, eyre, Result};
 use wgpu::Instance;
 use winit::{dpi::PhysicalSize, window::Window};
 
+mod auto_exposure;
+mod depth_texture;
+mod error_overlay;
 mod global_ubo;
+mod gpu_profiler;
 mod hdr_backbuffer;
 #[allow(dead_code)]
 mod pipelines;
+mod post_processing;
 mod present_pipeline;
 mod screenshot;
+mod shader_params;
+mod taa;
+mod tonemapping;
+mod transfer_function_texture;
 mod volume_texture;
 
+use auto_exposure::AutoExposurePipeline;
+pub use depth_texture::DepthTexture;
+use error_overlay::ErrorOverlay;
 pub use hdr_backbuffer::HdrBackBuffer;
+pub use pipelines::mesh::{Mesh, MeshPipeline, MeshUniform};
 use present_pipeline::PresentPipeline;
 
 use crate::{
-    camera::{Camera, CameraBinding},
-    utils::{frame_counter::FrameCounter, shader_compiler::ShaderCompiler},
+    camera::{Camera, CameraBinding, CameraUniform},
+    utils::{
+        dynamic_resolution::DynamicResolution,
+        frame_counter::FrameCounter,
+        reflection::ShaderLayout,
+        shader_compiler::{CompilerError, ShaderBackend, ShaderCompiler},
+        shader_roots::ShaderRoots,
+    },
     utils::{input::Input, ImageDimentions},
-    watcher::Watcher,
+    watcher::{ShaderKey, Watcher, WatcherEvent},
 };
 
 pub use global_ubo::GlobalUniformBinding;
 pub use global_ubo::Uniform;
+pub use gpu_profiler::{GpuFrame, GpuProfiler, GpuScope, ProfilerTarget};
+pub use post_processing::{PostProcessChain, PostProcessEffect, PostProcessPipeline};
+pub use shader_params::ShaderParams;
+pub use taa::{Taa, TaaUniform};
+pub use tonemapping::{Luminance, TonemapBinding, TonemapOperator, TonemapUniform};
+pub use transfer_function_texture::TransferFunctionTexture;
 pub use volume_texture::VolumeTexture;
 
 use screenshot::ScreenshotCtx;
 
-pub type PipelineHandle<T> = Rc<T>;
+/// Pipeline shared between its user and the [Watcher], which rebuilds it in place
+/// when the shader changes.
+///
+/// Reloads happen between frames, so borrows held while recording passes never
+/// overlap with them. Don't keep a borrow alive across frames.
+pub type PipelineHandle<T> = Rc<RefCell<T>>;
+pub type AssetHandle<T> = Rc<RefCell<T>>;
+
+/// The [HdrBackBuffer] is a read-write storage texture in compute passes
+const REQUIRED_FEATURES: wgpu::Features = wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+/// Requested when the adapter has them: GPU timings for the [GpuProfiler]
+/// and SPIR-V handed to the driver as is, see [ShaderBackend]
+const OPTIONAL_FEATURES: wgpu::Features =
+    wgpu::Features::TIMESTAMP_QUERY.union(wgpu::Features::SPIRV_SHADER_PASSTHROUGH);
 
 pub struct Context {
     pub watcher: Watcher,
@@ -52,6 +91,11 @@ pub struct Context {
     pub camera_binding: CameraBinding,
 
     pub render_backbuffer: HdrBackBuffer,
+    pub depth_texture: DepthTexture,
+    pub taa: Taa,
+    pub post_processing: PostProcessChain,
+    render_scale: f32,
+    pub dynamic_resolution: Option<DynamicResolution>,
 
     rgb_texture: wgpu::Texture,
 
@@ -63,19 +107,32 @@ pub struct Context {
     pub global_uniform: Uniform,
     pub global_uniform_binding: GlobalUniformBinding,
 
+    pub tonemap: TonemapUniform,
+    pub tonemap_binding: TonemapBinding,
+    auto_exposure_pipeline: PipelineHandle<AutoExposurePipeline>,
+
     present_pipeline: PipelineHandle<PresentPipeline>,
+    error_overlay: ErrorOverlay,
+
+    /// Borrowed mutably while recording, demos can add scopes in `render`
+    pub profiler: RefCell<GpuProfiler>,
 }
 
 impl Context {
     /// Create a new window with a given `window`
+    ///
+    /// Shaders are looked up in `shader_roots`, which are also watched for changes.
     pub async fn new(
         window: &Window,
-        event_loop: &winit::event_loop::EventLoop<(PathBuf, wgpu::ShaderModule)>,
+        event_loop: &winit::event_loop::EventLoop<WatcherEvent>,
         camera: Option<Camera>,
+        shader_roots: ShaderRoots,
     ) -> Result<Self> {
         // Create new instance using first-tier backend of WGPU
-        // One of Vulkan + Metal + DX12 + Browser WebGPU
-        let instance = Instance::new(wgpu::Backends::PRIMARY);
+        // One of Vulkan + Metal + DX12 + Browser WebGPU,
+        // unless `WGPU_BACKEND` asks for another one like `gl`
+        let backends = wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::PRIMARY);
+        let instance = Instance::new(backends);
 
         // Create a `surface` represents a platform-specific window
         // onto which rendered images may be presented
@@ -91,10 +148,16 @@ impl Context {
             .await
             .ok_or(eyre!("Failed to create device adapter."))?;
 
-        // Use default features and limits for your machine
-        let features = adapter.features();
+        let missing = REQUIRED_FEATURES - adapter.features();
+        if !missing.is_empty() {
+            bail!("The adapter lacks required features {missing:?}");
+        }
+        let features = REQUIRED_FEATURES | (adapter.features() & OPTIONAL_FEATURES);
+        // Use default limits for your machine
         let limits = adapter.limits();
-        let surface_format = wgpu::TextureFormat::Bgra8Unorm;
+        let surface_format = surface
+            .get_preferred_format(&adapter)
+            .ok_or(eyre!("The surface is incompatible with the adapter."))?;
 
         // Create the logical device and command queue
         let (device, queue) = adapter
@@ -119,7 +182,18 @@ impl Context {
         };
         surface.configure(&device, &surface_config);
 
-        let mut watcher = Watcher::new(device.clone(), event_loop)?;
+        let mut shader_compiler = ShaderCompiler::with_roots(shader_roots);
+        register_shader_structs(&mut shader_compiler);
+        // Every compiler opens the disk cache, expired entries only need removing once
+        if let Some(cache) = shader_compiler.disk_cache() {
+            cache.prune();
+        }
+        let mut watcher = Watcher::new(
+            device.clone(),
+            event_loop,
+            shader_compiler.roots(),
+            shader_compiler.structs(),
+        )?;
 
         let camera = camera.unwrap_or_else(|| {
             Camera::new(
@@ -133,18 +207,62 @@ impl Context {
         let render_backbuffer = HdrBackBuffer::new(&device, HdrBackBuffer::DEFAULT_RESOLUTION);
         let rgb_texture = create_rgb_framebuffer(&device, &surface_config);
 
-        let mut shader_compiler = ShaderCompiler::new();
-
-        let present_shader = Path::new("shaders/present.wgsl");
+        let present_shader = Path::new("present.wgsl");
         let present_pipeline = PresentPipeline::from_path(
             &device,
             surface_format,
             present_shader,
+            &PresentPipeline::defines(surface_format),
+            &mut shader_compiler,
+        );
+        let present_pipeline = watcher.register_variant(
+            &present_shader,
+            &PresentPipeline::defines(surface_format),
+            present_pipeline,
+        );
+
+        let depth_texture = DepthTexture::new(&device, render_backbuffer.size());
+        let taa = Taa::new(
+            &device,
+            render_backbuffer.size(),
+            &mut watcher,
+            &mut shader_compiler,
+        );
+        let post_processing = PostProcessChain::with_default_effects(
+            &device,
+            render_backbuffer.size(),
+            &mut watcher,
+            &mut shader_compiler,
+        );
+
+        let tonemap_binding = TonemapBinding::new(&device);
+        let auto_exposure_shader = Path::new("luminance_histogram.wgsl");
+        let auto_exposure_defines =
+            AutoExposurePipeline::defines(ShaderBackend::for_device(&device));
+        let auto_exposure_pipeline = AutoExposurePipeline::from_path(
+            &device,
+            auto_exposure_shader,
+            &auto_exposure_defines,
             &mut shader_compiler,
+            &tonemap_binding.luminance_buffer,
+        );
+        let auto_exposure_pipeline = watcher.register_variant(
+            &auto_exposure_shader,
+            &auto_exposure_defines,
+            auto_exposure_pipeline,
         );
-        let present_pipeline = watcher.register(&present_shader, present_pipeline);
 
-        Ok(Self {
+        let error_overlay = ErrorOverlay::new(
+            &device,
+            &queue,
+            surface_format,
+            &mut shader_compiler,
+            (width, height),
+        );
+
+        let profiler = RefCell::new(GpuProfiler::new(device.clone(), &queue));
+
+        let mut context = Self {
             shader_compiler,
             camera,
             camera_binding: CameraBinding::new(&device),
@@ -158,6 +276,11 @@ impl Context {
             rgb_texture,
 
             render_backbuffer,
+            depth_texture,
+            taa,
+            post_processing,
+            render_scale: 1.0,
+            dynamic_resolution: None,
 
             width,
             height,
@@ -167,6 +290,12 @@ impl Context {
             watcher,
 
             present_pipeline,
+            error_overlay,
+            profiler,
+
+            tonemap: TonemapUniform::default(),
+            tonemap_binding,
+            auto_exposure_pipeline,
 
             global_uniform: Uniform::default(),
             global_uniform_binding: GlobalUniformBinding::new(&device),
@@ -177,7 +306,9 @@ impl Context {
             surface,
             surface_config,
             limits,
-        })
+        };
+        context.show_shader_errors();
+        Ok(context)
     }
 
     pub fn get_info(&self) -> RendererInfo {
@@ -187,6 +318,7 @@ impl Context {
             device_type: self.get_device_type().to_string(),
             vendor_name: self.get_vendor_name().to_string(),
             backend: self.get_backend().to_string(),
+            shader_backend: ShaderBackend::for_device(&self.device),
             screen_format: self.surface_config.format,
         }
     }
@@ -223,6 +355,20 @@ impl Context {
     }
 
     pub fn update(&mut self, frame_counter: &FrameCounter, input: &Input) {
+        if let Some(scale) = self
+            .dynamic_resolution
+            .as_mut()
+            .and_then(|controller| controller.record(frame_counter.frame_time()))
+        {
+            self.set_render_scale(scale);
+        }
+
+        let (jitter, ray_offset) = self
+            .taa
+            .next_frame(&self.queue, self.render_backbuffer.size());
+        self.camera.set_jitter(jitter);
+        self.global_uniform.jitter = ray_offset;
+
         self.global_uniform.time = self.timeline.elapsed().as_secs_f32();
         self.global_uniform.time_delta = frame_counter.time_delta();
         self.global_uniform.frame = frame_counter.frame_count;
@@ -233,6 +379,12 @@ impl Context {
             .update(&self.queue, &self.global_uniform);
 
         self.camera_binding.update(&self.queue, &mut self.camera);
+
+        self.tonemap_binding.update(&self.queue, &self.tonemap);
+    }
+
+    pub fn toggle_auto_exposure(&mut self) {
+        self.tonemap.toggle_auto_exposure();
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
@@ -244,11 +396,40 @@ impl Context {
 
         self.screenshot_ctx.resize(&self.device, width, height);
         self.rgb_texture = create_rgb_framebuffer(&self.device, &self.surface_config);
+        self.error_overlay.resize(&self.device, width, height);
 
         self.camera.set_aspect(width, height);
+        self.taa.invalidate_history();
+    }
+
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    /// Resizes the [HdrBackBuffer] to `scale` times its default resolution.
+    pub fn set_render_scale(&mut self, scale: f32) {
+        self.render_scale = scale.clamp(0.1, 1.0);
+        let (width, height) = HdrBackBuffer::DEFAULT_RESOLUTION;
+        let size = (
+            ((width as f32 * self.render_scale) as u32).max(1),
+            ((height as f32 * self.render_scale) as u32).max(1),
+        );
+        self.render_backbuffer.resize(&self.device, size);
+        self.depth_texture.resize(&self.device, size);
+        self.taa.resize(&self.device, size);
+        self.post_processing.resize(&self.device, size);
     }
 
     pub fn render(&self) -> Result<(), wgpu::SurfaceError> {
+        self.render_with_overlay(|_, _, _| {})
+    }
+
+    /// Renders the frame like [`render`](Self::render), with `overlay`
+    /// recording its passes over it after the present pass.
+    pub fn render_with_overlay(
+        &self,
+        overlay: impl FnOnce(&Self, &mut wgpu::CommandEncoder, &wgpu::TextureView),
+    ) -> Result<(), wgpu::SurfaceError> {
         let frame = self.surface.get_current_texture()?;
         let frame_view = frame.texture.create_view(&Default::default());
 
@@ -258,7 +439,38 @@ impl Context {
                 label: Some("Present Encoder"),
             });
 
+        let mut profiler = self.profiler.borrow_mut();
+        let input = if self.taa.enabled {
+            profiler.scope("TAA", &mut encoder, |_, encoder| {
+                self.taa.record(
+                    encoder,
+                    &self.global_uniform_binding,
+                    &self.camera_binding,
+                    &self.render_backbuffer,
+                )
+            })
+        } else {
+            &self.render_backbuffer
+        };
+        let output = profiler.scope("Post Processing", &mut encoder, |_, encoder| {
+            self.post_processing
+                .record(encoder, &self.global_uniform_binding, input)
+        });
+
+        if self.tonemap.auto_exposure != 0 {
+            let auto_exposure_pipeline = self.auto_exposure_pipeline.borrow();
+            profiler.begin_scope("Auto Exposure", &mut encoder);
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Auto Exposure Pass"),
+            });
+            auto_exposure_pipeline.record(&mut cpass, &self.global_uniform_binding, output);
+            drop(cpass);
+            profiler.end_scope(&mut encoder);
+        }
+
         let rgb = self.rgb_texture.create_view(&Default::default());
+        let present_pipeline = self.present_pipeline.borrow();
+        profiler.begin_scope("Present", &mut encoder);
         let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Present Pass"),
             color_attachments: &[
@@ -282,12 +494,19 @@ impl Context {
             depth_stencil_attachment: None,
         });
 
-        self.present_pipeline.record(
+        present_pipeline.record(
             &mut rpass,
             &self.global_uniform_binding,
-            &self.render_backbuffer.render_bind_group,
+            &output.render_bind_group,
+            &self.tonemap_binding,
+            self.render_scale < 1.0,
         );
         drop(rpass);
+        profiler.end_scope(&mut encoder);
+        drop(profiler);
+
+        overlay(self, &mut encoder, &frame_view);
+        self.error_overlay.record(&mut encoder, &frame_view);
 
         self.queue.submit(Some(encoder.finish()));
 
@@ -305,15 +524,47 @@ impl Context {
         self.screenshot_ctx.image_dimentions
     }
 
-    pub fn register_shader_change(&mut self, path: PathBuf, shader: wgpu::ShaderModule) {
-        if let Some(pipelines) = self.watcher.hash_dump.get_mut(&path) {
-            for pipeline in pipelines.iter_mut() {
-                // SAFETY: no safety
-                let pipeline_ref = unsafe { Rc::get_mut_unchecked(pipeline) };
-                pipeline_ref.reload(&self.device, &shader);
+    pub fn register_shader_change(
+        &mut self,
+        key: ShaderKey,
+        shader: wgpu::ShaderModule,
+        layout: &ShaderLayout,
+    ) {
+        self.error_overlay.remove(&self.device, &key);
+        if let Some(pipelines) = self.watcher.hash_dump.get_mut(&key) {
+            for pipeline in pipelines.iter() {
+                pipeline.borrow_mut().reload(&self.device, &shader, layout);
+            }
+        }
+    }
+
+    /// Reloads every asset registered for `path` with its new `contents`.
+    pub fn register_asset_change(&mut self, path: PathBuf, contents: &[u8]) {
+        if let Some(assets) = self.watcher.assets.get_mut(&path) {
+            for asset in assets.iter() {
+                let res = asset
+                    .borrow_mut()
+                    .reload(&self.device, &self.queue, &path, contents);
+                match res {
+                    Ok(()) => crate::utils::green_blink(),
+                    Err(err) => eprintln!("Failed to reload {}: {err}", path.display()),
+                }
             }
         }
     }
+
+    /// Shows `error` on screen until the shader compiles.
+    pub fn register_shader_error(&mut self, key: ShaderKey, error: &CompilerError) {
+        self.error_overlay.push(&self.device, key, error);
+    }
+
+    /// Shows the errors of the shaders [`shader_compiler`](Self::shader_compiler)
+    /// replaced by fallbacks, which were only printed so far.
+    pub fn show_shader_errors(&mut self) {
+        for (key, error) in self.shader_compiler.take_errors() {
+            self.register_shader_error(key, &error);
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -322,6 +573,7 @@ pub struct RendererInfo {
     pub device_type: String,
     pub vendor_name: String,
     pub backend: String,
+    pub shader_backend: ShaderBackend,
     pub screen_format: wgpu::TextureFormat,
 }
 
@@ -331,6 +583,7 @@ impl std::fmt::Display for RendererInfo {
         writeln!(f, "Device name: {}", self.device_name)?;
         writeln!(f, "Device type: {}", self.device_type)?;
         writeln!(f, "Backend: {}", self.backend)?;
+        writeln!(f, "Shaders: {}", self.shader_backend)?;
         write!(f, "Screen format: {:?}", self.screen_format)?;
         Ok(())
     }
@@ -357,3 +610,14 @@ fn create_rgb_framebuffer(
 
     device.create_texture(multisampled_frame_descriptor)
 }
+
+/// Checks shaders compiled by `compiler` against the uniforms the context
+/// shares with them.
+pub fn register_shader_structs(compiler: &mut ShaderCompiler) {
+    compiler.register_struct::<Uniform>();
+    compiler.register_struct::<CameraUniform>();
+    compiler.register_struct::<TonemapUniform>();
+    compiler.register_struct::<Luminance>();
+    compiler.register_struct::<MeshUniform>();
+    compiler.register_struct::<TaaUniform>();
+}
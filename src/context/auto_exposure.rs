@@ -0,0 +1,202 @@
+use std::path::Path;
+
+use crate::{
+    context::{tonemapping::Luminance, HdrBackBuffer, Uniform},
+    utils::{
+        dispatch_optimal,
+        fallback::FallbackShader,
+        preprocessor::ShaderDefines,
+        reflection::ShaderLayout,
+        shader_compiler::{ShaderBackend, ShaderCompiler},
+        NonZeroSized,
+    },
+    watcher::ReloadablePipeline,
+    GlobalUniformBinding,
+};
+
+const HISTOGRAM_BINS: u64 = 256;
+
+/// Computes the average scene luminance from a log-luminance histogram
+/// of the [HdrBackBuffer].
+///
+/// Backends without atomics average the log luminance of a grid of pixels
+/// in a single workgroup instead, see [`defines`](Self::defines).
+pub struct AutoExposurePipeline {
+    /// Missing in the [`NO_ATOMICS`](Self::NO_ATOMICS) variant
+    histogram_pipeline: Option<wgpu::ComputePipeline>,
+    average_pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    _histogram_buffer: wgpu::Buffer,
+}
+
+impl AutoExposurePipeline {
+    pub const DESC: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
+        label: Some("Auto Exposure Bind Group Layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(Luminance::SIZE),
+                },
+                count: None,
+            },
+        ],
+    };
+    /// Variant of the shader without the histogram
+    pub const NO_ATOMICS: &'static str = "NO_ATOMICS";
+    /// Bind groups set by [`record`](Self::record)
+    const BIND_GROUPS: [wgpu::BindGroupLayoutDescriptor<'static>; 3] =
+        [Uniform::DESC, HdrBackBuffer::DESC_RENDER, Self::DESC];
+
+    /// Variant of the shader that runs on `backend`, wgpu's SPIR-V frontend
+    /// doesn't translate atomics.
+    pub fn defines(backend: ShaderBackend) -> ShaderDefines {
+        match backend {
+            ShaderBackend::Passthrough => ShaderDefines::new(),
+            ShaderBackend::Translated => ShaderDefines::new().with(Self::NO_ATOMICS),
+        }
+    }
+
+    pub fn from_path(
+        device: &wgpu::Device,
+        path: &Path,
+        defines: &ShaderDefines,
+        compiler: &mut ShaderCompiler,
+        luminance_buffer: &wgpu::Buffer,
+    ) -> Self {
+        compiler.pipeline_or_fallback(
+            device,
+            path,
+            defines,
+            &Self::fallback_shader(),
+            |layout, shader| {
+                let histogram = !defines.contains(Self::NO_ATOMICS);
+                Self::new_with_module(device, histogram, layout, shader, luminance_buffer)
+            },
+        )
+    }
+
+    fn fallback_shader() -> FallbackShader {
+        FallbackShader::new()
+            .compute("build_histogram")
+            .compute("average_histogram")
+    }
+
+    /// Builds the histogram pipeline too if `histogram` is set.
+    pub fn new_with_module(
+        device: &wgpu::Device,
+        histogram: bool,
+        layout: &ShaderLayout,
+        module: &wgpu::ShaderModule,
+        luminance_buffer: &wgpu::Buffer,
+    ) -> Self {
+        let histogram_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Luminance Histogram Buffer"),
+            size: HISTOGRAM_BINS * std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Auto Exposure Bind Group"),
+            layout: &device.create_bind_group_layout(&Self::DESC),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: histogram_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: luminance_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let (histogram_pipeline, average_pipeline) =
+            Self::make_pipelines(device, histogram, layout, module);
+        Self {
+            histogram_pipeline,
+            average_pipeline,
+            bind_group,
+            _histogram_buffer: histogram_buffer,
+        }
+    }
+
+    fn make_pipelines(
+        device: &wgpu::Device,
+        histogram: bool,
+        layout: &ShaderLayout,
+        module: &wgpu::ShaderModule,
+    ) -> (Option<wgpu::ComputePipeline>, wgpu::ComputePipeline) {
+        let layout = layout
+            .clone()
+            .with_bind_groups(&Self::BIND_GROUPS)
+            .create_pipeline_layout(device, Some("Auto Exposure Pipeline Layout"));
+        let histogram = histogram.then(|| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Luminance Histogram Pipeline"),
+                layout: Some(&layout),
+                module,
+                entry_point: "build_histogram",
+            })
+        });
+        let average = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Average Luminance Pipeline"),
+            layout: Some(&layout),
+            module,
+            entry_point: "average_histogram",
+        });
+        (histogram, average)
+    }
+}
+
+impl<'a> AutoExposurePipeline {
+    pub fn record<'pass>(
+        &'a self,
+        cpass: &mut wgpu::ComputePass<'pass>,
+        uniform_bind_group: &'a GlobalUniformBinding,
+        hdr_backbuffer: &'a HdrBackBuffer,
+    ) where
+        'a: 'pass,
+    {
+        let (width, height) = hdr_backbuffer.size();
+
+        cpass.set_bind_group(0, &uniform_bind_group.binding, &[]);
+        cpass.set_bind_group(1, &hdr_backbuffer.render_bind_group, &[]);
+        cpass.set_bind_group(2, &self.bind_group, &[]);
+
+        if let Some(histogram_pipeline) = &self.histogram_pipeline {
+            cpass.set_pipeline(histogram_pipeline);
+            cpass.dispatch(dispatch_optimal(width, 16), dispatch_optimal(height, 16), 1);
+        }
+
+        cpass.set_pipeline(&self.average_pipeline);
+        cpass.dispatch(1, 1, 1);
+    }
+}
+
+impl ReloadablePipeline for AutoExposurePipeline {
+    fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        module: &wgpu::ShaderModule,
+        layout: &ShaderLayout,
+    ) {
+        let histogram = self.histogram_pipeline.is_some();
+        let (histogram, average) = Self::make_pipelines(device, histogram, layout, module);
+        self.histogram_pipeline = histogram;
+        self.average_pipeline = average;
+    }
+}
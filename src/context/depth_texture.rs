@@ -0,0 +1,87 @@
+pub struct DepthTexture {
+    size: (u32, u32),
+    pub texture: wgpu::Texture,
+    pub texture_view: wgpu::TextureView,
+
+    pub render_bind_group: wgpu::BindGroup,
+}
+
+impl DepthTexture {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    pub const DESC_RENDER: wgpu::BindGroupLayoutDescriptor<'static> =
+        wgpu::BindGroupLayoutDescriptor {
+            label: Some("Depth: Render Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        };
+
+    pub fn new(device: &wgpu::Device, (width, height): (u32, u32)) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Texture: Depth"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        });
+        let texture_view = texture.create_view(&Default::default());
+
+        let render_bind_group_layout = device.create_bind_group_layout(&Self::DESC_RENDER);
+        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Depth: Render Bind Group"),
+            layout: &render_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&texture_view),
+            }],
+        });
+
+        Self {
+            size: (width, height),
+            texture,
+            texture_view,
+
+            render_bind_group,
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: (u32, u32)) {
+        if self.size != size {
+            *self = Self::new(device, size);
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        Self::FORMAT
+    }
+
+    /// Depth state used by the pipelines writing into this texture
+    pub fn depth_stencil_state(depth_write_enabled: bool) -> wgpu::DepthStencilState {
+        wgpu::DepthStencilState {
+            format: Self::FORMAT,
+            depth_write_enabled,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::{num::NonZeroU32, path::Path};
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    utils::{
+        bitmap_font::{self, GLYPH_COUNT, GLYPH_SIZE},
+        shader_compiler::{self, CompilerError, ShaderCompiler},
+    },
+    watcher::ShaderKey,
+};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Quad {
+    position: [f32; 2],
+    size: [f32; 2],
+    glyph: u32,
+}
+
+impl Quad {
+    const BACKGROUND: u32 = 0xffff;
+}
+
+/// Shows the error of the most recently failed shader on top of the frame
+/// until that shader compiles again.
+pub struct ErrorOverlay {
+    pipeline: wgpu::RenderPipeline,
+    font_bind_group: wgpu::BindGroup,
+    /// Failed shaders in the order they failed, the last one is shown
+    errors: Vec<(ShaderKey, String)>,
+    quads: Option<(wgpu::Buffer, u32)>,
+    width: u32,
+    height: u32,
+}
+
+impl ErrorOverlay {
+    pub const DESC: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
+        label: Some("Error Overlay Font BGL"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        }],
+    };
+
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        surface_format: wgpu::TextureFormat,
+        compiler: &mut ShaderCompiler,
+        (width, height): (u32, u32),
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: GLYPH_COUNT * GLYPH_SIZE,
+            height: GLYPH_SIZE,
+            depth_or_array_layers: 1,
+        };
+        let font = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Error Overlay Font"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+        });
+        queue.write_texture(
+            font.as_image_copy(),
+            &bitmap_font::atlas(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(size.width),
+                rows_per_image: None,
+            },
+            size,
+        );
+        let font_view = font.create_view(&Default::default());
+        let font_bind_group_layout = device.create_bind_group_layout(&Self::DESC);
+        let font_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Error Overlay Font Bind Group"),
+            layout: &font_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&font_view),
+            }],
+        });
+
+        // Not hot reloaded, the overlay has to work while shaders in the folder are broken
+        let path = Path::new("error_overlay.wgsl");
+        let source = compiler
+            .create_shader_module_from_source(
+                path,
+                include_str!("../../shaders/error_overlay.wgsl"),
+            )
+            .expect("Error overlay shader is valid");
+        let shader = shader_compiler::create_module(device, path.to_str(), &source);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Error Overlay Layout"),
+            bind_group_layouts: &[&font_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Error Overlay Pipeline"),
+            layout: Some(&layout),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<Quad>() as _,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Uint32],
+                }],
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            font_bind_group,
+            errors: vec![],
+            quads: None,
+            width,
+            height,
+        }
+    }
+
+    pub fn push(&mut self, device: &wgpu::Device, key: ShaderKey, error: &CompilerError) {
+        self.errors.retain(|(x, _)| x != &key);
+        self.errors.push((key, error.to_string()));
+        self.rebuild(device);
+    }
+
+    pub fn remove(&mut self, device: &wgpu::Device, key: &ShaderKey) {
+        let len = self.errors.len();
+        self.errors.retain(|(x, _)| x != key);
+        if self.errors.len() != len {
+            self.rebuild(device);
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.rebuild(device);
+    }
+
+    /// Lays out the last error as glyph quads in clip space.
+    fn rebuild(&mut self, device: &wgpu::Device) {
+        self.quads = None;
+        let (key, message) = match self.errors.last() {
+            Some(error) => error,
+            None => return,
+        };
+
+        let (width, height) = (self.width as f32, self.height as f32);
+        let scale = (height / 480.).round().max(1.);
+        let cell = GLYPH_SIZE as f32 * scale;
+        let columns = ((width / cell) as usize).saturating_sub(2).max(1);
+        let max_rows = ((height / cell) as usize).saturating_sub(2).max(1);
+
+        let mut header = key.path.display().to_string();
+        if !key.defines.is_empty() {
+            header += &format!(" {}", key.defines);
+        }
+        let rows: Vec<Vec<char>> = std::iter::once(header.as_str())
+            .chain(message.lines())
+            .flat_map(|line| {
+                let chars: Vec<char> = line.replace('\t', "    ").chars().collect();
+                if chars.is_empty() {
+                    return vec![vec![]];
+                }
+                chars.chunks(columns).map(<[char]>::to_vec).collect()
+            })
+            .take(max_rows)
+            .collect();
+
+        let glyph_size = [cell / width * 2., cell / height * 2.];
+        let mut quads = vec![Quad {
+            position: [-1., 1.],
+            size: [2., (rows.len() + 2) as f32 * glyph_size[1]],
+            glyph: Quad::BACKGROUND,
+        }];
+        for (row, line) in rows.iter().enumerate() {
+            for (column, &c) in line.iter().enumerate() {
+                if c == ' ' {
+                    continue;
+                }
+                quads.push(Quad {
+                    position: [
+                        -1. + (column + 1) as f32 * glyph_size[0],
+                        1. - (row + 1) as f32 * glyph_size[1],
+                    ],
+                    size: glyph_size,
+                    glyph: bitmap_font::glyph_index(c),
+                });
+            }
+        }
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Error Overlay Quads"),
+            contents: bytemuck::cast_slice(&quads),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        self.quads = Some((buffer, quads.len() as u32));
+    }
+
+    pub fn record(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let (quads, count) = match self.quads {
+            Some((ref buffer, count)) => (buffer, count),
+            None => return,
+        };
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Error Overlay Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.font_bind_group, &[]);
+        rpass.set_vertex_buffer(0, quads.slice(..));
+        rpass.draw(0..4, 0..count);
+    }
+}
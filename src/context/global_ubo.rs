@@ -1,9 +1,10 @@
 use crate::utils::NonZeroSized;
 use std::time::Duration;
 
-use bytemuck::{Pod, Zeroable};
 use wgpu::util::DeviceExt;
 
+pub use crate::shader_types::Uniform;
+
 pub struct GlobalUniformBinding {
     pub binding: wgpu::BindGroup,
     buffer: wgpu::Buffer,
@@ -49,21 +50,6 @@ impl GlobalUniformBinding {
     }
 }
 
-#[repr(C)]
-#[derive(Debug, Clone, Copy, Pod, Zeroable)]
-pub struct Uniform {
-    pub pos: [f32; 3],
-    pub frame: u32,
-    pub resolution: [f32; 2],
-    pub mouse: [f32; 2],
-    pub mouse_pressed: u32,
-    pub time: f32,
-    pub time_delta: f32,
-    _padding: f32,
-    // pub record_period: f32,
-    // _padding2: [f32; 3],
-}
-
 impl Default for Uniform {
     fn default() -> Self {
         Self {
@@ -74,7 +60,7 @@ impl Default for Uniform {
             mouse_pressed: false as _,
             frame: 0,
             time_delta: 1. / 60.,
-            _padding: 0.,
+            jitter: 0.,
             // record_period: 10.,
         }
     }
@@ -113,7 +99,7 @@ impl Uniform {
             time,
             time_delta,
             frame,
-            _padding: 0.,
+            jitter: 0.,
         }
     }
 }
@@ -0,0 +1,497 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    future::Future,
+    io,
+    path::Path,
+    pin::Pin,
+    sync::Arc,
+    task::{Context as TaskContext, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+/// Timestamps one frame can write, two per scope
+const QUERIES_PER_FRAME: u32 = 256;
+/// Frames whose timestamps may still be on the way back from the GPU
+const FRAMES_IN_FLIGHT: usize = 4;
+/// Finished frames kept for [`GpuProfiler::chrome_trace`]
+const HISTORY: usize = 1000;
+
+type MapFuture = Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+/// Command encoder or pass a profiler scope can be recorded on.
+pub trait ProfilerTarget {
+    fn write_timestamp(&mut self, query_set: &wgpu::QuerySet, index: u32);
+    fn push_debug_group(&mut self, label: &str);
+    fn pop_debug_group(&mut self);
+}
+
+macro_rules! impl_profiler_target {
+    ($($ty:ty),*) => {$(
+        impl ProfilerTarget for $ty {
+            fn write_timestamp(&mut self, query_set: &wgpu::QuerySet, index: u32) {
+                <$ty>::write_timestamp(self, query_set, index)
+            }
+            fn push_debug_group(&mut self, label: &str) {
+                <$ty>::push_debug_group(self, label)
+            }
+            fn pop_debug_group(&mut self) {
+                <$ty>::pop_debug_group(self)
+            }
+        }
+    )*};
+}
+
+impl_profiler_target!(
+    wgpu::CommandEncoder,
+    wgpu::ComputePass<'_>,
+    wgpu::RenderPass<'_>
+);
+
+/// GPU time spent in a named scope.
+#[derive(Debug, Clone)]
+pub struct GpuScope {
+    pub label: String,
+    /// Number of scopes this one is nested in
+    pub depth: usize,
+    /// Nanoseconds since the first recorded timestamp
+    pub start: f64,
+    pub end: f64,
+}
+
+impl GpuScope {
+    pub fn duration_ms(&self) -> f64 {
+        (self.end - self.start) / 1e6
+    }
+}
+
+/// Scopes of one frame in the order they were opened.
+#[derive(Debug, Clone)]
+pub struct GpuFrame {
+    pub index: u64,
+    pub scopes: Vec<GpuScope>,
+}
+
+struct ScopeQueries {
+    label: String,
+    depth: usize,
+    start: u32,
+    end: Option<u32>,
+}
+
+/// Queries and buffers of a single frame, reused once its timestamps are read.
+struct FrameQueries {
+    query_set: wgpu::QuerySet,
+    read_buffer: wgpu::Buffer,
+    scopes: Vec<ScopeQueries>,
+    next_query: u32,
+    index: u64,
+    mapping: Option<MapFuture>,
+}
+
+impl FrameQueries {
+    fn new(device: &wgpu::Device) -> Self {
+        let size = QUERIES_PER_FRAME as u64 * wgpu::QUERY_SIZE as u64;
+        Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Profiler Query Set"),
+                count: QUERIES_PER_FRAME,
+                ty: wgpu::QueryType::Timestamp,
+            }),
+            read_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Profiler Read Buffer"),
+                size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            scopes: vec![],
+            next_query: 0,
+            index: 0,
+            mapping: None,
+        }
+    }
+
+    fn size(&self) -> u64 {
+        self.next_query as u64 * wgpu::QUERY_SIZE as u64
+    }
+}
+
+/// Measures the GPU time of nested named scopes on encoders and passes.
+///
+/// Timestamps are read back a few frames later without waiting for the GPU,
+/// frames that find every buffer still in flight aren't measured. Scopes still
+/// push debug groups when the adapter lacks [`wgpu::Features::TIMESTAMP_QUERY`],
+/// they just never produce timings.
+///
+/// Scopes must only be recorded on encoders that get submitted before
+/// [`end_frame`](Self::end_frame). It resolves every query of the frame, and
+/// queries of a dropped encoder are never written, so the frame would read
+/// undefined timestamps.
+pub struct GpuProfiler {
+    device: Arc<wgpu::Device>,
+    supported: bool,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    current: Option<FrameQueries>,
+    free: Vec<FrameQueries>,
+    pending: VecDeque<FrameQueries>,
+    /// Open scopes, `None` for the ones without queries
+    open: Vec<Option<usize>>,
+    frame_index: u64,
+    /// First timestamp ever read, the origin of [GpuScope] times
+    origin: Option<u64>,
+    frames: VecDeque<GpuFrame>,
+}
+
+impl GpuProfiler {
+    pub fn new(device: Arc<wgpu::Device>, queue: &wgpu::Queue) -> Self {
+        let supported = device.features().contains(wgpu::Features::TIMESTAMP_QUERY);
+        Self {
+            device,
+            supported,
+            period: queue.get_timestamp_period(),
+            current: None,
+            free: vec![],
+            pending: VecDeque::new(),
+            open: vec![],
+            frame_index: 0,
+            origin: None,
+            frames: VecDeque::new(),
+        }
+    }
+
+    /// Whether the adapter can measure anything.
+    pub fn is_supported(&self) -> bool {
+        self.supported
+    }
+
+    /// Opens a scope on `target`, closed by the next [`end_scope`](Self::end_scope).
+    ///
+    /// The encoder of `target` has to be submitted in this frame.
+    pub fn begin_scope(&mut self, label: &str, target: &mut impl ProfilerTarget) {
+        target.push_debug_group(label);
+        let depth = self.open.len();
+        let frame = match self.current_frame() {
+            Some(frame) if frame.next_query + 2 <= QUERIES_PER_FRAME => frame,
+            _ => {
+                self.open.push(None);
+                return;
+            }
+        };
+        target.write_timestamp(&frame.query_set, frame.next_query);
+        frame.scopes.push(ScopeQueries {
+            label: label.to_string(),
+            depth,
+            start: frame.next_query,
+            end: None,
+        });
+        // The end query is reserved so nested scopes can't use it up
+        frame.next_query += 2;
+        let scope = frame.scopes.len() - 1;
+        self.open.push(Some(scope));
+    }
+
+    /// Closes the innermost open scope on `target`.
+    pub fn end_scope(&mut self, target: &mut impl ProfilerTarget) {
+        let scope = match self.open.pop() {
+            Some(scope) => scope,
+            None => {
+                eprintln!("GPU profiler: end_scope without a matching begin_scope");
+                return;
+            }
+        };
+        if let (Some(scope), Some(frame)) = (scope, self.current.as_mut()) {
+            let scope = &mut frame.scopes[scope];
+            target.write_timestamp(&frame.query_set, scope.start + 1);
+            scope.end = Some(scope.start + 1);
+        }
+        target.pop_debug_group();
+    }
+
+    /// Records `f` in a scope, `f` gets the profiler back for nested scopes.
+    pub fn scope<T: ProfilerTarget, R>(
+        &mut self,
+        label: &str,
+        target: &mut T,
+        f: impl FnOnce(&mut Self, &mut T) -> R,
+    ) -> R {
+        self.begin_scope(label, target);
+        let res = f(self, target);
+        self.end_scope(target);
+        res
+    }
+
+    /// Resolves the timestamps of the frame and collects the ones that arrived.
+    ///
+    /// Call it once per frame after submitting all the work of the frame,
+    /// including every encoder a scope was recorded on.
+    pub fn end_frame(&mut self, queue: &wgpu::Queue) {
+        if !self.open.is_empty() {
+            eprintln!(
+                "GPU profiler: {} scopes weren't closed before the end of the frame",
+                self.open.len()
+            );
+            self.open.clear();
+        }
+        self.frame_index += 1;
+
+        if let Some(mut frame) = self.current.take() {
+            if frame.next_query == 0 {
+                self.free.push(frame);
+            } else {
+                let mut encoder =
+                    self.device
+                        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some("Profiler Resolve Encoder"),
+                        });
+                // Resolving waits for every query, unclosed scopes are ended here and dropped
+                for scope in frame.scopes.iter().filter(|x| x.end.is_none()) {
+                    encoder.write_timestamp(&frame.query_set, scope.start + 1);
+                }
+                encoder.resolve_query_set(
+                    &frame.query_set,
+                    0..frame.next_query,
+                    &frame.read_buffer,
+                    0,
+                );
+                queue.submit(Some(encoder.finish()));
+
+                let mapping = frame
+                    .read_buffer
+                    .slice(..frame.size())
+                    .map_async(wgpu::MapMode::Read);
+                frame.mapping = Some(Box::pin(mapping));
+                self.pending.push_back(frame);
+            }
+        }
+        self.collect();
+    }
+
+    /// The latest frame with timings.
+    pub fn last_frame(&self) -> Option<&GpuFrame> {
+        self.frames.back()
+    }
+
+    /// Measured frames, the oldest first.
+    pub fn frames(&self) -> impl Iterator<Item = &GpuFrame> {
+        self.frames.iter()
+    }
+
+    /// Measured frames in the Chrome trace event format, for `chrome://tracing` or Perfetto.
+    pub fn chrome_trace(&self) -> String {
+        chrome_trace(&self.frames)
+    }
+
+    pub fn save_chrome_trace(&self, path: &Path) -> io::Result<()> {
+        std::fs::write(path, self.chrome_trace())
+    }
+
+    fn current_frame(&mut self) -> Option<&mut FrameQueries> {
+        if !self.supported {
+            return None;
+        }
+        if self.current.is_none() {
+            let frame = match self.free.pop() {
+                Some(frame) => frame,
+                None if self.pending.len() < FRAMES_IN_FLIGHT => FrameQueries::new(&self.device),
+                // The GPU is behind, this frame goes unmeasured
+                None => return None,
+            };
+            self.current = Some(FrameQueries {
+                index: self.frame_index,
+                ..frame
+            });
+        }
+        self.current.as_mut()
+    }
+
+    /// Reads every pending frame whose buffer got mapped, in submission order.
+    fn collect(&mut self) {
+        self.device.poll(wgpu::Maintain::Poll);
+        while let Some(frame) = self.pending.front_mut() {
+            let res = match frame.mapping.as_mut().and_then(poll_now) {
+                Some(res) => res,
+                None => break,
+            };
+            let mut frame = self.pending.pop_front().unwrap();
+            frame.mapping = None;
+            if res.is_ok() {
+                let view = frame.read_buffer.slice(..frame.size()).get_mapped_range();
+                let ticks: &[u64] = bytemuck::cast_slice(&view);
+                let scopes = read_scopes(&frame.scopes, ticks, &mut self.origin, self.period);
+                drop(view);
+                frame.read_buffer.unmap();
+
+                if self.frames.len() == HISTORY {
+                    self.frames.pop_front();
+                }
+                self.frames.push_back(GpuFrame {
+                    index: frame.index,
+                    scopes,
+                });
+            }
+            frame.scopes.clear();
+            frame.next_query = 0;
+            self.free.push(frame);
+        }
+    }
+}
+
+impl std::fmt::Display for GpuFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for scope in &self.scopes {
+            let indent = 2 * scope.depth;
+            writeln!(
+                f,
+                "{:indent$}{}: {:.3}ms",
+                "",
+                scope.label,
+                scope.duration_ms()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Polls `future` once, without an executor to wake.
+fn poll_now(future: &mut MapFuture) -> Option<Result<(), wgpu::BufferAsyncError>> {
+    fn noop_waker() -> RawWaker {
+        RawWaker::new(std::ptr::null(), &NOOP_WAKER)
+    }
+    static NOOP_WAKER: RawWakerVTable =
+        RawWakerVTable::new(|_| noop_waker(), |_| {}, |_| {}, |_| {});
+
+    // SAFETY: the vtable ignores the data pointer
+    let waker = unsafe { Waker::from_raw(noop_waker()) };
+    match future.as_mut().poll(&mut TaskContext::from_waker(&waker)) {
+        Poll::Ready(res) => Some(res),
+        Poll::Pending => None,
+    }
+}
+
+/// Times of the closed `scopes` from their resolved `ticks`.
+///
+/// The earliest start of the first frame read becomes the `origin`, ticks
+/// are `period` nanoseconds long.
+fn read_scopes(
+    scopes: &[ScopeQueries],
+    ticks: &[u64],
+    origin: &mut Option<u64>,
+    period: f32,
+) -> Vec<GpuScope> {
+    let origin = *origin.get_or_insert_with(|| {
+        let closed = scopes.iter().filter(|x| x.end.is_some());
+        closed.map(|x| ticks[x.start as usize]).min().unwrap_or(0)
+    });
+    let time = |tick: u64| tick.saturating_sub(origin) as f64 * period as f64;
+    scopes
+        .iter()
+        .filter_map(|scope| {
+            let end = ticks[scope.end? as usize];
+            Some(GpuScope {
+                label: scope.label.clone(),
+                depth: scope.depth,
+                start: time(ticks[scope.start as usize]),
+                end: time(end.max(ticks[scope.start as usize])),
+            })
+        })
+        .collect()
+}
+
+fn chrome_trace<'a>(frames: impl IntoIterator<Item = &'a GpuFrame>) -> String {
+    let mut events = vec![
+        r#"{"name":"thread_name","ph":"M","pid":0,"tid":0,"args":{"name":"GPU"}}"#.to_string(),
+    ];
+    for frame in frames {
+        for scope in &frame.scopes {
+            let mut event = String::from(r#"{"name":""#);
+            escape_json(&scope.label, &mut event);
+            let _ = write!(
+                event,
+                r#"","cat":"gpu","ph":"X","pid":0,"tid":0,"ts":{:.3},"dur":{:.3},"args":{{"frame":{}}}}}"#,
+                scope.start / 1e3,
+                (scope.end - scope.start) / 1e3,
+                frame.index
+            );
+            events.push(event);
+        }
+    }
+    format!(
+        "{{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n{}\n]}}\n",
+        events.join(",\n")
+    )
+}
+
+fn escape_json(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(label: &str, depth: usize, start: u32, closed: bool) -> ScopeQueries {
+        ScopeQueries {
+            label: label.to_string(),
+            depth,
+            start,
+            end: closed.then(|| start + 1),
+        }
+    }
+
+    #[test]
+    fn reads_nested_scopes_relative_to_the_first_frame() {
+        let scopes = [
+            scope("Frame", 0, 0, true),
+            scope("Inner", 1, 2, true),
+            scope("Unclosed", 1, 4, false),
+        ];
+        let ticks = [1000, 1100, 1010, 1050, 1060, 1070];
+        let mut origin = None;
+        let read = read_scopes(&scopes, &ticks, &mut origin, 2.);
+        assert_eq!(origin, Some(1000));
+        let times: Vec<_> = read
+            .iter()
+            .map(|x| (x.label.as_str(), x.depth, x.start, x.end))
+            .collect();
+        assert_eq!(times, [("Frame", 0, 0., 200.), ("Inner", 1, 20., 100.)]);
+
+        // Later frames keep the origin, ends before starts are clamped
+        let ticks = [900, 1500, 2000, 1900];
+        let read = read_scopes(&scopes[..2], &ticks, &mut origin, 2.);
+        assert_eq!(origin, Some(1000));
+        assert_eq!((read[0].start, read[0].end), (0., 1000.));
+        assert_eq!((read[1].start, read[1].end), (2000., 2000.));
+    }
+
+    #[test]
+    fn escapes_labels_in_chrome_traces() {
+        let mut escaped = String::new();
+        escape_json("a \"b\" \\ \n\t", &mut escaped);
+        assert_eq!(escaped, r#"a \"b\" \\ \u000a\u0009"#);
+
+        let frame = GpuFrame {
+            index: 7,
+            scopes: vec![GpuScope {
+                label: "Post \"Bloom\"".to_string(),
+                depth: 1,
+                start: 1500.,
+                end: 4000.,
+            }],
+        };
+        let trace = chrome_trace([&frame]);
+        assert!(trace.starts_with(r#"{"displayTimeUnit":"ms","traceEvents":["#));
+        assert!(trace.contains(
+            r#"{"name":"Post \"Bloom\"","cat":"gpu","ph":"X","pid":0,"tid":0,"ts":1.500,"dur":2.500,"args":{"frame":7}}"#
+        ));
+        assert!(trace.ends_with("]}\n"));
+    }
+}
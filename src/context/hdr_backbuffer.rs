@@ -1,4 +1,5 @@
 pub struct HdrBackBuffer {
+    size: (u32, u32),
     pub texture: wgpu::Texture,
     pub texture_view: wgpu::TextureView,
 
@@ -28,7 +29,7 @@ impl HdrBackBuffer {
             label: Some("BackBuffer: Render Bind Group Layout"),
             entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
                 ty: wgpu::BindingType::Texture {
                     sample_type: wgpu::TextureSampleType::Float { filterable: true },
                     view_dimension: wgpu::TextureViewDimension::D2,
@@ -78,6 +79,7 @@ impl HdrBackBuffer {
         });
 
         Self {
+            size: (width, height),
             texture,
             texture_view,
 
@@ -86,6 +88,16 @@ impl HdrBackBuffer {
         }
     }
 
+    pub fn resize(&mut self, device: &wgpu::Device, size: (u32, u32)) {
+        if self.size != size {
+            *self = Self::new(device, size);
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
     pub fn format(&self) -> wgpu::TextureFormat {
         Self::FORMAT
     }
@@ -3,46 +3,56 @@ use std::path::Path;
 use crate::{
     camera::CameraBinding,
     context::{global_ubo::GlobalUniformBinding, Uniform},
-    utils::shader_compiler::ShaderCompiler,
+    utils::{
+        fallback::FallbackShader, preprocessor::ShaderDefines, reflection::ShaderLayout,
+        shader_compiler::ShaderCompiler,
+    },
     watcher::ReloadablePipeline,
 };
 
 pub struct BasicPipeline {
     pub pipeline: wgpu::RenderPipeline,
     surface_format: wgpu::TextureFormat,
+    depth_format: Option<wgpu::TextureFormat>,
 }
 
 impl BasicPipeline {
+    /// Bind groups set by [`record`](Self::record)
+    const BIND_GROUPS: [wgpu::BindGroupLayoutDescriptor<'static>; 2] =
+        [Uniform::DESC, CameraBinding::DESC];
+
     pub fn from_path(
         device: &wgpu::Device,
         format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
         path: &Path,
         compiler: &mut ShaderCompiler,
     ) -> Self {
-        let shader = unsafe {
-            device.create_shader_module_spirv(&wgpu::ShaderModuleDescriptorSpirV {
-                label: path.to_str(),
-                source: compiler.create_shader_module(path).unwrap().into(),
-            })
-        };
-        Self::new_with_module(device, format, &shader)
+        compiler.pipeline_or_fallback(
+            device,
+            path,
+            &ShaderDefines::default(),
+            &FallbackShader::new()
+                .vertex("vs_main")
+                .fragment("fs_main", 1),
+            |layout, shader| Self::new_with_module(device, format, depth_format, layout, shader),
+        )
     }
 
     pub fn new_with_module(
         device: &wgpu::Device,
         surface_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+        layout: &ShaderLayout,
         shader: &wgpu::ShaderModule,
     ) -> Self {
-        let global_bind_group_layout = device.create_bind_group_layout(&Uniform::DESC);
-        let camera_bind_group_layout = device.create_bind_group_layout(&CameraBinding::DESC);
-        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Screen Pass Layout"),
-            bind_group_layouts: &[&global_bind_group_layout, &camera_bind_group_layout],
-            push_constant_ranges: &[],
-        });
+        let pipeline_layout = layout
+            .clone()
+            .with_bind_groups(&Self::BIND_GROUPS)
+            .create_pipeline_layout(device, Some("Screen Pass Layout"));
         let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("Render with Camera Pipeline"),
-            layout: Some(&layout),
+            layout: Some(&pipeline_layout),
             fragment: Some(wgpu::FragmentState {
                 module: shader,
                 entry_point: "fs_main",
@@ -54,13 +64,20 @@ impl BasicPipeline {
                 buffers: &[],
             },
             primitive: wgpu::PrimitiveState::default(),
-            depth_stencil: None,
+            depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                format,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
             multisample: wgpu::MultisampleState::default(),
             multiview: None,
         });
         Self {
             pipeline,
             surface_format,
+            depth_format,
         }
     }
 }
@@ -82,7 +99,18 @@ impl<'a> BasicPipeline {
 }
 
 impl ReloadablePipeline for BasicPipeline {
-    fn reload(&mut self, device: &wgpu::Device, module: &wgpu::ShaderModule) {
-        *self = Self::new_with_module(device, self.surface_format, module);
+    fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        module: &wgpu::ShaderModule,
+        layout: &ShaderLayout,
+    ) {
+        *self = Self::new_with_module(
+            device,
+            self.surface_format,
+            self.depth_format,
+            layout,
+            module,
+        );
     }
 }
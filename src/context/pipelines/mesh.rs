@@ -0,0 +1,188 @@
+use std::path::Path;
+
+use color_eyre::eyre::Result;
+use glam::Mat4;
+use wgpu::util::DeviceExt;
+
+pub use crate::shader_types::MeshUniform;
+
+use crate::{
+    camera::CameraBinding,
+    context::{global_ubo::GlobalUniformBinding, DepthTexture, HdrBackBuffer, Uniform},
+    utils::{
+        fallback::FallbackShader,
+        obj::{load_obj, MeshVertex},
+        preprocessor::ShaderDefines,
+        reflection::ShaderLayout,
+        shader_compiler::ShaderCompiler,
+        NonZeroSized,
+    },
+    watcher::ReloadablePipeline,
+};
+
+impl Default for MeshUniform {
+    fn default() -> Self {
+        Self {
+            model: Mat4::IDENTITY.to_cols_array_2d(),
+            color: [0.8, 0.8, 0.85, 1.0],
+        }
+    }
+}
+
+pub struct Mesh {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+    uniform_buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl Mesh {
+    pub const DESC: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
+        label: Some("Mesh Bind Group Layout"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: Some(MeshUniform::SIZE),
+            },
+            count: None,
+        }],
+    };
+
+    pub fn from_obj(device: &wgpu::Device, path: &Path) -> Result<Self> {
+        let (vertices, indices) = load_obj(path)?;
+        Ok(Self::new(device, &vertices, &indices))
+    }
+
+    pub fn new(device: &wgpu::Device, vertices: &[MeshVertex], indices: &[u32]) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Vertex Buffer"),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Index Buffer"),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Uniform Buffer"),
+            contents: bytemuck::bytes_of(&MeshUniform::default()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Mesh Bind Group"),
+            layout: &device.create_bind_group_layout(&Self::DESC),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            index_count: indices.len() as _,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, uniform: &MeshUniform) {
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(uniform));
+    }
+}
+
+/// Lit opaque meshes rendered into the [HdrBackBuffer] with depth.
+pub struct MeshPipeline {
+    pub pipeline: wgpu::RenderPipeline,
+}
+
+impl MeshPipeline {
+    /// Bind groups set by [`record`](Self::record)
+    const BIND_GROUPS: [wgpu::BindGroupLayoutDescriptor<'static>; 3] =
+        [Uniform::DESC, CameraBinding::DESC, Mesh::DESC];
+
+    pub fn from_path(device: &wgpu::Device, path: &Path, compiler: &mut ShaderCompiler) -> Self {
+        compiler.pipeline_or_fallback(
+            device,
+            path,
+            &ShaderDefines::default(),
+            &FallbackShader::new()
+                .vertex("vs_main")
+                .fragment("fs_main", 1),
+            |layout, shader| Self::new_with_module(device, layout, shader),
+        )
+    }
+
+    pub fn new_with_module(
+        device: &wgpu::Device,
+        layout: &ShaderLayout,
+        shader: &wgpu::ShaderModule,
+    ) -> Self {
+        let layout = layout
+            .clone()
+            .with_bind_groups(&Self::BIND_GROUPS)
+            .create_pipeline_layout(device, Some("Mesh Pass Layout"));
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Mesh Pipeline"),
+            layout: Some(&layout),
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                targets: &[HdrBackBuffer::FORMAT.into()],
+            }),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<MeshVertex>() as _,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+                }],
+            },
+            primitive: wgpu::PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(DepthTexture::depth_stencil_state(true)),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        Self { pipeline }
+    }
+}
+
+impl<'a> MeshPipeline {
+    pub fn record<'pass>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        uniform_bind_group: &'a GlobalUniformBinding,
+        camera_bind_group: &'a CameraBinding,
+        mesh: &'a Mesh,
+    ) where
+        'a: 'pass,
+    {
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &uniform_bind_group.binding, &[]);
+        rpass.set_bind_group(1, &camera_bind_group.bind_group, &[]);
+        rpass.set_bind_group(2, &mesh.bind_group, &[]);
+        rpass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        rpass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        rpass.draw_indexed(0..mesh.index_count, 0, 0..1);
+    }
+}
+
+impl ReloadablePipeline for MeshPipeline {
+    fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        module: &wgpu::ShaderModule,
+        layout: &ShaderLayout,
+    ) {
+        *self = Self::new_with_module(device, layout, module);
+    }
+}
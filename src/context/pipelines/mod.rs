@@ -1 +1,2 @@
 pub mod basic_with_camera;
+pub mod mesh;
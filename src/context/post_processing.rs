@@ -0,0 +1,252 @@
+use std::path::Path;
+
+use crate::{
+    context::{global_ubo::GlobalUniformBinding, HdrBackBuffer, PipelineHandle, Uniform},
+    utils::{
+        fallback::FallbackShader, preprocessor::ShaderDefines, reflection::ShaderLayout,
+        shader_compiler::ShaderCompiler,
+    },
+    watcher::{ReloadablePipeline, Watcher},
+};
+
+/// Full screen pass reading one HDR texture and writing another
+pub struct PostProcessPipeline {
+    pub pipeline: wgpu::RenderPipeline,
+    sampler_bind_group: wgpu::BindGroup,
+}
+
+impl PostProcessPipeline {
+    const SAMPLER_DESC: wgpu::BindGroupLayoutDescriptor<'static> =
+        wgpu::BindGroupLayoutDescriptor {
+            label: Some("Post Process Sampler BGL"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            }],
+        };
+    /// Bind groups set by [`record`](Self::record)
+    const BIND_GROUPS: [wgpu::BindGroupLayoutDescriptor<'static>; 3] = [
+        Uniform::DESC,
+        HdrBackBuffer::DESC_RENDER,
+        Self::SAMPLER_DESC,
+    ];
+
+    pub fn from_path(device: &wgpu::Device, path: &Path, compiler: &mut ShaderCompiler) -> Self {
+        compiler.pipeline_or_fallback(
+            device,
+            path,
+            &ShaderDefines::default(),
+            &FallbackShader::new()
+                .vertex("vs_main")
+                .fragment("fs_main", 1),
+            |layout, shader| Self::new_with_module(device, layout, shader),
+        )
+    }
+
+    pub fn new_with_module(
+        device: &wgpu::Device,
+        layout: &ShaderLayout,
+        shader: &wgpu::ShaderModule,
+    ) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Process Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let sampler_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Post Process Sampler Bind Group"),
+            layout: &device.create_bind_group_layout(&Self::SAMPLER_DESC),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Sampler(&sampler),
+            }],
+        });
+
+        let pipeline = Self::make_pipeline(device, layout, shader);
+        Self {
+            pipeline,
+            sampler_bind_group,
+        }
+    }
+
+    fn make_pipeline(
+        device: &wgpu::Device,
+        layout: &ShaderLayout,
+        shader: &wgpu::ShaderModule,
+    ) -> wgpu::RenderPipeline {
+        let layout = layout
+            .clone()
+            .with_bind_groups(&Self::BIND_GROUPS)
+            .create_pipeline_layout(device, Some("Post Process Pass Layout"));
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Post Process Pipeline"),
+            layout: Some(&layout),
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                targets: &[HdrBackBuffer::FORMAT.into()],
+            }),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+}
+
+impl<'a> PostProcessPipeline {
+    pub fn record<'pass>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        uniform_bind_group: &'a GlobalUniformBinding,
+        input_texture_binding: &'a wgpu::BindGroup,
+    ) where
+        'a: 'pass,
+    {
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &uniform_bind_group.binding, &[]);
+        rpass.set_bind_group(1, input_texture_binding, &[]);
+        rpass.set_bind_group(2, &self.sampler_bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
+
+impl ReloadablePipeline for PostProcessPipeline {
+    fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        module: &wgpu::ShaderModule,
+        layout: &ShaderLayout,
+    ) {
+        self.pipeline = Self::make_pipeline(device, layout, module);
+    }
+}
+
+pub struct PostProcessEffect {
+    pub name: String,
+    pub enabled: bool,
+    pipeline: PipelineHandle<PostProcessPipeline>,
+}
+
+/// Ordered list of full screen effects applied to the [HdrBackBuffer]
+/// before presenting, ping-ponging between two intermediate targets.
+pub struct PostProcessChain {
+    effects: Vec<PostProcessEffect>,
+    targets: [HdrBackBuffer; 2],
+}
+
+impl PostProcessChain {
+    /// Effects in the order of application, all disabled by default.
+    pub const DEFAULT_EFFECTS: [(&'static str, &'static str); 6] = [
+        ("FXAA", "post/fxaa.wgsl"),
+        ("Sharpen", "post/sharpen.wgsl"),
+        ("Bloom", "post/bloom.wgsl"),
+        ("Chromatic Aberration", "post/chromatic_aberration.wgsl"),
+        ("Vignette", "post/vignette.wgsl"),
+        ("Film Grain", "post/film_grain.wgsl"),
+    ];
+
+    pub fn new(device: &wgpu::Device, size: (u32, u32)) -> Self {
+        Self {
+            effects: vec![],
+            targets: [
+                HdrBackBuffer::new(device, size),
+                HdrBackBuffer::new(device, size),
+            ],
+        }
+    }
+
+    pub fn with_default_effects(
+        device: &wgpu::Device,
+        size: (u32, u32),
+        watcher: &mut Watcher,
+        compiler: &mut ShaderCompiler,
+    ) -> Self {
+        let mut chain = Self::new(device, size);
+        for (name, path) in Self::DEFAULT_EFFECTS {
+            chain.push_effect(device, watcher, compiler, name, Path::new(path));
+        }
+        chain
+    }
+
+    /// Appends a disabled effect to the end of the chain.
+    pub fn push_effect(
+        &mut self,
+        device: &wgpu::Device,
+        watcher: &mut Watcher,
+        compiler: &mut ShaderCompiler,
+        name: &str,
+        path: &Path,
+    ) {
+        let pipeline = PostProcessPipeline::from_path(device, path, compiler);
+        let pipeline = watcher.register(&path, pipeline);
+        self.effects.push(PostProcessEffect {
+            name: name.to_string(),
+            enabled: false,
+            pipeline,
+        });
+    }
+
+    pub fn effects(&self) -> &[PostProcessEffect] {
+        &self.effects
+    }
+
+    pub fn effects_mut(&mut self) -> &mut [PostProcessEffect] {
+        &mut self.effects
+    }
+
+    /// Toggles the effect at `index` returning it if exists.
+    pub fn toggle(&mut self, index: usize) -> Option<&PostProcessEffect> {
+        let effect = self.effects.get_mut(index)?;
+        effect.enabled = !effect.enabled;
+        Some(effect)
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: (u32, u32)) {
+        for target in &mut self.targets {
+            target.resize(device, size);
+        }
+    }
+
+    /// Records all enabled effects and returns the buffer holding the result.
+    pub fn record<'a>(
+        &'a self,
+        encoder: &mut wgpu::CommandEncoder,
+        uniform_bind_group: &GlobalUniformBinding,
+        input: &'a HdrBackBuffer,
+    ) -> &'a HdrBackBuffer {
+        let mut source = input;
+        for (i, effect) in self.effects.iter().filter(|e| e.enabled).enumerate() {
+            let target = &self.targets[i % 2];
+            let pipeline = effect.pipeline.borrow();
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&effect.name),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: &target.texture_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            pipeline.record(&mut rpass, uniform_bind_group, &source.render_bind_group);
+            drop(rpass);
+            source = target;
+        }
+        source
+    }
+}
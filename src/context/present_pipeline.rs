@@ -1,96 +1,109 @@
 use std::path::Path;
 
 use crate::{
-    context::{global_ubo::GlobalUniformBinding, Uniform},
-    utils::shader_compiler::ShaderCompiler,
+    context::{
+        global_ubo::GlobalUniformBinding, tonemapping::TonemapBinding, HdrBackBuffer, Uniform,
+    },
+    utils::{
+        fallback::FallbackShader, preprocessor::ShaderDefines, reflection::ShaderLayout,
+        shader_compiler::ShaderCompiler,
+    },
     watcher::ReloadablePipeline,
 };
 
 pub struct PresentPipeline {
     pub pipeline: wgpu::RenderPipeline,
+    pub upscale_pipeline: wgpu::RenderPipeline,
     surface_format: wgpu::TextureFormat,
     sampler_bind_group: wgpu::BindGroup,
 }
 
 impl PresentPipeline {
+    const SAMPLER_DESC: wgpu::BindGroupLayoutDescriptor<'static> =
+        wgpu::BindGroupLayoutDescriptor {
+            label: Some("Present Sampler BGL"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            }],
+        };
+    /// Bind groups set by [`record`](Self::record)
+    const BIND_GROUPS: [wgpu::BindGroupLayoutDescriptor<'static>; 4] = [
+        Uniform::DESC,
+        HdrBackBuffer::DESC_RENDER,
+        Self::SAMPLER_DESC,
+        TonemapBinding::DESC,
+    ];
+
+    /// Variant of the shader for `surface_format`: sRGB surfaces encode the
+    /// output themselves, the screenshot texture is always encoded by the shader.
+    pub fn defines(surface_format: wgpu::TextureFormat) -> ShaderDefines {
+        let mut defines = ShaderDefines::new();
+        if surface_format.describe().srgb {
+            defines.insert("SRGB_SURFACE");
+        }
+        defines
+    }
+
     pub fn from_path(
         device: &wgpu::Device,
         surface_format: wgpu::TextureFormat,
         path: &Path,
+        defines: &ShaderDefines,
         compiler: &mut ShaderCompiler,
     ) -> Self {
-        let shader = unsafe {
-            device.create_shader_module_spirv(&wgpu::ShaderModuleDescriptorSpirV {
-                label: path.to_str(),
-                source: compiler.create_shader_module(path).unwrap().into(),
-            })
-        };
-        Self::new_with_module(device, surface_format, &shader)
+        compiler.pipeline_or_fallback(
+            device,
+            path,
+            defines,
+            &FallbackShader::new()
+                .vertex("vs_main")
+                .fragment("fs_main", 2)
+                .fragment("fs_main_bicubic", 2),
+            |layout, shader| Self::new_with_module(device, surface_format, layout, shader),
+        )
     }
 
     pub fn new_with_module(
         device: &wgpu::Device,
         surface_format: wgpu::TextureFormat,
+        layout: &ShaderLayout,
         shader: &wgpu::ShaderModule,
     ) -> Self {
-        let global_bind_group_layout = device.create_bind_group_layout(&Uniform::DESC);
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Present Texture BGL"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        multisampled: false,
-                    },
-                    count: None,
-                }],
-            });
-        let sampler_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Present Sampler BGL"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                }],
-            });
-        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Screen Pass Layout"),
-            bind_group_layouts: &[
-                &global_bind_group_layout,
-                &texture_bind_group_layout,
-                &sampler_bind_group_layout,
-            ],
-            push_constant_ranges: &[],
-        });
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Present Pipeline"),
-            layout: Some(&layout),
-            fragment: Some(wgpu::FragmentState {
-                module: shader,
-                entry_point: "fs_main",
-                targets: &[
-                    surface_format.into(),
-                    wgpu::TextureFormat::Rgba8Unorm.into(),
-                ],
-            }),
-            vertex: wgpu::VertexState {
-                module: shader,
-                entry_point: "vs_main",
-                buffers: &[],
-            },
-            primitive: wgpu::PrimitiveState::default(),
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                ..Default::default()
-            },
-            multiview: None,
-        });
+        let layout = layout
+            .clone()
+            .with_bind_groups(&Self::BIND_GROUPS)
+            .create_pipeline_layout(device, Some("Screen Pass Layout"));
+        let make_pipeline = |entry_point| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Present Pipeline"),
+                layout: Some(&layout),
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point,
+                    targets: &[
+                        surface_format.into(),
+                        wgpu::TextureFormat::Rgba8Unorm.into(),
+                    ],
+                }),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    ..Default::default()
+                },
+                multiview: None,
+            })
+        };
+        let pipeline = make_pipeline("fs_main");
+        let upscale_pipeline = make_pipeline("fs_main_bicubic");
 
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("Present Sampler"),
@@ -104,7 +117,7 @@ impl PresentPipeline {
         });
         let sampler_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Present Sampler Bind Group"),
-            layout: &sampler_bind_group_layout,
+            layout: &device.create_bind_group_layout(&Self::SAMPLER_DESC),
             entries: &[wgpu::BindGroupEntry {
                 binding: 0,
                 resource: wgpu::BindingResource::Sampler(&sampler),
@@ -113,6 +126,7 @@ impl PresentPipeline {
 
         Self {
             pipeline,
+            upscale_pipeline,
             surface_format,
             sampler_bind_group,
         }
@@ -125,19 +139,31 @@ impl<'a> PresentPipeline {
         rpass: &mut wgpu::RenderPass<'pass>,
         uniform_bind_group: &'a GlobalUniformBinding,
         input_texture_binding: &'a wgpu::BindGroup,
+        tonemap_binding: &'a TonemapBinding,
+        upscale: bool,
     ) where
         'a: 'pass,
     {
-        rpass.set_pipeline(&self.pipeline);
+        if upscale {
+            rpass.set_pipeline(&self.upscale_pipeline);
+        } else {
+            rpass.set_pipeline(&self.pipeline);
+        }
         rpass.set_bind_group(0, &uniform_bind_group.binding, &[]);
         rpass.set_bind_group(1, input_texture_binding, &[]);
         rpass.set_bind_group(2, &self.sampler_bind_group, &[]);
+        rpass.set_bind_group(3, &tonemap_binding.bind_group, &[]);
         rpass.draw(0..3, 0..1);
     }
 }
 
 impl ReloadablePipeline for PresentPipeline {
-    fn reload(&mut self, device: &wgpu::Device, module: &wgpu::ShaderModule) {
-        *self = Self::new_with_module(device, self.surface_format, module);
+    fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        module: &wgpu::ShaderModule,
+        layout: &ShaderLayout,
+    ) {
+        *self = Self::new_with_module(device, self.surface_format, layout, module);
     }
 }
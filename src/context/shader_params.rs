@@ -0,0 +1,351 @@
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use color_eyre::eyre::{eyre, Result};
+use wgpu::util::DeviceExt;
+use winit::event::VirtualKeyCode;
+
+use crate::{
+    context::AssetHandle,
+    utils::{
+        params::{parse_config, splat, ParamInfo, ParamLayout},
+        preprocessor::{Preprocessor, ShaderDefines},
+        shader_roots::ShaderRoots,
+    },
+    watcher::{canonical_asset_path, ReloadableAsset, Watcher},
+};
+
+/// Uniform buffer with the [annotated fields](ParamInfo) of a shader.
+///
+/// Values start at their defaults and can be changed from code, with the
+/// keyboard (`Tab` selects a value, `[` and `]` decrease and increase it) or
+/// from a config file of `name = value` lines. Once [registered](Self::register),
+/// fields added to the shader or its includes and changes of the config file
+/// are picked up without restarting.
+pub struct ShaderParams {
+    layout: ParamLayout,
+    /// Per param, only its components are used
+    values: Vec<[f32; 4]>,
+    /// Values of the config file, applied to params without a value
+    config_values: Vec<(String, Vec<f32>)>,
+    buffer: wgpu::Buffer,
+    buffer_size: u32,
+    pub bind_group: wgpu::BindGroup,
+    shader: PathBuf,
+    /// Files on disk the shader is built from
+    files: Vec<PathBuf>,
+    config: Option<PathBuf>,
+    preprocessor: Preprocessor,
+    /// Param and component adjusted by the keyboard
+    selected: (usize, usize),
+    dirty: bool,
+}
+
+impl ShaderParams {
+    pub const DESC: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
+        label: Some("Shader Params Bind Group Layout"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT.union(wgpu::ShaderStages::COMPUTE),
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                // The struct grows when fields are added to the shader
+                min_binding_size: None,
+            },
+            count: None,
+        }],
+    };
+
+    /// Params of the shader at `path`, resolved against `roots`.
+    pub fn new(device: &wgpu::Device, path: &Path, roots: &ShaderRoots) -> Result<Self> {
+        let shader = roots
+            .resolve(path)
+            .ok_or_else(|| eyre!("shader {} not found", path.display()))?;
+        let preprocessor = Preprocessor::new(roots.clone());
+        let (layout, files) = parse_layout(&preprocessor, &shader)?;
+        Ok(Self::with_layout(
+            device,
+            layout,
+            shader,
+            files,
+            preprocessor,
+        ))
+    }
+
+    /// Params of the copy of the shader at `path` embedded in the binary, for
+    /// when [`new`](Self::new) fails. Once [registered](Self::register), the
+    /// shader found in `roots` is still watched and replaces them as soon as
+    /// it parses. Without a usable embedded copy there are no params.
+    pub fn embedded(device: &wgpu::Device, path: &Path, roots: &ShaderRoots) -> Self {
+        let embedded = Path::new(ShaderRoots::EMBEDDED).join(path);
+        let preprocessor = Preprocessor::new(roots.clone());
+        let layout = match parse_layout(&preprocessor, &embedded) {
+            Ok((layout, _)) => layout,
+            Err(_) => ParamLayout {
+                struct_name: String::new(),
+                params: vec![],
+                size: 16,
+            },
+        };
+        let shader = roots.resolve(path).unwrap_or(embedded);
+        let files = Some(shader.clone())
+            .filter(|x| !ShaderRoots::is_embedded(x))
+            .into_iter()
+            .collect();
+        Self::with_layout(device, layout, shader, files, preprocessor)
+    }
+
+    fn with_layout(
+        device: &wgpu::Device,
+        layout: ParamLayout,
+        shader: PathBuf,
+        files: Vec<PathBuf>,
+        preprocessor: Preprocessor,
+    ) -> Self {
+        let values = layout.params.iter().map(|x| x.default).collect();
+        let buffer_size = layout.size;
+        let (buffer, bind_group) = create_buffer(device, buffer_size);
+        Self {
+            layout,
+            values,
+            config_values: vec![],
+            buffer,
+            buffer_size,
+            bind_group,
+            shader,
+            files,
+            config: None,
+            preprocessor,
+            selected: (0, 0),
+            dirty: true,
+        }
+    }
+
+    /// Takes values from the config file at `path`, it doesn't have to exist yet.
+    pub fn with_config(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            if let Err(err) = self.apply_config(&text) {
+                eprintln!("{}: {err}", path.display());
+            }
+        }
+        self.config = Some(path);
+        self
+    }
+
+    /// Reloads the params when the shader, one of its includes or the config file changes.
+    pub fn register(self, watcher: &mut Watcher) -> AssetHandle<Self> {
+        let files: Vec<_> = self.files.iter().chain(&self.config).cloned().collect();
+        let params = Rc::new(RefCell::new(self));
+        for file in files {
+            watcher.register_asset_handle(&file, params.clone());
+        }
+        params
+    }
+
+    pub fn params(&self) -> &[ParamInfo] {
+        &self.layout.params
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[f32]> {
+        let i = self.index(name)?;
+        Some(&self.values[i][..self.layout.params[i].components])
+    }
+
+    /// Sets every component to a single value or each to its own, clamped to the bounds.
+    ///
+    /// Returns `false` if there is no such param or the number of values doesn't fit.
+    pub fn set(&mut self, name: &str, values: &[f32]) -> bool {
+        let i = match self.index(name) {
+            Some(i) => i,
+            None => return false,
+        };
+        let param = &self.layout.params[i];
+        let values = match splat(values, param.components) {
+            Some(values) => values,
+            None => return false,
+        };
+        for (value, new) in self.values[i].iter_mut().zip(values) {
+            *value = param.clamp(new);
+        }
+        self.dirty = true;
+        true
+    }
+
+    /// Handles the keyboard controls, returns `true` if `key` was one of them.
+    pub fn handle_key(&mut self, key: VirtualKeyCode) -> bool {
+        if self.layout.params.is_empty() {
+            return false;
+        }
+        let (param, component) = self.selected;
+        match key {
+            VirtualKeyCode::Tab => {
+                self.selected = if component + 1 < self.layout.params[param].components {
+                    (param, component + 1)
+                } else {
+                    ((param + 1) % self.layout.params.len(), 0)
+                };
+            }
+            VirtualKeyCode::LBracket | VirtualKeyCode::RBracket => {
+                let info = &self.layout.params[param];
+                let step = match key {
+                    VirtualKeyCode::LBracket => -info.step,
+                    _ => info.step,
+                };
+                let value = &mut self.values[param][component];
+                *value = info.clamp(*value + step);
+                self.dirty = true;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Name and value of the component adjusted by the keyboard, like `background.y`.
+    pub fn selected(&self) -> Option<(String, f32)> {
+        let (param, component) = self.selected;
+        let info = self.layout.params.get(param)?;
+        let name = match info.components {
+            1 => info.name.clone(),
+            _ => format!("{}.{}", info.name, ['x', 'y', 'z', 'w'][component]),
+        };
+        Some((name, self.values[param][component]))
+    }
+
+    /// Uploads changed values.
+    pub fn update(&mut self, queue: &wgpu::Queue) {
+        if self.dirty {
+            queue.write_buffer(&self.buffer, 0, &self.bytes());
+            self.dirty = false;
+        }
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.layout.params.iter().position(|x| x.name == name)
+    }
+
+    fn apply_config(&mut self, text: &str) -> Result<()> {
+        self.config_values = parse_config(text)?;
+        for (name, values) in self.config_values.clone() {
+            if !self.set(&name, &values) {
+                eprintln!("Unknown param `{name}` or wrong number of values");
+            }
+        }
+        Ok(())
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0; self.layout.size as usize];
+        for (param, values) in self.layout.params.iter().zip(&self.values) {
+            let offset = param.offset as usize;
+            let values = bytemuck::cast_slice(&values[..param.components]);
+            bytes[offset..offset + values.len()].copy_from_slice(values);
+        }
+        bytes
+    }
+}
+
+impl ReloadableAsset for ShaderParams {
+    fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &Path,
+        contents: &[u8],
+    ) -> Result<()> {
+        if self.config.as_deref().map(canonical_asset_path).as_deref() == Some(path) {
+            self.apply_config(std::str::from_utf8(contents)?)?;
+        } else {
+            // The struct may live in any of the files, so the whole shader is parsed again
+            let (layout, files) = parse_layout(&self.preprocessor, &self.shader)?;
+            // Values stay with the param of the same name
+            let values = layout
+                .params
+                .iter()
+                .map(|param| {
+                    let old = self
+                        .get(&param.name)
+                        .filter(|x| x.len() == param.components)
+                        .map(|x| splat(x, param.components).unwrap());
+                    let config = self
+                        .config_values
+                        .iter()
+                        .find(|(name, _)| name == &param.name)
+                        .and_then(|(_, values)| splat(values, param.components));
+                    old.or(config).unwrap_or(param.default)
+                })
+                .collect();
+            // Pipelines still built from the old shader may use a larger struct
+            if layout.size > self.buffer_size {
+                (self.buffer, self.bind_group) = create_buffer(device, layout.size);
+                self.buffer_size = layout.size;
+            }
+            self.layout = layout;
+            self.values = values;
+            self.files = files;
+            self.selected = (0, 0);
+        }
+        self.dirty = true;
+        self.update(queue);
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for ShaderParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (param, values) in self.layout.params.iter().zip(&self.values) {
+            let values: Vec<_> = values[..param.components]
+                .iter()
+                .map(|x| x.to_string())
+                .collect();
+            writeln!(
+                f,
+                "\t{} = {}\t[{}, {}] step {}",
+                param.name,
+                values.join(" "),
+                param.min,
+                param.max,
+                param.step
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Layout of the params of the shader at the resolved `path` and the files on disk it includes.
+fn parse_layout(preprocessor: &Preprocessor, path: &Path) -> Result<(ParamLayout, Vec<PathBuf>)> {
+    let source = ShaderRoots::read(path)?;
+    let source = preprocessor.process(path, &source, &ShaderDefines::default())?;
+    let layout = ParamLayout::parse(&source)?
+        .ok_or_else(|| eyre!("{} has no @param fields", path.display()))?;
+    let files = source
+        .source_map
+        .files()
+        .iter()
+        .filter(|x| !ShaderRoots::is_embedded(x))
+        .cloned()
+        .collect();
+    Ok((layout, files))
+}
+
+fn create_buffer(device: &wgpu::Device, size: u32) -> (wgpu::Buffer, wgpu::BindGroup) {
+    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Shader Params"),
+        contents: &vec![0; size as usize],
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+    let layout = device.create_bind_group_layout(&ShaderParams::DESC);
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Shader Params Bind Group"),
+        layout: &layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: buffer.as_entire_binding(),
+        }],
+    });
+    (buffer, bind_group)
+}
@@ -0,0 +1,305 @@
+use std::path::Path;
+
+use glam::Vec2;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    camera::CameraBinding,
+    context::{global_ubo::GlobalUniformBinding, HdrBackBuffer, PipelineHandle, Uniform},
+    utils::{
+        fallback::FallbackShader, preprocessor::ShaderDefines, reflection::ShaderLayout,
+        shader_compiler::ShaderCompiler, NonZeroSized,
+    },
+    watcher::{ReloadablePipeline, Watcher},
+};
+
+pub use crate::shader_types::TaaUniform;
+
+pub struct TaaPipeline {
+    pub pipeline: wgpu::RenderPipeline,
+}
+
+impl TaaPipeline {
+    /// Bind groups set by [`Taa::record`]
+    const BIND_GROUPS: [wgpu::BindGroupLayoutDescriptor<'static>; 4] = [
+        Uniform::DESC,
+        CameraBinding::DESC,
+        HdrBackBuffer::DESC_RENDER,
+        Taa::HISTORY_DESC,
+    ];
+
+    pub fn from_path(device: &wgpu::Device, path: &Path, compiler: &mut ShaderCompiler) -> Self {
+        compiler.pipeline_or_fallback(
+            device,
+            path,
+            &ShaderDefines::default(),
+            &FallbackShader::new()
+                .vertex("vs_main")
+                .fragment("fs_main", 1),
+            |layout, shader| Self::new_with_module(device, layout, shader),
+        )
+    }
+
+    pub fn new_with_module(
+        device: &wgpu::Device,
+        layout: &ShaderLayout,
+        shader: &wgpu::ShaderModule,
+    ) -> Self {
+        let layout = layout
+            .clone()
+            .with_bind_groups(&Self::BIND_GROUPS)
+            .create_pipeline_layout(device, Some("TAA Pass Layout"));
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("TAA Resolve Pipeline"),
+            layout: Some(&layout),
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                targets: &[HdrBackBuffer::FORMAT.into()],
+            }),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        Self { pipeline }
+    }
+}
+
+impl ReloadablePipeline for TaaPipeline {
+    fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        module: &wgpu::ShaderModule,
+        layout: &ShaderLayout,
+    ) {
+        *self = Self::new_with_module(device, layout, module);
+    }
+}
+
+/// Temporal anti-aliasing: jitters the camera every frame and accumulates
+/// the [HdrBackBuffer] into a reprojected history buffer.
+pub struct Taa {
+    pub enabled: bool,
+    pipeline: PipelineHandle<TaaPipeline>,
+    history: [HdrBackBuffer; 2],
+    history_bind_groups: [wgpu::BindGroup; 2],
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    current: usize,
+    frame_index: u32,
+    /// Whether the history buffer holds the previous frame at the current size
+    history_valid: bool,
+}
+
+impl Taa {
+    /// Length of the Halton sequence used for subpixel jitter.
+    const JITTER_PERIOD: u32 = 16;
+    const GOLDEN_RATIO_CONJUGATE: f32 = 0.618_034;
+
+    pub const HISTORY_DESC: wgpu::BindGroupLayoutDescriptor<'static> =
+        wgpu::BindGroupLayoutDescriptor {
+            label: Some("TAA History Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(TaaUniform::SIZE),
+                    },
+                    count: None,
+                },
+            ],
+        };
+
+    pub fn new(
+        device: &wgpu::Device,
+        size: (u32, u32),
+        watcher: &mut Watcher,
+        compiler: &mut ShaderCompiler,
+    ) -> Self {
+        let path = Path::new("taa.wgsl");
+        let pipeline = TaaPipeline::from_path(device, path, compiler);
+        let pipeline = watcher.register(&path, pipeline);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("TAA History Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let history = [
+            HdrBackBuffer::new(device, size),
+            HdrBackBuffer::new(device, size),
+        ];
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("TAA Uniform"),
+            contents: bytemuck::bytes_of(&TaaUniform { history_valid: 0 }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let history_bind_groups =
+            Self::make_history_bind_groups(device, &history, &sampler, &uniform_buffer);
+
+        Self {
+            enabled: false,
+            pipeline,
+            history,
+            history_bind_groups,
+            sampler,
+            uniform_buffer,
+            current: 0,
+            frame_index: 0,
+            history_valid: false,
+        }
+    }
+
+    fn make_history_bind_groups(
+        device: &wgpu::Device,
+        history: &[HdrBackBuffer; 2],
+        sampler: &wgpu::Sampler,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> [wgpu::BindGroup; 2] {
+        let layout = device.create_bind_group_layout(&Self::HISTORY_DESC);
+        let make_bind_group = |buffer: &HdrBackBuffer| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("TAA History Bind Group"),
+                layout: &layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&buffer.texture_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        };
+        [make_bind_group(&history[0]), make_bind_group(&history[1])]
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: (u32, u32)) {
+        self.invalidate_history();
+        if self.history[0].size() == size {
+            return;
+        }
+        for buffer in &mut self.history {
+            buffer.resize(device, size);
+        }
+        self.history_bind_groups = Self::make_history_bind_groups(
+            device,
+            &self.history,
+            &self.sampler,
+            &self.uniform_buffer,
+        );
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        // The history stopped following the camera while TAA was off
+        self.invalidate_history();
+    }
+
+    /// Makes the next frame take only the current sample, for when the
+    /// history no longer matches what is rendered.
+    pub fn invalidate_history(&mut self) {
+        self.history_valid = false;
+    }
+
+    /// Advances the jitter sequence, returning the subpixel camera offset in NDC
+    /// and the ray start offset in `[0, 1)`.
+    pub fn next_frame(&mut self, queue: &wgpu::Queue, resolution: (u32, u32)) -> (Vec2, f32) {
+        if !self.enabled {
+            return (Vec2::ZERO, 0.);
+        }
+        let uniform = TaaUniform {
+            history_valid: self.history_valid as _,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+        // This frame's output is the history of the next one
+        self.history_valid = true;
+
+        self.frame_index = self.frame_index.wrapping_add(1);
+        self.current = 1 - self.current;
+
+        let index = self.frame_index % Self::JITTER_PERIOD + 1;
+        let offset = Vec2::new(halton(index, 2), halton(index, 3)) - 0.5;
+        let jitter = 2. * offset / Vec2::new(resolution.0 as f32, resolution.1 as f32);
+        let ray_offset = (self.frame_index as f32 * Self::GOLDEN_RATIO_CONJUGATE).fract();
+        (jitter, ray_offset)
+    }
+
+    /// Resolves `input` against the history and returns the anti-aliased buffer.
+    pub fn record<'a>(
+        &'a self,
+        encoder: &mut wgpu::CommandEncoder,
+        uniform_bind_group: &GlobalUniformBinding,
+        camera_binding: &CameraBinding,
+        input: &HdrBackBuffer,
+    ) -> &'a HdrBackBuffer {
+        let target = &self.history[self.current];
+        let pipeline = self.pipeline.borrow();
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("TAA Resolve Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: &target.texture_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(&pipeline.pipeline);
+        rpass.set_bind_group(0, &uniform_bind_group.binding, &[]);
+        rpass.set_bind_group(1, &camera_binding.bind_group, &[]);
+        rpass.set_bind_group(2, &input.render_bind_group, &[]);
+        rpass.set_bind_group(3, &self.history_bind_groups[1 - self.current], &[]);
+        rpass.draw(0..3, 0..1);
+        drop(rpass);
+
+        target
+    }
+}
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.;
+    let mut fraction = 1.;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
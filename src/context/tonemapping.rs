@@ -0,0 +1,172 @@
+use crate::utils::NonZeroSized;
+
+use wgpu::util::DeviceExt;
+
+pub use crate::shader_types::{Luminance, TonemapUniform};
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TonemapOperator {
+    None = 0,
+    Reinhard = 1,
+    ReinhardExtended = 2,
+    AcesFitted = 3,
+    AgX = 4,
+    Uncharted2 = 5,
+}
+
+impl TonemapOperator {
+    const ALL: [Self; 6] = [
+        Self::None,
+        Self::Reinhard,
+        Self::ReinhardExtended,
+        Self::AcesFitted,
+        Self::AgX,
+        Self::Uncharted2,
+    ];
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+}
+
+impl From<u32> for TonemapOperator {
+    fn from(x: u32) -> Self {
+        Self::ALL.get(x as usize).copied().unwrap_or(Self::None)
+    }
+}
+
+impl Default for TonemapUniform {
+    fn default() -> Self {
+        Self {
+            mode: TonemapOperator::AcesFitted as _,
+            exposure: 0.,
+            gamma: 1.,
+            white_point: 4.,
+            auto_exposure: false as _,
+        }
+    }
+}
+
+impl TonemapUniform {
+    const EXPOSURE_STEP: f32 = 0.25;
+
+    pub fn operator(&self) -> TonemapOperator {
+        self.mode.into()
+    }
+
+    pub fn set_operator(&mut self, operator: TonemapOperator) {
+        self.mode = operator as _;
+    }
+
+    pub fn next_operator(&mut self) {
+        self.set_operator(self.operator().next());
+    }
+
+    pub fn add_exposure(&mut self, delta: f32) {
+        self.exposure += delta;
+    }
+
+    pub fn increase_exposure(&mut self) {
+        self.add_exposure(Self::EXPOSURE_STEP);
+    }
+
+    pub fn decrease_exposure(&mut self) {
+        self.add_exposure(-Self::EXPOSURE_STEP);
+    }
+
+    pub fn toggle_auto_exposure(&mut self) {
+        self.auto_exposure = (self.auto_exposure == 0) as _;
+    }
+}
+
+impl std::fmt::Display for TonemapUniform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "tonemapping: {:?}, exposure: {:+.2}EV{}, gamma: {:.2}, white point: {:.2}",
+            self.operator(),
+            self.exposure,
+            if self.auto_exposure != 0 {
+                " (auto)"
+            } else {
+                ""
+            },
+            self.gamma,
+            self.white_point,
+        )
+    }
+}
+
+pub struct TonemapBinding {
+    pub bind_group: wgpu::BindGroup,
+    buffer: wgpu::Buffer,
+    pub luminance_buffer: wgpu::Buffer,
+}
+
+impl TonemapBinding {
+    pub const DESC: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
+        label: Some("Tonemap Bind Group Layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(TonemapUniform::SIZE),
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(Luminance::SIZE),
+                },
+                count: None,
+            },
+        ],
+    };
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Tonemap Uniform"),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            contents: bytemuck::bytes_of(&TonemapUniform::default()),
+        });
+        let luminance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Average Luminance Buffer"),
+            usage: wgpu::BufferUsages::STORAGE,
+            contents: bytemuck::bytes_of(&Luminance { average: 0.18 }),
+        });
+
+        let layout = device.create_bind_group_layout(&Self::DESC);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Tonemap Bind Group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: luminance_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            bind_group,
+            buffer,
+            luminance_buffer,
+        }
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue, uniform: &TonemapUniform) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(uniform))
+    }
+}
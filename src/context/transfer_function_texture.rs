@@ -0,0 +1,128 @@
+use std::{num::NonZeroU32, path::Path};
+
+use color_eyre::eyre::Result;
+
+use crate::{utils::transfer_function::TransferFunction, watcher::ReloadableAsset};
+
+/// [TransferFunction] as a `RESOLUTION`x1 texture, sampled with `v = 0.5`.
+pub struct TransferFunctionTexture {
+    pub texture: wgpu::Texture,
+    pub bind_group: wgpu::BindGroup,
+    pub sampler: wgpu::Sampler,
+}
+
+impl TransferFunctionTexture {
+    pub const DESC: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
+        label: Some("Transfer Function BGL"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    };
+
+    /// Loads a `.tf` file, see [`TransferFunction::parse`].
+    pub fn from_path(device: &wgpu::Device, queue: &wgpu::Queue, path: &Path) -> Result<Self> {
+        let transfer_function = TransferFunction::load(path)?;
+        Ok(Self::new(device, queue, &transfer_function))
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        transfer_function: &TransferFunction,
+    ) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Transfer Function Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let (texture, bind_group) = Self::upload(device, queue, &sampler, transfer_function);
+
+        Self {
+            texture,
+            bind_group,
+            sampler,
+        }
+    }
+
+    fn upload(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        sampler: &wgpu::Sampler,
+        transfer_function: &TransferFunction,
+    ) -> (wgpu::Texture, wgpu::BindGroup) {
+        let size = wgpu::Extent3d {
+            width: transfer_function.colors.len() as _,
+            height: 1,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Transfer Function Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+        });
+        let texture_view = texture.create_view(&Default::default());
+
+        queue.write_texture(
+            texture.as_image_copy(),
+            bytemuck::cast_slice(&transfer_function.colors),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(size.width * 4),
+                rows_per_image: None,
+            },
+            size,
+        );
+
+        let bind_group_layout = device.create_bind_group_layout(&Self::DESC);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Transfer Function Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        });
+        (texture, bind_group)
+    }
+}
+
+impl ReloadableAsset for TransferFunctionTexture {
+    fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &Path,
+        contents: &[u8],
+    ) -> Result<()> {
+        let transfer_function = TransferFunction::parse(path, contents)?;
+        (self.texture, self.bind_group) =
+            Self::upload(device, queue, &self.sampler, &transfer_function);
+        Ok(())
+    }
+}
@@ -1,4 +1,8 @@
-use std::num::NonZeroU32;
+use std::{num::NonZeroU32, path::Path};
+
+use color_eyre::eyre::{bail, Result};
+
+use crate::{utils::volume::Volume, watcher::ReloadableAsset};
 
 pub struct VolumeTexture {
     pub texture: wgpu::Texture,
@@ -29,12 +33,49 @@ impl VolumeTexture {
         ],
     };
 
+    /// The bonsai volume embedded in the binary.
     pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
-        let data = include_bytes!("../../bonsai_256x256x256_uint8.raw");
+        let volume = Volume {
+            size: [256; 3],
+            data: include_bytes!("../../bonsai_256x256x256_uint8.raw").to_vec(),
+        };
+        Self::from_volume(device, queue, &volume)
+    }
+
+    /// Loads a `.raw` or `.nrrd` volume, see [`Volume::parse`].
+    pub fn from_path(device: &wgpu::Device, queue: &wgpu::Queue, path: &Path) -> Result<Self> {
+        let volume = Volume::load(path)?;
+        check_size(device, &volume)?;
+        Ok(Self::from_volume(device, queue, &volume))
+    }
+
+    pub fn from_volume(device: &wgpu::Device, queue: &wgpu::Queue, volume: &Volume) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Present Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let (texture, bind_group) = Self::upload(device, queue, &sampler, volume);
+
+        VolumeTexture {
+            texture,
+            bind_group,
+            sampler,
+        }
+    }
+
+    fn upload(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        sampler: &wgpu::Sampler,
+        volume: &Volume,
+    ) -> (wgpu::Texture, wgpu::BindGroup) {
+        let [width, height, depth] = volume.size;
         let size = wgpu::Extent3d {
-            width: 256,
-            height: 256,
-            depth_or_array_layers: 256,
+            width,
+            height,
+            depth_or_array_layers: depth,
         };
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Foot Texture"),
@@ -49,22 +90,15 @@ impl VolumeTexture {
 
         queue.write_texture(
             texture.as_image_copy(),
-            data,
+            &volume.data,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: NonZeroU32::new(256),
-                rows_per_image: NonZeroU32::new(256),
+                bytes_per_row: NonZeroU32::new(width),
+                rows_per_image: NonZeroU32::new(height),
             },
             size,
         );
 
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("Present Sampler"),
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            ..Default::default()
-        });
-
         let bind_group_layout = device.create_bind_group_layout(&Self::DESC);
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Foot Bind Group"),
@@ -76,15 +110,34 @@ impl VolumeTexture {
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
+                    resource: wgpu::BindingResource::Sampler(sampler),
                 },
             ],
         });
+        (texture, bind_group)
+    }
+}
 
-        VolumeTexture {
-            texture,
-            bind_group,
-            sampler,
-        }
+impl ReloadableAsset for VolumeTexture {
+    fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &Path,
+        contents: &[u8],
+    ) -> Result<()> {
+        let volume = Volume::parse(path, contents)?;
+        check_size(device, &volume)?;
+        (self.texture, self.bind_group) = Self::upload(device, queue, &self.sampler, &volume);
+        Ok(())
+    }
+}
+
+fn check_size(device: &wgpu::Device, volume: &Volume) -> Result<()> {
+    let max = device.limits().max_texture_dimension_3d;
+    if volume.size.iter().any(|&x| x == 0 || x > max) {
+        let [x, y, z] = volume.size;
+        bail!("{x}x{y}x{z} volume doesn't fit in a 3D texture of up to {max}^3 texels");
     }
+    Ok(())
 }
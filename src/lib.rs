@@ -1,21 +1,42 @@
-#![feature(get_mut_unchecked)]
-
-use std::{
-    path::{Path, PathBuf},
-    time::Instant,
-};
+use std::time::Instant;
 
 pub mod camera;
 pub mod context;
+mod shader_types;
+mod shared;
+#[cfg(feature = "ui")]
+mod ui;
 mod utils;
 mod watcher;
 
 pub use camera::{Camera, CameraBinding};
 pub use context::{
-    Context, GlobalUniformBinding, HdrBackBuffer, PipelineHandle, Uniform, VolumeTexture,
+    AssetHandle, Context, DepthTexture, GlobalUniformBinding, HdrBackBuffer, Mesh, MeshPipeline,
+    MeshUniform, PipelineHandle, PostProcessChain, ShaderParams, TonemapOperator, TonemapUniform,
+    TransferFunctionTexture, Uniform, VolumeTexture,
+};
+pub use shared::codegen;
+pub use utils::{
+    diagnostic::{Diagnostic, DiagnosticLabel},
+    dispatch_optimal,
+    dynamic_resolution::DynamicResolution,
+    fallback::FallbackShader,
+    params::{ParamInfo, ParamLayout},
+    preprocessor::ShaderDefines,
+    reflection::ShaderLayout,
+    shader_compiler,
+    shader_roots::ShaderRoots,
+    struct_layout::{
+        size_of_pointee, FieldLayout, LayoutMismatch, ShaderStruct, StructLayout, StructRegistry,
+    },
+    transfer_function::TransferFunction,
+    volume::Volume,
+    NonZeroSized,
 };
-pub use utils::{dispatch_optimal, shader_compiler, NonZeroSized};
-pub use watcher::{ReloadablePipeline, Watcher};
+pub use watcher::{ReloadableAsset, ReloadablePipeline, ShaderKey, Watcher, WatcherEvent};
+
+#[cfg(feature = "ui")]
+pub use egui;
 
 use color_eyre::eyre::Result;
 use pollster::FutureExt;
@@ -30,9 +51,9 @@ use winit::{
     window::Window,
 };
 
-const SHADER_FOLDER: &str = "shaders";
 const SCREENSHOTS_FOLDER: &str = "screenshots";
 const VIDEO_FOLDER: &str = "recordings";
+const PROFILES_FOLDER: &str = "profiles";
 
 pub trait Demo: 'static + Sized {
     fn init(ctx: &mut Context) -> Self;
@@ -40,23 +61,35 @@ pub trait Demo: 'static + Sized {
     fn update(&mut self, _: &mut Context) {}
     fn update_input(&mut self, _: WindowEvent) {}
     fn render(&mut self, _: &Context) {}
+    /// Adds panels to the overlay, called every frame before [`render`](Self::render).
+    #[cfg(feature = "ui")]
+    fn ui(&mut self, _: &egui::Context) {}
 }
 
+/// Runs the demo `D`, resolving shaders against `shader_roots`.
+///
+/// Executables usually pass [`ShaderRoots::from_args`] so shader folders can
+/// be given on the command line.
 pub fn run<D: Demo>(
-    event_loop: EventLoop<(PathBuf, wgpu::ShaderModule)>,
+    event_loop: EventLoop<WatcherEvent>,
     window: Window,
     camera: Option<Camera>,
+    shader_roots: ShaderRoots,
 ) -> Result<()> {
     // Initialize hooks for pretty errors and logging
     color_eyre::install()?;
     env_logger::init();
 
-    let mut context = Context::new(&window, &event_loop, camera).block_on()?;
+    let mut context = Context::new(&window, &event_loop, camera, shader_roots).block_on()?;
 
     let mut recording_status = false;
     let recorder = utils::recorder::Recorder::new();
 
-    print_help(context.get_info(), &recorder.ffmpeg_version);
+    print_help(
+        context.get_info(),
+        &recorder.ffmpeg_version,
+        context.shader_compiler.roots(),
+    );
 
     let mut frame_counter = FrameCounter::new();
     let mut input = Input::new();
@@ -66,6 +99,9 @@ pub fn run<D: Demo>(
     let zoom_speed = 0.002;
 
     let mut demo = D::init(&mut context);
+    context.show_shader_errors();
+    #[cfg(feature = "ui")]
+    let mut ui = ui::Ui::new(&context, &window);
 
     let mut main_window_focused = false;
     event_loop.run(move |event, _, control_flow| {
@@ -80,6 +116,10 @@ pub fn run<D: Demo>(
             Event::WindowEvent {
                 event, window_id, ..
             } if window.id() == window_id => {
+                #[cfg(feature = "ui")]
+                if ui.handle_event(&event) {
+                    return;
+                }
                 input.update(&event, &window);
 
                 match event {
@@ -122,6 +162,67 @@ pub fn run<D: Demo>(
                             },
                         ..
                     } => {
+                        let tonemap_changed = match keycode {
+                            VirtualKeyCode::F2 => {
+                                context.tonemap.next_operator();
+                                true
+                            }
+                            VirtualKeyCode::F3 => {
+                                context.toggle_auto_exposure();
+                                true
+                            }
+                            VirtualKeyCode::Equals | VirtualKeyCode::NumpadAdd => {
+                                context.tonemap.increase_exposure();
+                                true
+                            }
+                            VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
+                                context.tonemap.decrease_exposure();
+                                true
+                            }
+                            _ => false,
+                        };
+                        if tonemap_changed {
+                            println!("{}", context.tonemap);
+                        }
+
+                        #[cfg(feature = "ui")]
+                        if VirtualKeyCode::F1 == keycode {
+                            ui.toggle();
+                        }
+
+                        if VirtualKeyCode::F4 == keycode {
+                            context.taa.toggle();
+                            let state = if context.taa.enabled { "on" } else { "off" };
+                            println!("TAA: {}", state);
+                        }
+
+                        let effect_index = match keycode {
+                            VirtualKeyCode::Key1 => Some(0),
+                            VirtualKeyCode::Key2 => Some(1),
+                            VirtualKeyCode::Key3 => Some(2),
+                            VirtualKeyCode::Key4 => Some(3),
+                            VirtualKeyCode::Key5 => Some(4),
+                            VirtualKeyCode::Key6 => Some(5),
+                            VirtualKeyCode::Key7 => Some(6),
+                            VirtualKeyCode::Key8 => Some(7),
+                            VirtualKeyCode::Key9 => Some(8),
+                            _ => None,
+                        };
+                        if let Some(effect) =
+                            effect_index.and_then(|i| context.post_processing.toggle(i))
+                        {
+                            let state = if effect.enabled { "on" } else { "off" };
+                            println!("{}: {}", effect.name, state);
+                        }
+
+                        if VirtualKeyCode::F7 == keycode {
+                            if let Err(err) =
+                                utils::recorder::save_profile(&context.profiler.borrow())
+                            {
+                                eprintln!("Failed to save the GPU profile: {err}");
+                            }
+                        }
+
                         if VirtualKeyCode::F11 == keycode {
                             let now = Instant::now();
                             let frame = context.capture_frame();
@@ -155,6 +256,9 @@ pub fn run<D: Demo>(
                     state: statee,
                 } => {
                     let is_pressed = *statee == ElementState::Pressed;
+                    // Drags that start over the overlay belong to it
+                    #[cfg(feature = "ui")]
+                    let is_pressed = is_pressed && !ui.wants_pointer();
                     mouse_dragged = is_pressed;
                 }
                 DeviceEvent::MouseWheel { delta, .. } => {
@@ -164,6 +268,12 @@ pub fn run<D: Demo>(
                             *scroll as f32
                         }
                     };
+                    #[cfg(feature = "ui")]
+                    let scroll_amount = if ui.wants_pointer() {
+                        0.
+                    } else {
+                        scroll_amount
+                    };
                     context.camera.add_zoom(scroll_amount * zoom_speed);
                 }
                 DeviceEvent::MouseMotion { delta } => {
@@ -178,9 +288,17 @@ pub fn run<D: Demo>(
             Event::RedrawRequested(_) => {
                 frame_counter.record();
 
+                #[cfg(feature = "ui")]
+                ui.run(&window, &context, &frame_counter, |ctx| demo.ui(ctx));
                 demo.render(&context);
 
-                match context.render() {
+                #[cfg(feature = "ui")]
+                let res = context.render_with_overlay(|context, encoder, view| {
+                    ui.record(context, encoder, view)
+                });
+                #[cfg(not(feature = "ui"))]
+                let res = context.render();
+                match res {
                     Ok(_) => {}
                     Err(wgpu::SurfaceError::Lost) => {
                         context.resize(context.width, context.height);
@@ -192,13 +310,22 @@ pub fn run<D: Demo>(
                         window.request_redraw();
                     }
                 }
+                context.profiler.borrow_mut().end_frame(&context.queue);
 
                 if recording_status {
                     let (frame, _) = context.capture_frame();
                     recorder.send(RecordEvent::Record(frame));
                 }
             }
-            Event::UserEvent((path, shader)) => context.register_shader_change(path, shader),
+            Event::UserEvent(WatcherEvent::Reloaded(key, shader, layout)) => {
+                context.register_shader_change(key, shader, &layout)
+            }
+            Event::UserEvent(WatcherEvent::Failed(key, error)) => {
+                context.register_shader_error(key, &error)
+            }
+            Event::UserEvent(WatcherEvent::AssetChanged(path, contents)) => {
+                context.register_asset_change(path, &contents)
+            }
             Event::LoopDestroyed => {
                 println!("\n// End from the loop. Bye bye~⏎ ");
             }
@@ -207,20 +334,24 @@ pub fn run<D: Demo>(
     })
 }
 
-pub fn print_help(info: impl std::fmt::Display, ffmpeg_version: &str) {
+pub fn print_help(info: impl std::fmt::Display, ffmpeg_version: &str, shader_roots: &ShaderRoots) {
     println!("{}", info);
     println!("{}", ffmpeg_version);
-    println!(
-        "Default shader path:\n\t{}\n",
-        Path::new(SHADER_FOLDER).canonicalize().unwrap().display()
-    );
+    println!("Shader paths:\n{}\n", shader_roots);
     // println!("\n- `F1`:   Print help");
-    // println!("- `F2`:   Toggle play/pause");
-    // println!("- `F3`:   Pause and step back one frame");
-    // println!("- `F4`:   Pause and step forward one frame");
+    #[cfg(feature = "ui")]
+    println!("- `F1`:   Toggle the UI overlay");
+    println!("- `F2`:   Cycle tonemapping operators");
+    println!("- `F3`:   Toggle auto exposure");
+    println!("- `F4`:   Toggle temporal anti-aliasing");
+    println!("- `+/-`:  Increase/Decrease exposure");
+    println!("- `1-9`:  Toggle post processing effects:");
+    for (i, (name, _)) in PostProcessChain::DEFAULT_EFFECTS.iter().enumerate() {
+        println!("\t{}: {}", i + 1, name);
+    }
     // println!("- `F5`:   Restart playback at frame 0 (`Time` and `Pos` = 0)");
     // println!("- `F6`:   Print parameters");
-    // println!("- `F7`:   Toggle profiler");
+    println!("- `F7`:   Save GPU profile");
     // println!("- `F8`:   Switch backend");
     // println!("- `F10`:  Save shaders");
     println!("- `F11`:  Take Screenshot");
@@ -0,0 +1,4 @@
+//! Uniforms shared with the shaders, generated from their `// @export`
+//! WGSL declarations by the build script.
+
+include!(concat!(env!("OUT_DIR"), "/shader_types.rs"));
@@ -0,0 +1,275 @@
+//! Generation of Rust types from WGSL structs, meant to be called from build scripts.
+//!
+//! Structs are exported by putting a `// @export` comment on the line
+//! right before their declaration, optionally followed by the name of the
+//! Rust type if it differs:
+//!
+//! ```wgsl
+//! // @export LightUniform
+//! struct Light {
+//!     position: vec3<f32>,
+//!     intensity: f32,
+//! };
+//! ```
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("shader_types.rs");
+//!     vokselis::codegen::generate_shader_types("shaders", out, "vokselis").unwrap();
+//! }
+//!
+//! // lib.rs
+//! include!(concat!(env!("OUT_DIR"), "/shader_types.rs"));
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::{eyre, Result};
+use naga::{
+    proc::Layouter, ArraySize, ConstantInner, Handle, Module, ScalarKind, ScalarValue, Type,
+    TypeInner, VectorSize,
+};
+
+use super::preprocessor::{Preprocessor, ShaderDefines, ShaderDir};
+
+const EXPORT_MARKER: &str = "// @export";
+
+/// Writes `#[repr(C)]` [`bytemuck::Pod`] structs for every exported WGSL
+/// struct found in `shader_dir` to `out_path`.
+///
+/// Gaps required by WGSL alignment rules are filled with explicit padding
+/// fields. Every generated type also implements [`ShaderStruct`](crate::ShaderStruct)
+/// so shader compilation keeps checking it against the WGSL declaration.
+/// `krate` is the path of this crate in the generated code, `crate` for
+/// vokselis' own build script.
+pub fn generate_shader_types(
+    shader_dir: impl AsRef<Path>,
+    out_path: impl AsRef<Path>,
+    krate: &str,
+) -> Result<()> {
+    let shader_dir = shader_dir.as_ref();
+    println!("cargo:rerun-if-changed={}", shader_dir.display());
+
+    let mut files = vec![];
+    collect_shaders(shader_dir, &mut files)?;
+    files.sort();
+
+    let preprocessor = Preprocessor::new(ShaderDir(shader_dir.to_path_buf()));
+    let mut structs = BTreeMap::new();
+    for path in files {
+        let source = std::fs::read_to_string(&path)?;
+        let source = preprocessor.process(&path, &source, &ShaderDefines::default())?;
+        let exports = exported_structs(&source.code);
+        if exports.is_empty() {
+            continue;
+        }
+        let module = naga::front::wgsl::parse_str(&source.code)
+            .map_err(|err| eyre!("{}: {}", path.display(), err.emit_to_string(&source.code)))?;
+        let mut layouter = Layouter::default();
+        layouter
+            .update(&module.types, &module.constants)
+            .map_err(|err| eyre!("{}: {}", path.display(), err))?;
+
+        let mut generator = Generator {
+            module: &module,
+            layouter: &layouter,
+            krate,
+            structs: &mut structs,
+        };
+        for (name, rust_name) in exports {
+            let ty = module
+                .types
+                .iter()
+                .find(|(_, ty)| ty.name.as_deref() == Some(name))
+                .map(|(handle, _)| handle)
+                .ok_or_else(|| eyre!("{}: struct `{}` not found", path.display(), name))?;
+            generator
+                .generate(ty, rust_name)
+                .map_err(|err| eyre!("{}: {}", path.display(), err))?;
+        }
+    }
+
+    let mut out = String::from("// Generated from WGSL by `vokselis::codegen`, do not edit.\n");
+    for code in structs.values() {
+        out.push('\n');
+        out.push_str(code);
+    }
+    std::fs::write(out_path, out)?;
+    Ok(())
+}
+
+fn collect_shaders(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_shaders(&path, files)?;
+        } else if path.extension() == Some(OsStr::new("wgsl")) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Names of the structs declared right after an export marker, with
+/// the Rust names given by the markers.
+fn exported_structs(code: &str) -> Vec<(&str, Option<&str>)> {
+    let mut lines = code.lines().map(str::trim);
+    let mut res = vec![];
+    while let Some(line) = lines.next() {
+        let rust_name = match line.strip_prefix(EXPORT_MARKER) {
+            Some(rest) if rest.is_empty() || rest.starts_with(' ') => rest.trim(),
+            _ => continue,
+        };
+        let name = lines
+            .next()
+            .and_then(|x| x.strip_prefix("struct "))
+            .and_then(|x| x.split(|c: char| c == '{' || c.is_whitespace()).next())
+            .filter(|x| !x.is_empty());
+        if let Some(name) = name {
+            res.push((name, Some(rust_name).filter(|x| !x.is_empty())));
+        }
+    }
+    res
+}
+
+struct Generator<'a> {
+    module: &'a Module,
+    layouter: &'a Layouter,
+    /// Path of this crate in the generated code
+    krate: &'a str,
+    /// Generated code by struct name
+    structs: &'a mut BTreeMap<String, String>,
+}
+
+impl Generator<'_> {
+    /// Generates the struct unless it already was, returns the name of the Rust type.
+    fn generate(&mut self, handle: Handle<Type>, rust_name: Option<&str>) -> Result<String> {
+        let ty = &self.module.types[handle];
+        let (members, span) = match ty.inner {
+            TypeInner::Struct { ref members, span } => (members, span),
+            _ => return Err(eyre!("`{:?}` is not a struct", ty.name)),
+        };
+        let name = ty.name.clone().ok_or_else(|| eyre!("unnamed struct"))?;
+        let rust_name = rust_name.unwrap_or(&name).to_string();
+
+        let mut fields = String::new();
+        let mut field_names = vec![];
+        let mut offset = 0;
+        let mut padding_count = 0;
+        for member in members {
+            let member_name = member
+                .name
+                .clone()
+                .ok_or_else(|| eyre!("unnamed member in `{}`", name))?;
+            if member.offset > offset {
+                let _ = writeln!(
+                    fields,
+                    "    pub _padding{}: [u32; {}],",
+                    padding_count,
+                    (member.offset - offset) / 4
+                );
+                padding_count += 1;
+            }
+            let rust_ty = self.rust_type(member.ty)?;
+            let _ = writeln!(fields, "    pub {}: {},", member_name, rust_ty);
+            field_names.push(member_name);
+            offset = member.offset + self.layouter[member.ty].size;
+        }
+        if span > offset {
+            let _ = writeln!(
+                fields,
+                "    pub _padding{}: [u32; {}],",
+                padding_count,
+                (span - offset) / 4
+            );
+        }
+
+        let code = format!(
+            "/// Mirrors the WGSL struct `{name}`\n\
+             #[repr(C)]\n\
+             #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]\n\
+             pub struct {rust_name} {{\n{fields}}}\n\n\
+             {krate}::shader_struct!({rust_name} as \"{name}\" {{ {field_names} }});\n",
+            name = name,
+            rust_name = rust_name,
+            fields = fields,
+            krate = self.krate,
+            field_names = field_names.join(", "),
+        );
+        if let Some(existing) = self.structs.get(&name) {
+            if existing != &code {
+                return Err(eyre!("conflicting declarations of struct `{}`", name));
+            }
+        }
+        self.structs.insert(name, code);
+        Ok(rust_name)
+    }
+
+    fn rust_type(&mut self, handle: Handle<Type>) -> Result<String> {
+        let res = match self.module.types[handle].inner {
+            TypeInner::Scalar { kind, width: 4 } | TypeInner::Atomic { kind, width: 4 } => {
+                scalar(kind)?.to_string()
+            }
+            TypeInner::Vector {
+                size,
+                kind,
+                width: 4,
+            } => format!("[{}; {}]", scalar(kind)?, vector_size(size)),
+            TypeInner::Matrix {
+                columns,
+                rows,
+                width: 4,
+            } => {
+                // Columns of 3 rows are aligned to 16 bytes
+                let rows = match rows {
+                    VectorSize::Bi => 2,
+                    _ => 4,
+                };
+                format!("[[f32; {}]; {}]", rows, vector_size(columns))
+            }
+            TypeInner::Array {
+                base,
+                size: ArraySize::Constant(size),
+                stride,
+            } => {
+                if self.layouter[base].size != stride {
+                    return Err(eyre!("arrays with padded elements are not supported"));
+                }
+                let len = match self.module.constants[size].inner {
+                    ConstantInner::Scalar {
+                        value: ScalarValue::Uint(x),
+                        ..
+                    } => x,
+                    ConstantInner::Scalar {
+                        value: ScalarValue::Sint(x),
+                        ..
+                    } => x as u64,
+                    _ => return Err(eyre!("unsupported array size")),
+                };
+                format!("[{}; {}]", self.rust_type(base)?, len)
+            }
+            TypeInner::Struct { .. } => self.generate(handle, None)?,
+            ref inner => return Err(eyre!("`{:?}` is not host shareable", inner)),
+        };
+        Ok(res)
+    }
+}
+
+fn scalar(kind: ScalarKind) -> Result<&'static str> {
+    match kind {
+        ScalarKind::Float => Ok("f32"),
+        ScalarKind::Uint => Ok("u32"),
+        ScalarKind::Sint => Ok("i32"),
+        ScalarKind::Bool => Err(eyre!("`bool` is not host shareable")),
+    }
+}
+
+fn vector_size(size: VectorSize) -> u8 {
+    size as u8
+}
@@ -0,0 +1,8 @@
+//! Shader tooling the build script compiles as well, it only depends on
+//! std, naga, color_eyre and the wgpu types, which the build script gets
+//! from `wgpu-types` under the name `wgpu`.
+
+pub mod codegen;
+pub mod preprocessor;
+pub mod reflection;
+pub mod spirv_options;
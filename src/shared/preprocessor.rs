@@ -0,0 +1,545 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeSet,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Expands `#include "file.wgsl"` (or `#import`) directives and
+/// `#define`/`#undef`/`#ifdef`/`#ifndef`/`#else`/`#endif` conditionals.
+///
+/// Included paths are looked up in the [`ShaderFiles`] the preprocessor
+/// was created with. Every file is pasted at most once per compilation,
+/// so shared headers can be included from several places without
+/// redefinition errors.
+pub struct Preprocessor {
+    files: Box<dyn ShaderFiles>,
+}
+
+/// Where included files are found.
+pub trait ShaderFiles: Send + Sync {
+    /// Path of the file `include` refers to, if there is one.
+    fn resolve(&self, include: &Path) -> Option<PathBuf>;
+
+    /// Reads a file returned by [`resolve`](Self::resolve).
+    fn read(&self, path: &Path) -> io::Result<Cow<'static, str>>;
+}
+
+/// Files of a single folder.
+#[derive(Debug, Clone)]
+pub struct ShaderDir(pub PathBuf);
+
+impl ShaderFiles for ShaderDir {
+    fn resolve(&self, include: &Path) -> Option<PathBuf> {
+        Some(self.0.join(include)).filter(|x| x.is_file())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Cow<'static, str>> {
+        std::fs::read_to_string(path).map(Cow::Owned)
+    }
+}
+
+/// Set of flags selecting a variant of a shader.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShaderDefines(BTreeSet<String>);
+
+impl ShaderDefines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, name: impl Into<String>) -> Self {
+        self.insert(name);
+        self
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>) -> bool {
+        self.0.insert(name.into())
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.0.remove(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|x| x.as_str())
+    }
+}
+
+impl<S: Into<String>> FromIterator<S> for ShaderDefines {
+    fn from_iter<T: IntoIterator<Item = S>>(iter: T) -> Self {
+        Self(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+impl std::fmt::Display for ShaderDefines {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<_> = self.iter().collect();
+        write!(f, "[{}]", names.join(", "))
+    }
+}
+
+/// Expanded shader code with a mapping back to the original files.
+#[derive(Debug, Clone, Default)]
+pub struct ShaderSource {
+    pub code: String,
+    pub source_map: SourceMap,
+}
+
+/// Maps lines of the expanded code to lines of the original files.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<PathBuf>,
+    /// `(file index, 1-based line)` per line of the expanded code
+    lines: Vec<(usize, u32)>,
+}
+
+impl SourceMap {
+    /// Returns the original file and the 1-based line for the
+    /// 1-based `line` of the expanded code.
+    pub fn locate(&self, line: u32) -> Option<(&Path, u32)> {
+        let &(file, line) = self.lines.get(line.checked_sub(1)? as usize)?;
+        Some((&self.files[file], line))
+    }
+
+    /// All files that took part in the expansion, the root file first.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+}
+
+enum Directive<'a> {
+    Include(&'a str),
+    Define(&'a str),
+    Undef(&'a str),
+    IfDef(&'a str),
+    IfNDef(&'a str),
+    Else,
+    EndIf,
+}
+
+struct Conditional {
+    active: bool,
+    has_else: bool,
+}
+
+impl Preprocessor {
+    pub fn new(files: impl ShaderFiles + 'static) -> Self {
+        Self {
+            files: Box::new(files),
+        }
+    }
+
+    pub fn process(
+        &self,
+        path: &Path,
+        source: &str,
+        defines: &ShaderDefines,
+    ) -> Result<ShaderSource, PreprocessError> {
+        let mut res = ShaderSource::default();
+        let mut stack = vec![canonical(path)];
+        let mut defines = defines.clone();
+        self.expand(path, source, &mut stack, &mut defines, &mut res)?;
+        Ok(res)
+    }
+
+    fn expand(
+        &self,
+        path: &Path,
+        source: &str,
+        stack: &mut Vec<PathBuf>,
+        defines: &mut ShaderDefines,
+        res: &mut ShaderSource,
+    ) -> Result<(), PreprocessError> {
+        let file_index = res.source_map.files.len();
+        res.source_map.files.push(path.to_path_buf());
+
+        let malformed = |line| PreprocessError::Malformed {
+            path: path.to_path_buf(),
+            line,
+        };
+        // Conditionals have to be closed in the file they were opened in
+        let mut conditionals: Vec<Conditional> = vec![];
+        let mut last_line = 0;
+
+        for (line_number, line) in source.lines().enumerate() {
+            let line_number = line_number as u32 + 1;
+            last_line = line_number;
+            let active = conditionals.iter().all(|x| x.active);
+            let directive = match parse_directive(line) {
+                Some(directive) => directive.ok_or_else(|| malformed(line_number))?,
+                None => {
+                    if active {
+                        res.code.push_str(line);
+                        res.code.push('\n');
+                        res.source_map.lines.push((file_index, line_number));
+                    }
+                    continue;
+                }
+            };
+
+            let include = match directive {
+                Directive::IfDef(name) | Directive::IfNDef(name) => {
+                    let expected = matches!(directive, Directive::IfDef(_));
+                    conditionals.push(Conditional {
+                        active: defines.contains(name) == expected,
+                        has_else: false,
+                    });
+                    continue;
+                }
+                Directive::Else => {
+                    match conditionals.last_mut() {
+                        Some(cond) if !cond.has_else => {
+                            cond.active = !cond.active;
+                            cond.has_else = true;
+                        }
+                        _ => return Err(malformed(line_number)),
+                    }
+                    continue;
+                }
+                Directive::EndIf => {
+                    conditionals.pop().ok_or_else(|| malformed(line_number))?;
+                    continue;
+                }
+                _ if !active => continue,
+                Directive::Define(name) => {
+                    defines.insert(name);
+                    continue;
+                }
+                Directive::Undef(name) => {
+                    defines.remove(name);
+                    continue;
+                }
+                Directive::Include(include) => include,
+            };
+            let include_path = self
+                .files
+                .resolve(Path::new(include))
+                .unwrap_or_else(|| include.into());
+            let canonical_path = canonical(&include_path);
+
+            if let Some(start) = stack.iter().position(|x| x == &canonical_path) {
+                let mut cycle = stack[start..].to_vec();
+                cycle.push(canonical_path);
+                return Err(PreprocessError::Cycle(cycle));
+            }
+            if res
+                .source_map
+                .files
+                .iter()
+                .any(|x| canonical(x) == canonical_path)
+            {
+                continue;
+            }
+
+            let include_source =
+                self.files
+                    .read(&include_path)
+                    .map_err(|error| PreprocessError::Read {
+                        path: include_path.clone(),
+                        included_from: (path.to_path_buf(), line_number),
+                        error,
+                    })?;
+            stack.push(canonical_path);
+            self.expand(&include_path, &include_source, stack, defines, res)?;
+            stack.pop();
+        }
+
+        if !conditionals.is_empty() {
+            return Err(PreprocessError::UnterminatedConditional {
+                path: path.to_path_buf(),
+                line: last_line,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns `None` for regular lines, `Some(None)` for malformed directives.
+fn parse_directive(line: &str) -> Option<Option<Directive<'_>>> {
+    let line = line.trim_start().strip_prefix('#')?;
+    let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+    let name = || Some(rest).filter(|x| !x.is_empty() && !x.contains(char::is_whitespace));
+    let directive = match keyword {
+        "include" | "import" => rest
+            .strip_prefix('"')
+            .and_then(|x| x.strip_suffix('"'))
+            .filter(|x| !x.is_empty())
+            .map(Directive::Include),
+        "define" => name().map(Directive::Define),
+        "undef" => name().map(Directive::Undef),
+        "ifdef" => name().map(Directive::IfDef),
+        "ifndef" => name().map(Directive::IfNDef),
+        "else" => rest.is_empty().then_some(Directive::Else),
+        "endif" => rest.is_empty().then_some(Directive::EndIf),
+        _ => None,
+    };
+    Some(directive)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[derive(Debug)]
+pub enum PreprocessError {
+    Read {
+        path: PathBuf,
+        included_from: (PathBuf, u32),
+        error: io::Error,
+    },
+    Malformed {
+        path: PathBuf,
+        line: u32,
+    },
+    UnterminatedConditional {
+        path: PathBuf,
+        line: u32,
+    },
+    /// Chain of files that include each other, the first file repeated at the end
+    Cycle(Vec<PathBuf>),
+}
+
+impl std::fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read {
+                path,
+                included_from: (file, line),
+                error,
+            } => write!(
+                f,
+                "{}:{}: failed to include {}: {}",
+                file.display(),
+                line,
+                path.display(),
+                error
+            ),
+            Self::Malformed { path, line } => {
+                write!(f, "{}:{}: malformed directive", path.display(), line)
+            }
+            Self::UnterminatedConditional { path, line } => {
+                write!(f, "{}:{}: expected `#endif`", path.display(), line)
+            }
+            Self::Cycle(chain) => {
+                write!(f, "include cycle: ")?;
+                let chain: Vec<_> = chain.iter().map(|x| x.display().to_string()).collect();
+                write!(f, "{}", chain.join(" -> "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for PreprocessError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Read { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `files` to a fresh folder named after the test.
+    fn shader_dir(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "vokselis-preprocessor-{}-{test}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        for (name, source) in files {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        }
+        dir
+    }
+
+    fn process(
+        dir: &Path,
+        name: &str,
+        defines: &ShaderDefines,
+    ) -> Result<ShaderSource, PreprocessError> {
+        let preprocessor = Preprocessor::new(ShaderDir(dir.to_path_buf()));
+        let path = dir.join(name);
+        let source = std::fs::read_to_string(&path).unwrap();
+        preprocessor.process(&path, &source, defines)
+    }
+
+    #[test]
+    fn includes_are_resolved_against_the_roots_once() {
+        let dir = shader_dir(
+            "includes",
+            &[
+                (
+                    "main.wgsl",
+                    "#include \"common/a.wgsl\"\n#import \"common/b.wgsl\"\nmain\n",
+                ),
+                ("common/a.wgsl", "#include \"common/b.wgsl\"\na\n"),
+                ("common/b.wgsl", "b\n"),
+            ],
+        );
+        let res = process(&dir, "main.wgsl", &ShaderDefines::new()).unwrap();
+
+        assert_eq!(res.code, "b\na\nmain\n");
+        let files: Vec<_> = res
+            .source_map
+            .files()
+            .iter()
+            .map(|x| canonical(x))
+            .collect();
+        let expected: Vec<_> = ["main.wgsl", "common/a.wgsl", "common/b.wgsl"]
+            .iter()
+            .map(|x| canonical(&dir.join(x)))
+            .collect();
+        assert_eq!(files, expected);
+    }
+
+    #[test]
+    fn missing_includes_report_the_including_line() {
+        let dir = shader_dir("missing", &[("main.wgsl", "a\n#include \"nope.wgsl\"\n")]);
+        let err = process(&dir, "main.wgsl", &ShaderDefines::new()).unwrap_err();
+
+        match err {
+            PreprocessError::Read {
+                path,
+                included_from: (file, line),
+                ..
+            } => {
+                assert_eq!(path, Path::new("nope.wgsl"));
+                assert_eq!(file, dir.join("main.wgsl"));
+                assert_eq!(line, 2);
+            }
+            err => panic!("unexpected error: {err}"),
+        }
+    }
+
+    #[test]
+    fn include_cycles_are_reported_with_the_chain() {
+        let dir = shader_dir(
+            "cycle",
+            &[
+                ("main.wgsl", "#include \"a.wgsl\"\n"),
+                ("a.wgsl", "#include \"b.wgsl\"\n"),
+                ("b.wgsl", "#include \"a.wgsl\"\n"),
+            ],
+        );
+        let err = process(&dir, "main.wgsl", &ShaderDefines::new()).unwrap_err();
+
+        match err {
+            PreprocessError::Cycle(chain) => {
+                let expected: Vec<_> = ["a.wgsl", "b.wgsl", "a.wgsl"]
+                    .iter()
+                    .map(|x| canonical(&dir.join(x)))
+                    .collect();
+                assert_eq!(chain, expected);
+            }
+            err => panic!("unexpected error: {err}"),
+        }
+    }
+
+    #[test]
+    fn conditionals_follow_defines() {
+        let source = "\
+#ifdef A
+a
+#else
+not a
+#endif
+#define B
+#ifndef B
+not b
+#endif
+#undef B
+#ifdef B
+b
+#endif
+";
+        let dir = shader_dir("defines", &[("main.wgsl", source)]);
+
+        let res = process(&dir, "main.wgsl", &ShaderDefines::new()).unwrap();
+        assert_eq!(res.code, "not a\n");
+        let res = process(&dir, "main.wgsl", &ShaderDefines::new().with("A")).unwrap();
+        assert_eq!(res.code, "a\n");
+    }
+
+    #[test]
+    fn defines_reach_included_files() {
+        let dir = shader_dir(
+            "included_defines",
+            &[
+                ("main.wgsl", "#define A\n#include \"a.wgsl\"\n"),
+                ("a.wgsl", "#ifdef A\na\n#endif\n#ifdef B\nb\n#endif\n"),
+            ],
+        );
+        let res = process(&dir, "main.wgsl", &ShaderDefines::new().with("B")).unwrap();
+
+        assert_eq!(res.code, "a\nb\n");
+    }
+
+    #[test]
+    fn malformed_directives() {
+        let cases = [
+            ("#include nope.wgsl\n", 1),
+            ("#define\n", 1),
+            ("a\n#else\n", 2),
+            ("#ifdef A\n#else\n#else\n#endif\n", 3),
+            ("#endif\n", 1),
+            ("#pragma once\n", 1),
+        ];
+        for (source, expected) in cases {
+            let dir = shader_dir("malformed", &[("main.wgsl", source)]);
+            match process(&dir, "main.wgsl", &ShaderDefines::new()) {
+                Err(PreprocessError::Malformed { line, .. }) => assert_eq!(line, expected),
+                res => panic!("{source:?}: unexpected result {res:?}"),
+            }
+        }
+
+        let dir = shader_dir("unterminated", &[("main.wgsl", "#ifdef A\na\n")]);
+        let err = process(&dir, "main.wgsl", &ShaderDefines::new()).unwrap_err();
+        assert!(matches!(
+            err,
+            PreprocessError::UnterminatedConditional { line: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn source_map_points_to_original_lines() {
+        let dir = shader_dir(
+            "source_map",
+            &[
+                (
+                    "main.wgsl",
+                    "m1\n#include \"a.wgsl\"\n#ifdef A\nskipped\n#endif\nm6\n",
+                ),
+                ("a.wgsl", "// a\na2\n"),
+            ],
+        );
+        let res = process(&dir, "main.wgsl", &ShaderDefines::new()).unwrap();
+        assert_eq!(res.code, "m1\n// a\na2\nm6\n");
+
+        let locate = |line| {
+            let (path, line) = res.source_map.locate(line)?;
+            Some((path.file_name()?.to_str()?.to_owned(), line))
+        };
+        assert_eq!(locate(1), Some(("main.wgsl".into(), 1)));
+        assert_eq!(locate(2), Some(("a.wgsl".into(), 1)));
+        assert_eq!(locate(3), Some(("a.wgsl".into(), 2)));
+        assert_eq!(locate(4), Some(("main.wgsl".into(), 6)));
+        assert_eq!(locate(0), None);
+        assert_eq!(locate(5), None);
+    }
+}
@@ -0,0 +1,724 @@
+use std::{
+    collections::HashSet,
+    num::{NonZeroU32, NonZeroU64},
+};
+
+use naga::{
+    proc::Layouter, valid::ModuleInfo, AddressSpace, ArraySize, ConstantInner, Expression,
+    Function, GlobalVariable, Handle, ImageClass, ImageDimension, Module, ScalarKind, ScalarValue,
+    ShaderStage, StorageAccess, StorageFormat, TypeInner,
+};
+
+/// Bind group layouts of a shader module reconstructed from its global variables.
+///
+/// Visibility of a binding is the set of entry point stages that use it.
+/// Bindings which are declared but never used are visible to every stage
+/// of the module. Float textures are filterable if the shader samples them
+/// with a filtering sampler and unfilterable otherwise, which also accepts
+/// filterable formats. Properties that can't be expressed in WGSL, like
+/// dynamic offsets or textures of unfilterable formats read through a
+/// nearest sampler, have to come from
+/// [`with_bind_groups`](Self::with_bind_groups).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShaderLayout {
+    /// Entries of each group sorted by binding, indexed by group
+    pub groups: Vec<Vec<wgpu::BindGroupLayoutEntry>>,
+}
+
+impl ShaderLayout {
+    pub fn from_module(module: &Module, info: &ModuleInfo) -> Self {
+        let mut layouter = Layouter::default();
+        // Validated modules are always laid out successfully
+        let _ = layouter.update(&module.types, &module.constants);
+
+        let module_stages = module
+            .entry_points
+            .iter()
+            .fold(wgpu::ShaderStages::NONE, |acc, ep| acc | stage(ep.stage));
+
+        let filtered = filtered_textures(module);
+        let mut groups: Vec<Vec<wgpu::BindGroupLayoutEntry>> = vec![];
+        for (handle, var) in module.global_variables.iter() {
+            let binding = match var.binding {
+                Some(ref binding) => binding,
+                None => continue,
+            };
+
+            let mut visibility = wgpu::ShaderStages::NONE;
+            for (i, ep) in module.entry_points.iter().enumerate() {
+                if !info.get_entry_point(i)[handle].is_empty() {
+                    visibility |= stage(ep.stage);
+                }
+            }
+            if visibility.is_empty() {
+                visibility = module_stages;
+            }
+
+            let (ty, count) = match module.types[var.ty].inner {
+                TypeInner::BindingArray { base, size } => (base, array_count(module, size)),
+                _ => (var.ty, None),
+            };
+            let filterable = filtered.contains(&handle);
+            let ty = match binding_type(module, &layouter, var.space, ty, filterable) {
+                Some(ty) => ty,
+                None => continue,
+            };
+
+            let group = binding.group as usize;
+            if groups.len() <= group {
+                groups.resize_with(group + 1, Vec::new);
+            }
+            groups[group].push(wgpu::BindGroupLayoutEntry {
+                binding: binding.binding,
+                visibility,
+                ty,
+                count,
+            });
+        }
+        for entries in &mut groups {
+            entries.sort_by_key(|entry| entry.binding);
+        }
+
+        Self { groups }
+    }
+}
+
+/// What the compiler needs of a module besides its SPIR-V, so modules from
+/// a cache don't have to be parsed again.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reflection {
+    pub layout: ShaderLayout,
+    /// Struct layouts of the module, registered Rust layouts are checked against them
+    pub structs: Vec<WgslStruct>,
+}
+
+/// Memory layout of a struct declared in WGSL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WgslStruct {
+    pub name: String,
+    pub span: u32,
+    pub members: Vec<WgslMember>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WgslMember {
+    /// `_` for unnamed members
+    pub name: String,
+    pub offset: u32,
+    pub size: u32,
+}
+
+impl Reflection {
+    pub fn from_module(module: &Module, info: &ModuleInfo) -> Self {
+        Self {
+            layout: ShaderLayout::from_module(module, info),
+            structs: WgslStruct::declared_in(module),
+        }
+    }
+
+    /// Appends the reflection to `out` in a form [`decode`](Self::decode) reads back.
+    pub fn encode(&self, out: &mut Vec<u32>) {
+        out.push(self.layout.groups.len() as u32);
+        for entries in &self.layout.groups {
+            out.push(entries.len() as u32);
+            for entry in entries {
+                encode_entry(entry, out);
+            }
+        }
+        out.push(self.structs.len() as u32);
+        for wgsl_struct in &self.structs {
+            encode_str(&wgsl_struct.name, out);
+            out.push(wgsl_struct.span);
+            out.push(wgsl_struct.members.len() as u32);
+            for member in &wgsl_struct.members {
+                encode_str(&member.name, out);
+                out.extend([member.offset, member.size]);
+            }
+        }
+    }
+
+    /// Reads a reflection written by [`encode`](Self::encode), `None` if
+    /// `words` hold anything else.
+    pub fn decode(words: &[u32]) -> Option<Self> {
+        let mut words = words.iter().copied();
+        let words = &mut words;
+        let mut groups = vec![];
+        for _ in 0..words.next()? {
+            let entries = (0..words.next()?)
+                .map(|_| decode_entry(words))
+                .collect::<Option<_>>()?;
+            groups.push(entries);
+        }
+        let mut structs = vec![];
+        for _ in 0..words.next()? {
+            let name = decode_str(words)?;
+            let span = words.next()?;
+            let members = (0..words.next()?)
+                .map(|_| {
+                    Some(WgslMember {
+                        name: decode_str(words)?,
+                        offset: words.next()?,
+                        size: words.next()?,
+                    })
+                })
+                .collect::<Option<_>>()?;
+            structs.push(WgslStruct {
+                name,
+                span,
+                members,
+            });
+        }
+        if words.next().is_some() {
+            return None;
+        }
+        Some(Self {
+            layout: ShaderLayout { groups },
+            structs,
+        })
+    }
+}
+
+impl WgslStruct {
+    /// Every named struct of `module`.
+    pub fn declared_in(module: &Module) -> Vec<Self> {
+        let mut layouter = Layouter::default();
+        // Validated modules are always laid out successfully
+        if layouter.update(&module.types, &module.constants).is_err() {
+            return vec![];
+        }
+        module
+            .types
+            .iter()
+            .filter_map(|(_, ty)| match ty.inner {
+                TypeInner::Struct { ref members, span } => Some(Self {
+                    name: ty.name.clone()?,
+                    span,
+                    members: members
+                        .iter()
+                        .map(|member| WgslMember {
+                            name: member.name.clone().unwrap_or_else(|| String::from("_")),
+                            offset: member.offset,
+                            size: layouter[member.ty].size,
+                        })
+                        .collect(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+const VIEW_DIMENSIONS: [wgpu::TextureViewDimension; 6] = [
+    wgpu::TextureViewDimension::D1,
+    wgpu::TextureViewDimension::D2,
+    wgpu::TextureViewDimension::D2Array,
+    wgpu::TextureViewDimension::Cube,
+    wgpu::TextureViewDimension::CubeArray,
+    wgpu::TextureViewDimension::D3,
+];
+
+fn encode_entry(entry: &wgpu::BindGroupLayoutEntry, out: &mut Vec<u32>) {
+    let view_dimension = |dim| VIEW_DIMENSIONS.iter().position(|x| *x == dim).unwrap() as u32;
+    out.push(entry.binding);
+    out.push(entry.visibility.bits());
+    out.push(entry.count.map_or(0, NonZeroU32::get));
+    match entry.ty {
+        wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset,
+            min_binding_size,
+        } => {
+            let size = min_binding_size.map_or(0, NonZeroU64::get);
+            let ty = match ty {
+                wgpu::BufferBindingType::Uniform => 0,
+                wgpu::BufferBindingType::Storage { read_only: false } => 1,
+                wgpu::BufferBindingType::Storage { read_only: true } => 2,
+            };
+            out.extend([0, ty, has_dynamic_offset as u32]);
+            out.extend([size as u32, (size >> 32) as u32]);
+        }
+        wgpu::BindingType::Sampler(ty) => {
+            let ty = match ty {
+                wgpu::SamplerBindingType::Filtering => 0,
+                wgpu::SamplerBindingType::NonFiltering => 1,
+                wgpu::SamplerBindingType::Comparison => 2,
+            };
+            out.extend([1, ty]);
+        }
+        wgpu::BindingType::Texture {
+            sample_type,
+            view_dimension: dim,
+            multisampled,
+        } => {
+            let sample_type = match sample_type {
+                wgpu::TextureSampleType::Float { filterable: false } => 0,
+                wgpu::TextureSampleType::Float { filterable: true } => 1,
+                wgpu::TextureSampleType::Depth => 2,
+                wgpu::TextureSampleType::Sint => 3,
+                wgpu::TextureSampleType::Uint => 4,
+            };
+            out.extend([2, sample_type, view_dimension(dim), multisampled as u32]);
+        }
+        wgpu::BindingType::StorageTexture {
+            access,
+            format,
+            view_dimension: dim,
+        } => {
+            let access = match access {
+                wgpu::StorageTextureAccess::ReadOnly => 0,
+                wgpu::StorageTextureAccess::WriteOnly => 1,
+                wgpu::StorageTextureAccess::ReadWrite => 2,
+            };
+            // Formats WGSL can't declare don't decode
+            let format = STORAGE_FORMATS
+                .iter()
+                .position(|(_, x)| *x == format)
+                .map_or(u32::MAX, |x| x as u32);
+            out.extend([3, access, format, view_dimension(dim)]);
+        }
+    }
+}
+
+fn decode_entry(words: &mut impl Iterator<Item = u32>) -> Option<wgpu::BindGroupLayoutEntry> {
+    let mut next = || words.next();
+    let view_dimension = |x: u32| VIEW_DIMENSIONS.get(x as usize).copied();
+    let binding = next()?;
+    let visibility = wgpu::ShaderStages::from_bits(next()?)?;
+    let count = NonZeroU32::new(next()?);
+    let ty = match next()? {
+        0 => {
+            let ty = match next()? {
+                0 => wgpu::BufferBindingType::Uniform,
+                1 => wgpu::BufferBindingType::Storage { read_only: false },
+                2 => wgpu::BufferBindingType::Storage { read_only: true },
+                _ => return None,
+            };
+            let has_dynamic_offset = next()? != 0;
+            let size = next()? as u64 | (next()? as u64) << 32;
+            wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset,
+                min_binding_size: NonZeroU64::new(size),
+            }
+        }
+        1 => wgpu::BindingType::Sampler(match next()? {
+            0 => wgpu::SamplerBindingType::Filtering,
+            1 => wgpu::SamplerBindingType::NonFiltering,
+            2 => wgpu::SamplerBindingType::Comparison,
+            _ => return None,
+        }),
+        2 => wgpu::BindingType::Texture {
+            sample_type: match next()? {
+                0 => wgpu::TextureSampleType::Float { filterable: false },
+                1 => wgpu::TextureSampleType::Float { filterable: true },
+                2 => wgpu::TextureSampleType::Depth,
+                3 => wgpu::TextureSampleType::Sint,
+                4 => wgpu::TextureSampleType::Uint,
+                _ => return None,
+            },
+            view_dimension: view_dimension(next()?)?,
+            multisampled: next()? != 0,
+        },
+        3 => wgpu::BindingType::StorageTexture {
+            access: match next()? {
+                0 => wgpu::StorageTextureAccess::ReadOnly,
+                1 => wgpu::StorageTextureAccess::WriteOnly,
+                2 => wgpu::StorageTextureAccess::ReadWrite,
+                _ => return None,
+            },
+            format: STORAGE_FORMATS.get(next()? as usize)?.1,
+            view_dimension: view_dimension(next()?)?,
+        },
+        _ => return None,
+    };
+    Some(wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty,
+        count,
+    })
+}
+
+/// Length in bytes followed by the bytes, four per word.
+fn encode_str(s: &str, out: &mut Vec<u32>) {
+    out.push(s.len() as u32);
+    out.extend(s.as_bytes().chunks(4).map(|chunk| {
+        let mut bytes = [0; 4];
+        bytes[..chunk.len()].copy_from_slice(chunk);
+        u32::from_le_bytes(bytes)
+    }));
+}
+
+fn decode_str(words: &mut impl Iterator<Item = u32>) -> Option<String> {
+    let len = words.next()? as usize;
+    let mut bytes = Vec::with_capacity(len + 3);
+    while bytes.len() < len {
+        bytes.extend(words.next()?.to_le_bytes());
+    }
+    bytes.truncate(len);
+    String::from_utf8(bytes).ok()
+}
+
+fn stage(stage: ShaderStage) -> wgpu::ShaderStages {
+    match stage {
+        ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+        ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+        ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+    }
+}
+
+fn array_count(module: &Module, size: ArraySize) -> Option<NonZeroU32> {
+    match size {
+        ArraySize::Constant(handle) => match module.constants[handle].inner {
+            ConstantInner::Scalar {
+                value: ScalarValue::Uint(x),
+                ..
+            } => NonZeroU32::new(x as u32),
+            ConstantInner::Scalar {
+                value: ScalarValue::Sint(x),
+                ..
+            } => NonZeroU32::new(x as u32),
+            _ => None,
+        },
+        ArraySize::Dynamic => None,
+    }
+}
+
+fn binding_type(
+    module: &Module,
+    layouter: &Layouter,
+    space: AddressSpace,
+    ty: Handle<naga::Type>,
+    filterable: bool,
+) -> Option<wgpu::BindingType> {
+    let min_binding_size = NonZeroU64::new(layouter[ty].size as u64);
+    let binding_type = match space {
+        AddressSpace::Uniform => wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size,
+        },
+        AddressSpace::Storage { access } => wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage {
+                read_only: !access.contains(StorageAccess::STORE),
+            },
+            has_dynamic_offset: false,
+            min_binding_size,
+        },
+        AddressSpace::Handle => match module.types[ty].inner {
+            TypeInner::Sampler { comparison: true } => {
+                wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison)
+            }
+            TypeInner::Sampler { comparison: false } => {
+                wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)
+            }
+            TypeInner::Image {
+                dim,
+                arrayed,
+                class,
+            } => {
+                let view_dimension = view_dimension(dim, arrayed);
+                match class {
+                    ImageClass::Sampled { kind, multi } => wgpu::BindingType::Texture {
+                        sample_type: match kind {
+                            ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                            ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                            _ => wgpu::TextureSampleType::Float { filterable },
+                        },
+                        view_dimension,
+                        multisampled: multi,
+                    },
+                    ImageClass::Depth { multi } => wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension,
+                        multisampled: multi,
+                    },
+                    ImageClass::Storage { format, access } => wgpu::BindingType::StorageTexture {
+                        access: if access == StorageAccess::LOAD {
+                            wgpu::StorageTextureAccess::ReadOnly
+                        } else if access == StorageAccess::STORE {
+                            wgpu::StorageTextureAccess::WriteOnly
+                        } else {
+                            wgpu::StorageTextureAccess::ReadWrite
+                        },
+                        format: storage_format(format),
+                        view_dimension,
+                    },
+                }
+            }
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(binding_type)
+}
+
+/// Textures sampled with a non-comparison sampler anywhere in `module`.
+///
+/// Textures sampled in a function they are passed to can't be told apart,
+/// then every texture the module uses counts as filtered.
+fn filtered_textures(module: &Module) -> HashSet<Handle<GlobalVariable>> {
+    let functions: Vec<&Function> = module
+        .functions
+        .iter()
+        .map(|(_, function)| function)
+        .chain(module.entry_points.iter().map(|ep| &ep.function))
+        .collect();
+    let is_comparison = |sampler: Option<Handle<GlobalVariable>>| {
+        let ty = sampler.map(|x| &module.types[module.global_variables[x].ty].inner);
+        matches!(ty, Some(TypeInner::Sampler { comparison: true }))
+    };
+
+    let mut filtered = HashSet::new();
+    let mut through_arguments = false;
+    for function in &functions {
+        for (_, expression) in function.expressions.iter() {
+            if let Expression::ImageSample { image, sampler, .. } = *expression {
+                if is_comparison(global_variable(function, sampler)) {
+                    continue;
+                }
+                match global_variable(function, image) {
+                    Some(image) => {
+                        filtered.insert(image);
+                    }
+                    None => through_arguments = true,
+                }
+            }
+        }
+    }
+    if through_arguments {
+        for function in &functions {
+            for (_, expression) in function.expressions.iter() {
+                if let Expression::GlobalVariable(var) = *expression {
+                    filtered.insert(var);
+                }
+            }
+        }
+    }
+    filtered
+}
+
+/// Global variable an expression refers to, through indexing into binding arrays.
+fn global_variable(
+    function: &Function,
+    expression: Handle<Expression>,
+) -> Option<Handle<GlobalVariable>> {
+    match function.expressions[expression] {
+        Expression::GlobalVariable(var) => Some(var),
+        Expression::Access { base, .. } | Expression::AccessIndex { base, .. } => {
+            global_variable(function, base)
+        }
+        _ => None,
+    }
+}
+
+fn view_dimension(dim: ImageDimension, arrayed: bool) -> wgpu::TextureViewDimension {
+    match (dim, arrayed) {
+        (ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+        (ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+        (ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+        (ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+        (ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+        (ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+    }
+}
+
+/// Texture formats of WGSL storage textures
+const STORAGE_FORMATS: [(StorageFormat, wgpu::TextureFormat); 32] = {
+    use wgpu::TextureFormat as Tf;
+    use StorageFormat as Sf;
+    [
+        (Sf::R8Unorm, Tf::R8Unorm),
+        (Sf::R8Snorm, Tf::R8Snorm),
+        (Sf::R8Uint, Tf::R8Uint),
+        (Sf::R8Sint, Tf::R8Sint),
+        (Sf::R16Uint, Tf::R16Uint),
+        (Sf::R16Sint, Tf::R16Sint),
+        (Sf::R16Float, Tf::R16Float),
+        (Sf::Rg8Unorm, Tf::Rg8Unorm),
+        (Sf::Rg8Snorm, Tf::Rg8Snorm),
+        (Sf::Rg8Uint, Tf::Rg8Uint),
+        (Sf::Rg8Sint, Tf::Rg8Sint),
+        (Sf::R32Uint, Tf::R32Uint),
+        (Sf::R32Sint, Tf::R32Sint),
+        (Sf::R32Float, Tf::R32Float),
+        (Sf::Rg16Uint, Tf::Rg16Uint),
+        (Sf::Rg16Sint, Tf::Rg16Sint),
+        (Sf::Rg16Float, Tf::Rg16Float),
+        (Sf::Rgba8Unorm, Tf::Rgba8Unorm),
+        (Sf::Rgba8Snorm, Tf::Rgba8Snorm),
+        (Sf::Rgba8Uint, Tf::Rgba8Uint),
+        (Sf::Rgba8Sint, Tf::Rgba8Sint),
+        (Sf::Rgb10a2Unorm, Tf::Rgb10a2Unorm),
+        (Sf::Rg11b10Float, Tf::Rg11b10Float),
+        (Sf::Rg32Uint, Tf::Rg32Uint),
+        (Sf::Rg32Sint, Tf::Rg32Sint),
+        (Sf::Rg32Float, Tf::Rg32Float),
+        (Sf::Rgba16Uint, Tf::Rgba16Uint),
+        (Sf::Rgba16Sint, Tf::Rgba16Sint),
+        (Sf::Rgba16Float, Tf::Rgba16Float),
+        (Sf::Rgba32Uint, Tf::Rgba32Uint),
+        (Sf::Rgba32Sint, Tf::Rgba32Sint),
+        (Sf::Rgba32Float, Tf::Rgba32Float),
+    ]
+};
+
+fn storage_format(format: StorageFormat) -> wgpu::TextureFormat {
+    STORAGE_FORMATS
+        .iter()
+        .find(|(x, _)| *x == format)
+        .map(|(_, x)| *x)
+        .expect("Every storage format is in the table")
+}
+
+#[cfg(test)]
+mod tests {
+    use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+    use super::*;
+
+    fn reflect(code: &str) -> ShaderLayout {
+        let module = naga::front::wgsl::parse_str(code).unwrap();
+        let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+            .validate(&module)
+            .unwrap();
+        ShaderLayout::from_module(&module, &info)
+    }
+
+    fn entry(binding: u32, ty: wgpu::BindingType) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty,
+            count: None,
+        }
+    }
+
+    fn texture(filterable: bool) -> wgpu::BindingType {
+        wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        }
+    }
+
+    const SHADER: &str = "
+        struct Params {
+            scale: f32,
+            offset: vec2<f32>,
+        };
+
+        @group(0) @binding(0)
+        var<uniform> params: Params;
+        @group(1) @binding(0)
+        var color: texture_2d<f32>;
+        @group(1) @binding(1)
+        var color_sampler: sampler;
+        @group(1) @binding(2)
+        var loaded: texture_2d<f32>;
+        @group(2) @binding(0)
+        var output: texture_storage_2d<rgba16float, write>;
+
+        @compute @workgroup_size(8, 8)
+        fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+            let uv = vec2<f32>(id.xy) * params.scale + params.offset;
+            let sampled = textureSampleLevel(color, color_sampler, uv, 0.0);
+            let texel = textureLoad(loaded, vec2<i32>(id.xy), 0);
+            textureStore(output, vec2<i32>(id.xy), sampled + texel);
+        }
+    ";
+
+    #[test]
+    fn reflects_bind_group_layout_entries() {
+        let layout = reflect(SHADER);
+        let expected = vec![
+            vec![entry(
+                0,
+                wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(16),
+                },
+            )],
+            vec![
+                // Only sampled textures have to be filterable
+                entry(0, texture(true)),
+                entry(
+                    1,
+                    wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                ),
+                entry(2, texture(false)),
+            ],
+            vec![entry(
+                0,
+                wgpu::BindingType::StorageTexture {
+                    access: wgpu::StorageTextureAccess::WriteOnly,
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                },
+            )],
+        ];
+        assert_eq!(layout.groups, expected);
+    }
+
+    #[test]
+    fn reflection_survives_encoding() {
+        let module = naga::front::wgsl::parse_str(SHADER).unwrap();
+        let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+            .validate(&module)
+            .unwrap();
+        let reflection = Reflection::from_module(&module, &info);
+        assert_eq!(reflection.structs[0].name, "Params");
+        assert_eq!(reflection.structs[0].members[1].offset, 8);
+
+        let mut words = vec![];
+        reflection.encode(&mut words);
+        assert_eq!(Reflection::decode(&words), Some(reflection));
+        assert_eq!(Reflection::decode(&words[1..]), None);
+        words.push(0);
+        assert_eq!(Reflection::decode(&words), None);
+    }
+
+    #[test]
+    fn bind_groups_override_reflected_entries() {
+        let dynamic = entry(
+            0,
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: NonZeroU64::new(16),
+            },
+        );
+        let extra = entry(1, texture(true));
+        let layout = reflect(SHADER).with_bind_groups(&[wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[dynamic, extra],
+        }]);
+        assert_eq!(layout.groups[0], [dynamic, extra]);
+        assert_eq!(layout.groups[1].len(), 3);
+    }
+
+    #[test]
+    fn unused_bindings_are_visible_to_every_stage() {
+        let layout = reflect(
+            "
+            @group(0) @binding(0)
+            var unused: sampler;
+
+            @vertex
+            fn vs_main() -> @builtin(position) vec4<f32> {
+                return vec4<f32>(0.0);
+            }
+
+            @fragment
+            fn fs_main() -> @location(0) vec4<f32> {
+                return vec4<f32>(1.0);
+            }
+            ",
+        );
+        assert_eq!(
+            layout.groups[0][0].visibility,
+            wgpu::ShaderStages::VERTEX_FRAGMENT
+        );
+    }
+}
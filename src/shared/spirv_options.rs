@@ -0,0 +1,51 @@
+use naga::back::spv::{self, BindingMap};
+
+// https://github.com/gfx-rs/wgpu/blob/master/wgpu-hal/src/vulkan/adapter.rs#L1166
+pub fn get_options() -> spv::Options {
+    let capabilities = vec![
+        spv::Capability::Shader,
+        spv::Capability::Matrix,
+        spv::Capability::Sampled1D,
+        spv::Capability::Image1D,
+        spv::Capability::ImageQuery,
+        spv::Capability::DerivativeControl,
+        spv::Capability::SampledCubeArray,
+        spv::Capability::SampleRateShading,
+        //Note: this is requested always, no matter what the actual
+        // adapter supports. It's not the responsibility of SPV-out
+        // translation to handle the storage support for formats.
+        spv::Capability::StorageImageExtendedFormats,
+        spv::Capability::MultiView,
+        //TODO: fill out the rest
+    ];
+
+    let mut flags = spv::WriterFlags::empty();
+    flags.set(
+        spv::WriterFlags::DEBUG,
+        true,
+        // self.instance.flags.contains(crate::InstanceFlags::DEBUG),
+    );
+    flags.set(
+        spv::WriterFlags::LABEL_VARYINGS,
+        true, // self.phd_capabilities.properties.vendor_id != crate::auxil::db::qualcomm::VENDOR,
+    );
+    flags.set(
+        spv::WriterFlags::FORCE_POINT_SIZE,
+        //Note: we could technically disable this when we are compiling separate entry points,
+        // and we know exactly that the primitive topology is not `PointList`.
+        // But this requires cloning the `spv::Options` struct, which has heap allocations.
+        true, // could check `super::Workarounds::SEPARATE_ENTRY_POINTS`
+    );
+    spv::Options {
+        binding_map: BindingMap::new(),
+        lang_version: (1, 0),
+        flags,
+        capabilities: Some(capabilities.into_iter().collect()),
+        bounds_check_policies: naga::proc::BoundsCheckPolicies {
+            index: naga::proc::BoundsCheckPolicy::Unchecked,
+            buffer: naga::proc::BoundsCheckPolicy::Unchecked,
+            image: naga::proc::BoundsCheckPolicy::Unchecked,
+            binding_array: naga::proc::BoundsCheckPolicy::Unchecked,
+        },
+    }
+}
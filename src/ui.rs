@@ -0,0 +1,232 @@
+use std::collections::VecDeque;
+
+use egui_wgpu_backend::{RenderPass, ScreenDescriptor};
+use winit::{event::WindowEvent, window::Window};
+
+use crate::{
+    context::{Context, GpuProfiler, RendererInfo, Uniform},
+    utils::frame_counter::FrameCounter,
+};
+
+/// Number of frames in the frame time plot
+const FRAME_HISTORY: usize = 240;
+
+/// egui overlay drawn on top of the presented frame.
+///
+/// It sees window events before the demo and keeps the ones meant for its
+/// widgets. Screenshots and recordings are taken without it.
+pub struct Ui {
+    ctx: egui::Context,
+    state: egui_winit::State,
+    render_pass: RenderPass,
+    info: RendererInfo,
+    /// Frame times in milliseconds, the latest last
+    frame_times: VecDeque<f32>,
+    paint_jobs: Vec<egui::ClippedMesh>,
+    textures_delta: egui::TexturesDelta,
+    pub visible: bool,
+}
+
+impl Ui {
+    pub fn new(context: &Context, window: &Window) -> Self {
+        let max_texture_side = context.limits.max_texture_dimension_2d as usize;
+        Self {
+            ctx: egui::Context::default(),
+            state: egui_winit::State::new(max_texture_side, window),
+            render_pass: RenderPass::new(&context.device, context.surface_config.format, 1),
+            info: context.get_info(),
+            frame_times: VecDeque::with_capacity(FRAME_HISTORY),
+            paint_jobs: vec![],
+            textures_delta: Default::default(),
+            visible: true,
+        }
+    }
+
+    /// Passes `event` to egui, returns `true` if a widget takes it.
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        // Hidden, egui still has to follow resizes and scale changes
+        let consumed = self.state.on_event(&self.ctx, event);
+        self.visible && consumed
+    }
+
+    /// Whether the pointer is over or dragging a widget, so it shouldn't move the camera.
+    pub fn wants_pointer(&self) -> bool {
+        self.visible && self.ctx.wants_pointer_input()
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Lays out the built-in panels and the ones added by `demo_ui`.
+    pub fn run(
+        &mut self,
+        window: &Window,
+        context: &Context,
+        frame_counter: &FrameCounter,
+        demo_ui: impl FnOnce(&egui::Context),
+    ) {
+        if self.frame_times.len() == FRAME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_counter.frame_time());
+
+        if !self.visible {
+            self.paint_jobs.clear();
+            return;
+        }
+        let input = self.state.take_egui_input(window);
+        let output = self.ctx.run(input, |ctx| {
+            egui::Window::new("Vokselis")
+                .default_width(280.)
+                .show(ctx, |ui| {
+                    renderer_panel(ui, &self.info);
+                    frame_time_panel(ui, &self.frame_times);
+                    gpu_panel(ui, &context.profiler.borrow());
+                    uniform_panel(ui, &context.global_uniform);
+                });
+            demo_ui(ctx);
+        });
+        self.state
+            .handle_platform_output(window, &self.ctx, output.platform_output);
+        self.textures_delta.append(output.textures_delta);
+        self.paint_jobs = self.ctx.tessellate(output.shapes);
+    }
+
+    /// Draws the panels laid out by the last [`run`](Self::run) over `view`.
+    pub fn record(
+        &mut self,
+        context: &Context,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        let screen_descriptor = ScreenDescriptor {
+            physical_width: context.width,
+            physical_height: context.height,
+            scale_factor: self.ctx.pixels_per_point(),
+        };
+        let textures_delta = std::mem::take(&mut self.textures_delta);
+        if let Err(err) =
+            self.render_pass
+                .add_textures(&context.device, &context.queue, &textures_delta)
+        {
+            eprintln!("Failed to upload UI textures: {err:?}");
+        }
+        self.render_pass.update_buffers(
+            &context.device,
+            &context.queue,
+            &self.paint_jobs,
+            &screen_descriptor,
+        );
+        if let Err(err) =
+            self.render_pass
+                .execute(encoder, view, &self.paint_jobs, &screen_descriptor, None)
+        {
+            eprintln!("Failed to draw the UI: {err:?}");
+        }
+        if let Err(err) = self.render_pass.remove_textures(textures_delta) {
+            eprintln!("Failed to free UI textures: {err:?}");
+        }
+    }
+}
+
+fn renderer_panel(ui: &mut egui::Ui, info: &RendererInfo) {
+    egui::CollapsingHeader::new("Renderer")
+        .default_open(true)
+        .show(ui, |ui| {
+            egui::Grid::new("renderer").num_columns(2).show(ui, |ui| {
+                let rows = [
+                    ("Vendor", info.vendor_name.clone()),
+                    ("Device", info.device_name.clone()),
+                    ("Type", info.device_type.clone()),
+                    ("Backend", info.backend.clone()),
+                    ("Shaders", info.shader_backend.to_string()),
+                    ("Format", format!("{:?}", info.screen_format)),
+                ];
+                for (name, value) in rows {
+                    ui.label(name);
+                    ui.label(value);
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+fn frame_time_panel(ui: &mut egui::Ui, frame_times: &VecDeque<f32>) {
+    egui::CollapsingHeader::new("Frame time")
+        .default_open(true)
+        .show(ui, |ui| {
+            let last = frame_times.back().copied().unwrap_or_default();
+            let avg = frame_times.iter().sum::<f32>() / frame_times.len().max(1) as f32;
+            let max = frame_times.iter().copied().fold(0., f32::max);
+            ui.label(format!(
+                "{last:.2}ms, avg {avg:.2}ms ({:.0} fps), max {max:.2}ms",
+                1000. / avg.max(f32::EPSILON)
+            ));
+
+            let times: Vec<f32> = frame_times.iter().copied().collect();
+            let line = egui::plot::Line::new(egui::plot::Values::from_ys_f32(&times));
+            egui::plot::Plot::new("frame_times")
+                .height(80.)
+                .include_y(0.)
+                .include_x(FRAME_HISTORY as f32)
+                .allow_drag(false)
+                .allow_zoom(false)
+                .show_x(false)
+                .show(ui, |plot| plot.line(line));
+        });
+}
+
+fn gpu_panel(ui: &mut egui::Ui, profiler: &GpuProfiler) {
+    egui::CollapsingHeader::new("GPU")
+        .default_open(true)
+        .show(ui, |ui| {
+            if !profiler.is_supported() {
+                ui.label("Timestamp queries are unsupported");
+                return;
+            }
+            let frame = match profiler.last_frame() {
+                Some(frame) => frame,
+                None => return,
+            };
+            egui::Grid::new("gpu").num_columns(2).show(ui, |ui| {
+                for scope in &frame.scopes {
+                    ui.label(format!(
+                        "{:indent$}{}",
+                        "",
+                        scope.label,
+                        indent = 2 * scope.depth
+                    ));
+                    ui.monospace(format!("{:.3}ms", scope.duration_ms()));
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+fn uniform_panel(ui: &mut egui::Ui, uniform: &Uniform) {
+    egui::CollapsingHeader::new("Uniform")
+        .default_open(false)
+        .show(ui, |ui| {
+            egui::Grid::new("uniform").num_columns(2).show(ui, |ui| {
+                let [x, y, z] = uniform.pos;
+                let [width, height] = uniform.resolution;
+                let [mouse_x, mouse_y] = uniform.mouse;
+                let rows = [
+                    ("pos", format!("{x:.3} {y:.3} {z:.3}")),
+                    ("frame", uniform.frame.to_string()),
+                    ("resolution", format!("{width} {height}")),
+                    ("mouse", format!("{mouse_x:.3} {mouse_y:.3}")),
+                    ("mouse_pressed", uniform.mouse_pressed.to_string()),
+                    ("time", format!("{:.3}", uniform.time)),
+                    ("time_delta", format!("{:.3}", uniform.time_delta)),
+                    ("jitter", format!("{:.3}", uniform.jitter)),
+                ];
+                for (name, value) in rows {
+                    ui.label(name);
+                    ui.monospace(value);
+                    ui.end_row();
+                }
+            });
+        });
+}
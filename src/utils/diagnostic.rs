@@ -0,0 +1,157 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use codespan_reporting::{
+    diagnostic::Label,
+    files::SimpleFiles,
+    term::{
+        self,
+        termcolor::{ColorChoice, NoColor, StandardStream, WriteColor},
+        Chars, Config,
+    },
+};
+
+use super::preprocessor::ShaderSource;
+
+/// Parse or validation error with its spans mapped from the preprocessed
+/// code back to the files it was expanded from.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    pub labels: Vec<DiagnosticLabel>,
+    /// Causes of the error, outermost first
+    pub notes: Vec<String>,
+}
+
+/// Span of a [`Diagnostic`] in one of the original files.
+///
+/// Spans covering several lines are cut at the end of the first one.
+#[derive(Debug, Clone)]
+pub struct DiagnosticLabel {
+    pub path: PathBuf,
+    /// 1-based line in `path`
+    pub line: u32,
+    /// 1-based byte offset in the line
+    pub column: u32,
+    /// Length of the span in bytes
+    pub len: usize,
+    /// Text of the line the span starts in
+    pub source_line: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Maps `labels`, spans of the expanded code of `source`, to the original files.
+    pub fn new<'a>(
+        message: impl Into<String>,
+        labels: impl IntoIterator<Item = (Range<usize>, &'a str)>,
+        notes: Vec<String>,
+        source: &ShaderSource,
+    ) -> Self {
+        let labels = labels
+            .into_iter()
+            .map(|(span, message)| DiagnosticLabel::new(span, message, source))
+            .collect();
+        Self {
+            message: message.into(),
+            labels,
+            notes,
+        }
+    }
+
+    /// Writes the diagnostic to stderr, colored unless `NO_COLOR` is set.
+    pub fn emit_to_stderr(&self) {
+        let mut writer = StandardStream::stderr(ColorChoice::Auto);
+        self.emit(&mut writer, &Config::default());
+    }
+
+    /// Renders the diagnostic like [`emit_to_stderr`](Self::emit_to_stderr)
+    /// without colors.
+    ///
+    /// Only ASCII is used, the text also ends up in the error overlay.
+    pub fn emit_to_string(&self) -> String {
+        let config = Config {
+            chars: Chars::ascii(),
+            ..Default::default()
+        };
+        let mut writer = NoColor::new(vec![]);
+        self.emit(&mut writer, &config);
+        String::from_utf8_lossy(&writer.into_inner()).into_owned()
+    }
+
+    fn emit(&self, writer: &mut dyn WriteColor, config: &Config) {
+        // Files are rebuilt from the labeled lines, the ones on disk may have changed since
+        let mut files = SimpleFiles::new();
+        let mut paths: Vec<&Path> = vec![];
+        for label in &self.labels {
+            if !paths.contains(&label.path.as_path()) {
+                paths.push(&label.path);
+            }
+        }
+        let mut labels = vec![];
+        for path in paths {
+            let file_labels: Vec<_> = self.labels.iter().filter(|x| x.path == path).collect();
+            let line_count = file_labels.iter().map(|x| x.line).max().unwrap_or(1) as usize;
+            let mut lines = vec![""; line_count];
+            for label in &file_labels {
+                lines[label.line as usize - 1] = &label.source_line;
+            }
+
+            let mut line_starts = vec![0];
+            for line in &lines {
+                line_starts.push(line_starts.last().unwrap() + line.len() + 1);
+            }
+            let file = files.add(path.display().to_string(), lines.join("\n"));
+            for label in file_labels {
+                let start = line_starts[label.line as usize - 1] + label.column as usize - 1;
+                labels.push(
+                    Label::primary(file, start..start + label.len)
+                        .with_message(label.message.clone()),
+                );
+            }
+        }
+
+        let diagnostic = codespan_reporting::diagnostic::Diagnostic::error()
+            .with_message(self.message.clone())
+            .with_labels(labels)
+            .with_notes(self.notes.clone());
+        let _ = term::emit(writer, config, &files, &diagnostic);
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.emit_to_string())
+    }
+}
+
+impl DiagnosticLabel {
+    fn new(span: Range<usize>, message: &str, source: &ShaderSource) -> Self {
+        let code = &source.code;
+        // Errors at the end of the code point past the last line
+        let start = span.start.min(code.trim_end_matches('\n').len());
+        let line_start = code[..start].rfind('\n').map_or(0, |x| x + 1);
+        let line_number = code[..start].matches('\n').count() as u32 + 1;
+        let column = start - line_start;
+        let source_line = code[line_start..].lines().next().unwrap_or_default();
+        let len = span
+            .end
+            .saturating_sub(start)
+            .min(source_line.len() - column);
+
+        let (path, line) = match source.source_map.locate(line_number) {
+            Some((path, line)) => (path.to_path_buf(), line),
+            None => (PathBuf::from("<generated>"), line_number),
+        };
+        Self {
+            path,
+            line,
+            column: column as u32 + 1,
+            len,
+            source_line: source_line.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
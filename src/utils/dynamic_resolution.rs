@@ -0,0 +1,173 @@
+/// Adjusts the render scale of the [HdrBackBuffer](crate::HdrBackBuffer)
+/// to keep frame times close to a target budget.
+///
+/// Frame times are smoothed with an exponential moving average and the
+/// scale only changes once the average leaves the `target ± hysteresis`
+/// band, after which the controller waits `cooldown` frames before it
+/// is allowed to react again.
+#[derive(Debug, Clone, Copy)]
+pub struct DynamicResolution {
+    /// Frame time budget in milliseconds.
+    pub target_frame_time: f32,
+    /// Relative width of the dead zone around the target.
+    pub hysteresis: f32,
+    /// Number of frames to wait after a scale change.
+    pub cooldown: u32,
+    pub min_scale: f32,
+    pub max_scale: f32,
+    /// Largest scale change allowed in a single step.
+    pub max_step: f32,
+
+    smoothed_frame_time: f32,
+    scale: f32,
+    frames_since_change: u32,
+}
+
+impl DynamicResolution {
+    const SMOOTHING: f32 = 0.1;
+    const QUANTIZATION: f32 = 20.;
+
+    pub fn new(target_frame_time: f32) -> Self {
+        Self {
+            target_frame_time,
+            hysteresis: 0.1,
+            cooldown: 30,
+            min_scale: 0.25,
+            max_scale: 1.0,
+            max_step: 0.1,
+
+            smoothed_frame_time: target_frame_time,
+            scale: 1.0,
+            frames_since_change: 0,
+        }
+    }
+
+    /// Creates a controller for a target frame rate instead of a frame time.
+    pub fn with_fps(fps: f32) -> Self {
+        Self::new(1000. / fps)
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn smoothed_frame_time(&self) -> f32 {
+        self.smoothed_frame_time
+    }
+
+    /// Feeds a new frame time in milliseconds, measured either on the CPU or on the GPU.
+    ///
+    /// Returns the new render scale if it has changed.
+    pub fn record(&mut self, frame_time: f32) -> Option<f32> {
+        if !frame_time.is_finite() || frame_time <= 0. {
+            return None;
+        }
+        self.smoothed_frame_time += (frame_time - self.smoothed_frame_time) * Self::SMOOTHING;
+        self.frames_since_change = self.frames_since_change.saturating_add(1);
+        if self.frames_since_change < self.cooldown {
+            return None;
+        }
+
+        let upper = self.target_frame_time * (1. + self.hysteresis);
+        let lower = self.target_frame_time * (1. - self.hysteresis);
+        if (lower..=upper).contains(&self.smoothed_frame_time) {
+            return None;
+        }
+
+        // Frame time scales roughly with the pixel count, i.e. with `scale^2`.
+        let ideal = self.scale * (self.target_frame_time / self.smoothed_frame_time).sqrt();
+        let step = (ideal - self.scale).clamp(-self.max_step, self.max_step);
+        let scale = ((self.scale + step) * Self::QUANTIZATION).round() / Self::QUANTIZATION;
+        let scale = scale.clamp(self.min_scale, self.max_scale);
+        if (scale - self.scale).abs() < f32::EPSILON {
+            return None;
+        }
+
+        self.scale = scale;
+        self.frames_since_change = 0;
+        // The old measurements belong to the previous resolution.
+        self.smoothed_frame_time = self.target_frame_time;
+        Some(scale)
+    }
+
+    /// Forgets the accumulated history and returns to full resolution.
+    pub fn reset(&mut self) {
+        self.scale = 1.0_f32.clamp(self.min_scale, self.max_scale);
+        self.smoothed_frame_time = self.target_frame_time;
+        self.frames_since_change = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records `frames` frames of `frame_time` ms, returns every scale change.
+    fn run(controller: &mut DynamicResolution, frame_time: f32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .filter_map(|_| controller.record(frame_time))
+            .collect()
+    }
+
+    #[test]
+    fn lowers_the_scale_after_sustained_slow_frames() {
+        let mut controller = DynamicResolution::new(16.);
+        // A single spike is smoothed away
+        assert_eq!(controller.record(100.), None);
+        assert_eq!(run(&mut controller, 16., 100), []);
+        assert_eq!(controller.scale(), 1.);
+
+        let mut scales = vec![controller.scale()];
+        let mut change_frames = vec![];
+        for frame in 0..200 {
+            if let Some(scale) = controller.record(32.) {
+                scales.push(scale);
+                change_frames.push(frame);
+            }
+        }
+        assert!(scales.len() > 2, "{scales:?}");
+        for pair in scales.windows(2) {
+            assert!(pair[1] < pair[0], "{scales:?}");
+            assert!(
+                pair[0] - pair[1] <= controller.max_step + 1e-6,
+                "{scales:?}"
+            );
+        }
+        for pair in change_frames.windows(2) {
+            assert!(
+                pair[1] - pair[0] >= controller.cooldown,
+                "{change_frames:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn holds_the_scale_inside_the_hysteresis_band() {
+        let mut controller = DynamicResolution::new(16.);
+        controller.cooldown = 1;
+        let mut changes = vec![];
+        for frame in 0..1000 {
+            // Alternating frame times within ±10% around the target
+            let frame_time = if frame % 2 == 0 { 17.5 } else { 14.5 };
+            changes.extend(controller.record(frame_time));
+        }
+        assert_eq!(changes, []);
+        assert!((controller.smoothed_frame_time() - 16.).abs() < 1.);
+    }
+
+    #[test]
+    fn clamps_to_the_scale_bounds() {
+        let mut controller = DynamicResolution::new(16.);
+        controller.cooldown = 1;
+        run(&mut controller, 1000., 1000);
+        assert_eq!(controller.scale(), controller.min_scale);
+        assert_eq!(controller.record(1000.), None);
+
+        run(&mut controller, 1., 1000);
+        assert_eq!(controller.scale(), controller.max_scale);
+        assert_eq!(controller.record(1.), None);
+
+        assert_eq!(controller.record(f32::NAN), None);
+        assert_eq!(controller.record(0.), None);
+    }
+}
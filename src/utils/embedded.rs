@@ -0,0 +1,75 @@
+//! Shaders of the crate embedded in the binary by the build script.
+
+use std::path::Path;
+
+use super::{preprocessor::ShaderDefines, reflection::Reflection};
+
+/// Sources by path relative to the shader folder
+static SOURCES: &[(&str, &str)] = include!(concat!(env!("OUT_DIR"), "/embedded_shaders.rs"));
+
+/// Path, defines, SPIR-V and encoded [`Reflection`] of a compiled variant
+type Bundled = (
+    &'static str,
+    &'static [&'static str],
+    &'static [u32],
+    &'static [u32],
+);
+
+/// Variants compiled by the build script, only bundled in release builds
+static BUNDLE: &[Bundled] = include!(concat!(env!("OUT_DIR"), "/shader_bundle.rs"));
+
+pub fn source(path: &Path) -> Option<&'static str> {
+    let path = normalize(path)?;
+    SOURCES
+        .iter()
+        .find(|(name, _)| *name == path)
+        .map(|(_, x)| *x)
+}
+
+/// SPIR-V and reflection of the variant of the shader at `path` selected by
+/// `defines`, if the build script compiled it.
+pub fn compiled(path: &Path, defines: &ShaderDefines) -> Option<(&'static [u32], Reflection)> {
+    let path = normalize(path)?;
+    let (_, _, words, reflection) = BUNDLE
+        .iter()
+        .find(|(name, names, ..)| *name == path && names.iter().copied().eq(defines.iter()))?;
+    Some((words, Reflection::decode(reflection)?))
+}
+
+pub fn len() -> usize {
+    SOURCES.len()
+}
+
+/// Path relative to the shader folder with forward slashes.
+fn normalize(path: &Path) -> Option<String> {
+    let path = path.to_str()?.replace('\\', "/");
+    Some(path.trim_start_matches("./").to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{shader_compiler::ShaderCompiler, shader_roots::ShaderRoots};
+
+    /// Only release builds have a bundle to check.
+    #[test]
+    fn bundle_matches_the_embedded_sources() {
+        let mut compiler = ShaderCompiler::new();
+        compiler.set_disk_cache(None);
+        for (name, defines, words, reflection) in BUNDLE {
+            let defines: ShaderDefines = defines.iter().copied().collect();
+            let (module, info) = compiler
+                .validate(
+                    Path::new(ShaderRoots::EMBEDDED).join(name).as_path(),
+                    &defines,
+                )
+                .unwrap();
+            assert_eq!(
+                Reflection::decode(reflection),
+                Some(Reflection::from_module(&module, &info)),
+                "{name} {defines}"
+            );
+            assert_eq!(compiled(Path::new(name), &defines).unwrap().0, *words);
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use std::{fmt::Write, path::Path};
+
+use super::shader_compiler::{self, ShaderCompiler};
+
+/// Shader standing in for one that failed to compile.
+///
+/// Provides the entry points a pipeline expects: vertex entry points cover
+/// the screen with a triangle, fragment entry points write magenta to every
+/// target and compute entry points do nothing. Pipelines built with it keep
+/// their bind group layouts, so the watcher can reload them with the real
+/// shader once it compiles.
+#[derive(Debug, Clone, Default)]
+pub struct FallbackShader {
+    code: String,
+}
+
+impl FallbackShader {
+    pub const COLOR: [f32; 4] = [1., 0., 1., 1.];
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn vertex(mut self, entry_point: &str) -> Self {
+        let _ = write!(
+            self.code,
+            "@vertex
+fn {entry_point}(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {{
+    let idx = vertex_index % 3u;
+    let uv = vec2<f32>(f32((idx << 1u) & 2u), f32(idx & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}}
+
+"
+        );
+        self
+    }
+
+    pub fn fragment(mut self, entry_point: &str, targets: u32) -> Self {
+        let [r, g, b, a] = Self::COLOR;
+        let _ = writeln!(self.code, "struct {entry_point}_Output {{");
+        for location in 0..targets {
+            let _ = writeln!(
+                self.code,
+                "    @location({location}) target{location}: vec4<f32>,"
+            );
+        }
+        let values = vec![format!("vec4<f32>({r:?}, {g:?}, {b:?}, {a:?})"); targets as usize];
+        let _ = write!(
+            self.code,
+            "}};
+
+@fragment
+fn {entry_point}() -> {entry_point}_Output {{
+    return {entry_point}_Output({});
+}}
+
+",
+            values.join(", ")
+        );
+        self
+    }
+
+    pub fn compute(mut self, entry_point: &str) -> Self {
+        let _ = write!(
+            self.code,
+            "@compute @workgroup_size(1)
+fn {entry_point}() {{}}
+
+"
+        );
+        self
+    }
+
+    pub fn create_module(
+        &self,
+        device: &wgpu::Device,
+        compiler: &mut ShaderCompiler,
+    ) -> wgpu::ShaderModule {
+        let path = Path::new("<fallback>");
+        let words = compiler
+            .create_shader_module_from_source(path, &self.code)
+            .expect("Fallback shader is valid");
+        shader_compiler::create_module(device, Some("Fallback Shader"), &words)
+    }
+}
@@ -3,6 +3,7 @@ use std::time::Instant;
 pub struct FrameCounter {
     pub frame_count: u32,
     accum_time: f32,
+    last_frame_time: f32,
     last_inst: Instant,
 }
 
@@ -12,11 +13,21 @@ impl FrameCounter {
     }
 
     pub fn time_delta(&self) -> f32 {
+        if self.frame_count == 0 {
+            return self.last_frame_time;
+        }
         self.accum_time * 1000.0 / self.frame_count as f32
     }
 
+    /// Duration of the last recorded frame in milliseconds.
+    pub fn frame_time(&self) -> f32 {
+        self.last_frame_time
+    }
+
     pub fn record(&mut self) -> f32 /* dt */ {
-        self.accum_time += self.last_inst.elapsed().as_secs_f32();
+        let dt = self.last_inst.elapsed().as_secs_f32();
+        self.last_frame_time = dt * 1000.0;
+        self.accum_time += dt;
         self.last_inst = Instant::now();
 
         self.frame_count += 1;
@@ -34,6 +45,7 @@ impl Default for FrameCounter {
         Self {
             frame_count: 0,
             accum_time: 0.,
+            last_frame_time: 0.,
             last_inst: Instant::now(),
         }
     }
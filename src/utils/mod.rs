@@ -7,10 +7,25 @@ use std::{
     path::Path,
 };
 
+pub use crate::shared::{preprocessor, spirv_options};
+
+pub mod bitmap_font;
+pub mod diagnostic;
+pub mod dynamic_resolution;
+pub mod embedded;
+pub mod fallback;
 pub mod frame_counter;
 pub mod input;
+pub mod obj;
+pub mod params;
 pub mod recorder;
+pub mod reflection;
+pub mod shader_cache;
 pub mod shader_compiler;
+pub mod shader_roots;
+pub mod struct_layout;
+pub mod transfer_function;
+pub mod volume;
 
 pub fn dispatch_optimal(len: u32, subgroup_size: u32) -> u32 {
     let padded_size = (subgroup_size - len % subgroup_size) % subgroup_size;
@@ -0,0 +1,161 @@
+use std::path::Path;
+
+use color_eyre::eyre::{eyre, Result};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+}
+
+/// Loads positions and normals from a Wavefront OBJ file.
+///
+/// Polygons are triangulated as fans, missing normals are replaced
+/// with face normals.
+pub fn load_obj(path: &Path) -> Result<(Vec<MeshVertex>, Vec<u32>)> {
+    let source = std::fs::read_to_string(path)?;
+    parse_obj(&source).map_err(|err| eyre!("{}:{err}", path.display()))
+}
+
+/// Parses the contents of an OBJ file, errors start with the line number.
+pub fn parse_obj(source: &str) -> Result<(Vec<MeshVertex>, Vec<u32>)> {
+    let mut positions = vec![];
+    let mut normals = vec![];
+    let mut vertices = vec![];
+    let mut indices = vec![];
+
+    for (line_number, line) in source.lines().enumerate() {
+        let err = || eyre!("{}: malformed line", line_number + 1);
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => positions.push(parse_vec3(tokens).ok_or_else(err)?),
+            Some("vn") => normals.push(parse_vec3(tokens).ok_or_else(err)?),
+            Some("f") => {
+                let mut face = vec![];
+                for token in tokens {
+                    let mut parts = token.split('/');
+                    let position = parts
+                        .next()
+                        .and_then(|x| resolve_index(x, positions.len()))
+                        .ok_or_else(err)?;
+                    let normal = parts
+                        .nth(1)
+                        .and_then(|x| resolve_index(x, normals.len()))
+                        .map(|i| normals[i]);
+                    face.push((positions[position], normal));
+                }
+                if face.len() < 3 {
+                    return Err(err());
+                }
+
+                let face_normal = {
+                    let [a, b, c] = [face[0].0, face[1].0, face[2].0].map(glam::Vec3::from);
+                    (b - a).cross(c - a).normalize_or_zero().to_array()
+                };
+                let base = vertices.len() as u32;
+                vertices.extend(face.iter().map(|&(position, normal)| MeshVertex {
+                    position,
+                    normal: normal.unwrap_or(face_normal),
+                }));
+                for i in 1..face.len() as u32 - 1 {
+                    indices.extend([base, base + i, base + i + 1]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok((vertices, indices))
+}
+
+fn parse_vec3<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Option<[f32; 3]> {
+    let mut res = [0.; 3];
+    for x in &mut res {
+        *x = tokens.next()?.parse().ok()?;
+    }
+    Some(res)
+}
+
+/// OBJ indices are 1-based and negative values are relative to the end.
+fn resolve_index(token: &str, len: usize) -> Option<usize> {
+    let index: i64 = token.parse().ok()?;
+    let index = match index {
+        0 => return None,
+        i if i > 0 => i - 1,
+        i => len as i64 + i,
+    };
+    (0..len as i64).contains(&index).then_some(index as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUAD: &str = "\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vn 0 0 1
+vn 0 0 -1
+";
+
+    #[test]
+    fn faces_with_texture_and_normal_indices() {
+        let source = format!("{QUAD}f 1/1/2 2/1/2 3/1/2\nf 1//1 3//1 4//1\n");
+        let (vertices, indices) = parse_obj(&source).unwrap();
+
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(indices, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(vertices[1].position, [1., 0., 0.]);
+        assert!(vertices[..3].iter().all(|v| v.normal == [0., 0., -1.]));
+        assert!(vertices[3..].iter().all(|v| v.normal == [0., 0., 1.]));
+    }
+
+    #[test]
+    fn polygons_are_fans_with_face_normals() {
+        let source = format!("{QUAD}f 1 2 3 4\n");
+        let (vertices, indices) = parse_obj(&source).unwrap();
+
+        assert_eq!(indices, [0, 1, 2, 0, 2, 3]);
+        assert!(vertices.iter().all(|v| v.normal == [0., 0., 1.]));
+    }
+
+    #[test]
+    fn negative_indices_count_from_the_end() {
+        let source = format!("{QUAD}f -4//-1 -3//-1 -1//-1\n");
+        let (vertices, _) = parse_obj(&source).unwrap();
+
+        let positions: Vec<_> = vertices.iter().map(|v| v.position).collect();
+        assert_eq!(positions, [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]]);
+        assert!(vertices.iter().all(|v| v.normal == [0., 0., -1.]));
+    }
+
+    #[test]
+    fn malformed_lines_report_their_number() {
+        let cases = [
+            "v 0 0\n",
+            "v 0 0 0\nvn 0 x 1\n",
+            "v 0 0 0\nv 1 0 0\nf 1 2\n",
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 4\n",
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nf 0 1 2\n",
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 -4\n",
+        ];
+        for source in cases {
+            let line = source.lines().count();
+            let err = parse_obj(source).unwrap_err().to_string();
+            assert_eq!(err, format!("{line}: malformed line"), "{source:?}");
+        }
+    }
+
+    #[test]
+    fn unknown_statements_are_skipped() {
+        let source = format!("# comment\no quad\ns off\n{QUAD}usemtl none\nf 1 2 3\n");
+        let (vertices, indices) = parse_obj(&source).unwrap();
+
+        assert_eq!(vertices.len(), 3);
+        assert_eq!(indices.len(), 3);
+    }
+}
@@ -0,0 +1,381 @@
+use color_eyre::eyre::{bail, eyre, Result};
+use naga::{front::wgsl, Module, ScalarKind, TypeInner};
+
+use super::preprocessor::ShaderSource;
+
+/// Field of a WGSL struct annotated with a `// @param` comment.
+///
+/// The comment goes on the line of the field or the line above and may set
+/// `min`, `max`, `step` and `default`, like `// @param min=0 max=4 default=1`.
+/// Defaults of vectors are separated by commas, bounds apply to every component.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamInfo {
+    pub name: String,
+    /// Byte offset in the uniform buffer
+    pub offset: u32,
+    /// 1 for `f32`, 2 to 4 for `vecN<f32>`
+    pub components: usize,
+    pub min: f32,
+    pub max: f32,
+    pub step: f32,
+    pub default: [f32; 4],
+}
+
+impl ParamInfo {
+    pub fn clamp(&self, value: f32) -> f32 {
+        value.max(self.min).min(self.max)
+    }
+}
+
+/// Uniform buffer layout of the struct with annotated fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamLayout {
+    pub struct_name: String,
+    /// Annotated fields, other fields of the struct keep zero
+    pub params: Vec<ParamInfo>,
+    /// Size of the struct in bytes
+    pub size: u32,
+}
+
+/// Field annotated with a `// @param` comment.
+struct Annotation<'a> {
+    field: &'a str,
+    /// 0-based line in the expanded code
+    line: usize,
+    text: &'a str,
+}
+
+impl ParamLayout {
+    /// Parses the expanded code with naga and finds the struct with annotated
+    /// fields, see [`from_module`](Self::from_module).
+    pub fn parse(source: &ShaderSource) -> Result<Option<Self>> {
+        let module =
+            wgsl::parse_str(&source.code).map_err(|err| eyre!(err.emit_to_string(&source.code)))?;
+        Self::from_module(&module, source)
+    }
+
+    /// Layout of the struct of `module` with annotated fields, `None` if
+    /// there is none. Only one struct may be annotated.
+    ///
+    /// Annotations are comments, so they are read from `source`, the
+    /// expanded code `module` was parsed from. Offsets and the size come
+    /// from naga.
+    pub fn from_module(module: &Module, source: &ShaderSource) -> Result<Option<Self>> {
+        let location = |line: usize| match source.source_map.locate(line as u32 + 1) {
+            Some((path, line)) => format!("{}:{}", path.display(), line),
+            None => format!("<generated>:{}", line + 1),
+        };
+
+        let mut structs = annotations(&source.code).into_iter();
+        let (struct_name, annotations) = match structs.next() {
+            Some(annotated) => annotated,
+            None => return Ok(None),
+        };
+        if let Some((_, other)) = structs.next() {
+            bail!(
+                "{}: only one struct may have @param fields, `{struct_name}` already has",
+                location(other[0].line),
+            );
+        }
+        let (members, span) = module
+            .types
+            .iter()
+            .find_map(|(_, ty)| match ty.inner {
+                TypeInner::Struct { ref members, span }
+                    if ty.name.as_deref() == Some(struct_name) =>
+                {
+                    Some((members, span))
+                }
+                _ => None,
+            })
+            .ok_or_else(|| {
+                eyre!(
+                    "{}: no struct `{struct_name}`",
+                    location(annotations[0].line)
+                )
+            })?;
+
+        let mut params = vec![];
+        for annotation in annotations {
+            let member = members
+                .iter()
+                .find(|x| x.name.as_deref() == Some(annotation.field))
+                .ok_or_else(|| {
+                    eyre!(
+                        "{}: `{struct_name}` has no member `{}`",
+                        location(annotation.line),
+                        annotation.field
+                    )
+                })?;
+            let components = match module.types[member.ty].inner {
+                TypeInner::Scalar {
+                    kind: ScalarKind::Float,
+                    width: 4,
+                } => 1,
+                TypeInner::Vector {
+                    size,
+                    kind: ScalarKind::Float,
+                    width: 4,
+                } => size as usize,
+                _ => bail!(
+                    "{}: fields of @param structs must be `f32` or `vecN<f32>`, `{}` isn't",
+                    location(annotation.line),
+                    annotation.field
+                ),
+            };
+            let param =
+                parse_annotation(annotation.field, member.offset, components, annotation.text)
+                    .map_err(|err| eyre!("{}: {err}", location(annotation.line)))?;
+            params.push(param);
+        }
+
+        Ok(Some(Self {
+            struct_name: struct_name.to_string(),
+            params,
+            // Uniform buffers round structs up to 16 bytes
+            size: round_up(span, 16),
+        }))
+    }
+}
+
+/// Annotated fields by struct, structs without annotations are left out.
+///
+/// The comment goes on the line of the field or on a line of its own above it.
+fn annotations(code: &str) -> Vec<(&str, Vec<Annotation<'_>>)> {
+    let mut res = vec![];
+    let mut current: Option<(&str, Vec<Annotation>)> = None;
+    let mut pending = None;
+    for (i, line) in code.lines().enumerate() {
+        let line = line.trim();
+        let (_, fields) = match current {
+            Some(ref mut current) => current,
+            None => {
+                if let Some(rest) = line.strip_prefix("struct ") {
+                    current = Some((rest.trim_end_matches('{').trim(), vec![]));
+                    pending = None;
+                }
+                continue;
+            }
+        };
+
+        if line.starts_with('}') {
+            res.extend(current.take().filter(|(_, fields)| !fields.is_empty()));
+            continue;
+        }
+        let (code, comment) = line.split_once("//").unwrap_or((line, ""));
+        let annotation = comment.trim().strip_prefix("@param");
+        if code.trim().is_empty() {
+            if annotation.is_some() {
+                pending = annotation;
+            }
+            continue;
+        }
+        // Attributes like `@align(16)` come before the name
+        let field = match code.split_once(':') {
+            Some((name, _)) => name.split_whitespace().last().unwrap_or_default(),
+            None => continue,
+        };
+        if let Some(text) = annotation.or(pending.take()) {
+            fields.push(Annotation {
+                field,
+                line: i,
+                text,
+            });
+        }
+    }
+    res
+}
+
+fn parse_annotation(
+    name: &str,
+    offset: u32,
+    components: usize,
+    annotation: &str,
+) -> Result<ParamInfo> {
+    let mut min = f32::NEG_INFINITY;
+    let mut max = f32::INFINITY;
+    let mut step = None;
+    let mut default = None;
+    for token in annotation.split_whitespace() {
+        let (key, value) = token
+            .split_once('=')
+            .ok_or_else(|| eyre!("expected `key=value`, found `{token}`"))?;
+        let number = |x: &str| {
+            x.parse::<f32>()
+                .map_err(|_| eyre!("malformed number `{x}` in `{token}`"))
+        };
+        match key {
+            "min" => min = number(value)?,
+            "max" => max = number(value)?,
+            "step" => step = Some(number(value)?),
+            "default" => {
+                let values = value.split(',').map(number).collect::<Result<Vec<_>>>()?;
+                default = Some(splat(&values, components).ok_or_else(|| {
+                    eyre!(
+                        "expected 1 or {components} default values, found {}",
+                        values.len()
+                    )
+                })?);
+            }
+            _ => bail!("unknown key `{key}`, expected `min`, `max`, `step` or `default`"),
+        }
+    }
+    if min > max {
+        bail!("min {min} is greater than max {max}");
+    }
+
+    let step = step.unwrap_or(if min.is_finite() && max.is_finite() {
+        (max - min) / 100.
+    } else {
+        0.1
+    });
+    let mut param = ParamInfo {
+        name: name.to_string(),
+        offset,
+        components,
+        min,
+        max,
+        step,
+        default: [0.; 4],
+    };
+    let default = default.unwrap_or([0.; 4]);
+    for (value, default) in param.default.iter_mut().zip(default).take(components) {
+        *value = default.max(min).min(max);
+    }
+    Ok(param)
+}
+
+/// A single value repeated for every component, or one value per component.
+pub fn splat(values: &[f32], components: usize) -> Option<[f32; 4]> {
+    let mut res = [0.; 4];
+    match values.len() {
+        1 => res[..components].fill(values[0]),
+        len if len == components => res[..components].copy_from_slice(values),
+        _ => return None,
+    }
+    Some(res)
+}
+
+/// Parses `name = value` lines, `#` starts a comment. Values of
+/// vectors are separated by whitespace or commas.
+pub fn parse_config(text: &str) -> Result<Vec<(String, Vec<f32>)>> {
+    let mut res = vec![];
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let err = || eyre!("{}: expected `name = value`", line_number + 1);
+        let (name, value) = line.split_once('=').ok_or_else(err)?;
+        let values = value
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|x| !x.is_empty())
+            .map(|x| x.parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| err())?;
+        if values.is_empty() {
+            return Err(err());
+        }
+        res.push((name.trim().to_string(), values));
+    }
+    Ok(res)
+}
+
+fn round_up(x: u32, align: u32) -> u32 {
+    x.div_ceil(align) * align
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(code: &str) -> Result<Option<ParamLayout>> {
+        ParamLayout::parse(&ShaderSource {
+            code: code.to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn layout_comes_from_naga() {
+        let layout = parse(
+            "type float3 = vec3<f32>;
+            struct Params {
+                scale: f32, // @param min=0 max=4 default=1
+                unused: f32,
+                // @param min=0 max=1 default=0.1,0.2,0.3 step=0.05
+                @align(32) color: float3,
+                last: vec2<f32>, // @param default=2
+            };
+            @group(0) @binding(0)
+            var<uniform> params: Params;",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(layout.struct_name, "Params");
+        assert_eq!(layout.size, 64);
+
+        let [scale, color, last] = &layout.params[..] else {
+            panic!("expected 3 params, found {:?}", layout.params);
+        };
+        assert_eq!((scale.offset, scale.components), (0, 1));
+        assert_eq!(scale.default, [1., 0., 0., 0.]);
+        assert_eq!(scale.step, 0.04);
+        assert_eq!((color.offset, color.components), (32, 3));
+        assert_eq!(color.default, [0.1, 0.2, 0.3, 0.]);
+        assert_eq!(color.step, 0.05);
+        assert_eq!((last.offset, last.components), (48, 2));
+        assert_eq!(last.default, [2., 2., 0., 0.]);
+        assert_eq!(last.step, 0.1);
+    }
+
+    #[test]
+    fn structs_without_annotations_have_no_layout() {
+        assert_eq!(parse("struct A { x: f32, };").unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_invalid_annotations() {
+        let message = |code| parse(code).unwrap_err().to_string();
+        assert_eq!(
+            message("struct A {\n x: i32, // @param\n};"),
+            "<generated>:2: fields of @param structs must be `f32` or `vecN<f32>`, `x` isn't"
+        );
+        assert_eq!(
+            message("struct A {\n x: f32, // @param min=1 max=0\n};"),
+            "<generated>:2: min 1 is greater than max 0"
+        );
+        assert_eq!(
+            message("struct A {\n x: vec3<f32>, // @param default=1,2\n};"),
+            "<generated>:2: expected 1 or 3 default values, found 2"
+        );
+        assert_eq!(
+            message("struct A {\n x: f32, // @param\n};\nstruct B {\n y: f32, // @param\n};"),
+            "<generated>:5: only one struct may have @param fields, `A` already has"
+        );
+    }
+
+    #[test]
+    fn splat_repeats_single_values() {
+        assert_eq!(splat(&[1.], 3), Some([1., 1., 1., 0.]));
+        assert_eq!(splat(&[1., 2.], 2), Some([1., 2., 0., 0.]));
+        assert_eq!(splat(&[1., 2.], 3), None);
+        assert_eq!(splat(&[], 1), None);
+    }
+
+    #[test]
+    fn parses_config_lines() {
+        let config = parse_config("# comment\nscale = 2\n\ncolor = 0.1, 0.2 0.3 # rgb\n").unwrap();
+        assert_eq!(
+            config,
+            [
+                ("scale".to_string(), vec![2.]),
+                ("color".to_string(), vec![0.1, 0.2, 0.3])
+            ]
+        );
+        let message = |text| parse_config(text).unwrap_err().to_string();
+        assert_eq!(message("scale"), "1: expected `name = value`");
+        assert_eq!(message("a = 1\nscale ="), "2: expected `name = value`");
+        assert_eq!(message("scale = x"), "1: expected `name = value`");
+    }
+}
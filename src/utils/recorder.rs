@@ -13,7 +13,9 @@ use std::os::windows::process::CommandExt;
 
 use super::ImageDimentions;
 
-use crate::{utils::create_folder, SCREENSHOTS_FOLDER, VIDEO_FOLDER};
+use crate::{
+    context::GpuProfiler, utils::create_folder, PROFILES_FOLDER, SCREENSHOTS_FOLDER, VIDEO_FOLDER,
+};
 
 pub enum RecordEvent {
     Start(ImageDimentions),
@@ -215,3 +217,28 @@ pub fn save_screenshot(frame: Vec<u8>, image_dimentions: ImageDimentions) -> Res
     eprintln!("Encode image: {:#.2?}", now.elapsed());
     Ok(())
 }
+
+/// Writes the frames measured by `profiler` as a Chrome trace and prints the latest one.
+pub fn save_profile(profiler: &GpuProfiler) -> Result<()> {
+    if !profiler.is_supported() {
+        eprintln!("GPU profiling requires timestamp queries, which this adapter lacks");
+        return Ok(());
+    }
+    let last_frame = match profiler.last_frame() {
+        Some(frame) => frame,
+        None => {
+            eprintln!("No GPU timings yet");
+            return Ok(());
+        }
+    };
+    let profiles_folder = Path::new(PROFILES_FOLDER);
+    create_folder(profiles_folder)?;
+    let path = profiles_folder.join(format!(
+        "profile-{}.json",
+        chrono::Local::now().format("%d-%m-%Y-%H-%M-%S")
+    ));
+    profiler.save_chrome_trace(&path)?;
+    println!("GPU frame {}:\n{}", last_frame.index, last_frame);
+    println!("Saved GPU profile to {}", path.display());
+    Ok(())
+}
@@ -0,0 +1,48 @@
+pub use crate::shared::reflection::{Reflection, ShaderLayout, WgslStruct};
+
+impl ShaderLayout {
+    /// Takes the entries of the bind groups the pipeline is used with, by group index.
+    ///
+    /// Bind groups only fit pipeline layouts made of identical entries, so
+    /// entries of `groups` replace the reflected ones with the same binding
+    /// and are added where the shader doesn't use them. Reflected bindings
+    /// missing from `groups` are kept.
+    pub fn with_bind_groups(mut self, groups: &[wgpu::BindGroupLayoutDescriptor]) -> Self {
+        if self.groups.len() < groups.len() {
+            self.groups.resize_with(groups.len(), Vec::new);
+        }
+        for (entries, desc) in self.groups.iter_mut().zip(groups) {
+            entries.retain(|x| desc.entries.iter().all(|y| y.binding != x.binding));
+            entries.extend_from_slice(desc.entries);
+            entries.sort_by_key(|entry| entry.binding);
+        }
+        self
+    }
+
+    pub fn create_bind_group_layouts(&self, device: &wgpu::Device) -> Vec<wgpu::BindGroupLayout> {
+        self.groups
+            .iter()
+            .enumerate()
+            .map(|(i, entries)| {
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some(&format!("Reflected Bind Group Layout {}", i)),
+                    entries,
+                })
+            })
+            .collect()
+    }
+
+    pub fn create_pipeline_layout(
+        &self,
+        device: &wgpu::Device,
+        label: Option<&str>,
+    ) -> wgpu::PipelineLayout {
+        let bind_group_layouts = self.create_bind_group_layouts(device);
+        let bind_group_layouts: Vec<_> = bind_group_layouts.iter().collect();
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label,
+            bind_group_layouts: &bind_group_layouts,
+            push_constant_ranges: &[],
+        })
+    }
+}
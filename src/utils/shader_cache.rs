@@ -0,0 +1,311 @@
+use std::{
+    fs,
+    hash::Hasher,
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use super::reflection::Reflection;
+
+/// Compiled SPIR-V kept on disk between runs, along with the [`Reflection`]
+/// of the module it was written for.
+///
+/// Entries are named after a hash of everything that affects the output, so
+/// they never go stale and every compiler of the process, including the ones
+/// of the watcher threads, can share a directory. Entries are written to a
+/// temporary file and renamed into place, so readers never see partial writes.
+/// A checksum of the key and the entry follows it, entries that don't
+/// match it are ignored.
+///
+/// Cached modules are neither parsed nor validated again, so the directory
+/// must only be writable by the current user. It's created that way and
+/// refused otherwise.
+#[derive(Debug, Clone)]
+pub struct ShaderCache {
+    dir: PathBuf,
+}
+
+impl ShaderCache {
+    /// Environment variable with the cache directory, empty to disable the cache
+    pub const ENV_VAR: &'static str = "VOKSELIS_SHADER_CACHE";
+    /// Entries older than this are removed by [`prune`](Self::prune)
+    const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+    const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        create_private_dir(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Cache in the directory from [`ENV_VAR`](Self::ENV_VAR), or in
+    /// `vokselis` of the user's cache directory if it's not set.
+    pub fn from_env() -> Option<Self> {
+        let dir = match std::env::var_os(Self::ENV_VAR) {
+            Some(dir) if dir.is_empty() => return None,
+            Some(dir) => PathBuf::from(dir),
+            None => user_cache_dir()?.join("vokselis"),
+        };
+        match Self::new(&dir) {
+            Ok(cache) => Some(cache),
+            Err(err) => {
+                eprintln!("Shader cache in {} disabled: {err}", dir.display());
+                None
+            }
+        }
+    }
+
+    /// SPIR-V and reflection of the module stored under `key`.
+    pub fn get(&self, key: u64) -> Option<(Vec<u32>, Reflection)> {
+        let bytes = fs::read(self.entry(key)).ok()?;
+        if bytes.len() % 4 != 0 || bytes.len() < 8 {
+            return None;
+        }
+        let (bytes, checksum) = bytes.split_at(bytes.len() - 8);
+        // Anything else is a corrupted entry, it's overwritten after compiling
+        if checksum != Self::checksum(key, bytes).to_le_bytes() {
+            return None;
+        }
+        let entry: Vec<u32> = bytes
+            .chunks_exact(4)
+            .map(|x| u32::from_ne_bytes([x[0], x[1], x[2], x[3]]))
+            .collect();
+        // The SPIR-V length first, entries of older versions don't fit it
+        let (&len, entry) = entry.split_first()?;
+        if entry.len() < len as usize {
+            return None;
+        }
+        let (words, reflection) = entry.split_at(len as usize);
+        if words.first() != Some(&Self::SPIRV_MAGIC) {
+            return None;
+        }
+        Some((words.to_vec(), Reflection::decode(reflection)?))
+    }
+
+    pub fn insert(&self, key: u64, words: &[u32], reflection: &Reflection) -> io::Result<()> {
+        // Several threads may compile the same shader at once
+        let temp = self.dir.join(format!(
+            "{key:016x}.{}-{:?}.tmp",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        let mut entry = Vec::with_capacity(words.len() + 1);
+        entry.push(words.len() as u32);
+        entry.extend_from_slice(words);
+        reflection.encode(&mut entry);
+        let bytes: &[u8] = bytemuck::cast_slice(&entry);
+        let checksum = Self::checksum(key, bytes).to_le_bytes();
+        fs::write(&temp, [bytes, &checksum].concat())?;
+        let res = fs::rename(&temp, self.entry(key));
+        if res.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        res
+    }
+
+    fn checksum(key: u64, bytes: &[u8]) -> u64 {
+        let mut hasher = StableHasher::default();
+        hasher.write(&key.to_le_bytes());
+        hasher.write(bytes);
+        hasher.finish()
+    }
+
+    fn entry(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{key:016x}.spv"))
+    }
+
+    /// Removes expired entries, once per process is enough.
+    pub fn prune(&self) {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        let now = SystemTime::now();
+        for entry in entries.flatten() {
+            // The directory may be shared with other files
+            let path = entry.path();
+            let own = matches!(
+                path.extension().and_then(|x| x.to_str()),
+                Some("spv" | "tmp")
+            );
+            let expired = own
+                && entry
+                    .metadata()
+                    .and_then(|x| x.modified())
+                    .ok()
+                    .and_then(|x| now.duration_since(x).ok())
+                    .filter(|x| *x > Self::MAX_AGE)
+                    .is_some();
+            if expired {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+/// `$XDG_CACHE_HOME`, `~/.cache` or `%LOCALAPPDATA%`, whichever is set first.
+fn user_cache_dir() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|x| !x.is_empty());
+    var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| var("HOME").map(|home| Path::new(&home).join(".cache")))
+        .or_else(|| var("LOCALAPPDATA").map(PathBuf::from))
+}
+
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    let mode = fs::metadata(dir)?.permissions().mode() & 0o777;
+    if mode & 0o022 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("writable by other users (mode {mode:o})"),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)
+}
+
+/// FNV-1a, unlike `DefaultHasher` it's guaranteed to give the same
+/// hashes in every run and with every version of Rust.
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{num::NonZeroU32, os::unix::fs::PermissionsExt};
+
+    use super::*;
+    use crate::shared::reflection::{ShaderLayout, WgslMember, WgslStruct};
+
+    fn cache_dir(test: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("vokselis-cache-{}-{test}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn cache_dir_is_private() {
+        let dir = cache_dir("private").join("nested");
+        let cache = ShaderCache::new(&dir).unwrap();
+        let mode = fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        let words = [ShaderCache::SPIRV_MAGIC, 2];
+        cache.insert(1, &words, &Reflection::default()).unwrap();
+        assert_eq!(cache.get(1), Some((words.to_vec(), Reflection::default())));
+        assert_eq!(cache.get(2), None);
+    }
+
+    #[test]
+    fn corrupted_entries_are_ignored() {
+        let dir = cache_dir("corrupted");
+        let cache = ShaderCache::new(&dir).unwrap();
+        let words = [ShaderCache::SPIRV_MAGIC, 2, 3];
+        cache.insert(1, &words, &Reflection::default()).unwrap();
+
+        let entry = cache.entry(1);
+        let mut bytes = fs::read(&entry).unwrap();
+        bytes[4] ^= 1;
+        fs::write(&entry, &bytes).unwrap();
+        assert_eq!(cache.get(1), None);
+
+        // Entries are bound to their key
+        cache.insert(2, &words, &Reflection::default()).unwrap();
+        fs::rename(cache.entry(2), &entry).unwrap();
+        assert_eq!(cache.get(1), None);
+    }
+
+    #[test]
+    fn entries_keep_the_reflection() {
+        let cache = ShaderCache::new(cache_dir("reflection")).unwrap();
+        let entry = wgpu::BindGroupLayoutEntry {
+            binding: 3,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::ReadWrite,
+                format: wgpu::TextureFormat::R32Float,
+                view_dimension: wgpu::TextureViewDimension::D3,
+            },
+            count: NonZeroU32::new(4),
+        };
+        let reflection = Reflection {
+            layout: ShaderLayout {
+                groups: vec![vec![], vec![entry]],
+            },
+            structs: vec![WgslStruct {
+                name: String::from("Camera"),
+                span: 16,
+                members: vec![WgslMember {
+                    name: String::from("view_pos"),
+                    offset: 0,
+                    size: 12,
+                }],
+            }],
+        };
+        let words = [ShaderCache::SPIRV_MAGIC, 2, 3];
+        cache.insert(7, &words, &reflection).unwrap();
+        assert_eq!(cache.get(7), Some((words.to_vec(), reflection)));
+    }
+
+    #[test]
+    fn entries_without_reflection_are_ignored() {
+        let cache = ShaderCache::new(cache_dir("old")).unwrap();
+        // SPIR-V and the checksum only, like entries of earlier versions
+        let bytes: &[u8] = bytemuck::cast_slice(&[ShaderCache::SPIRV_MAGIC, 2, 3]);
+        let checksum = ShaderCache::checksum(1, bytes).to_le_bytes();
+        fs::write(cache.entry(1), [bytes, &checksum].concat()).unwrap();
+        assert_eq!(cache.get(1), None);
+    }
+
+    #[test]
+    fn prune_keeps_fresh_entries_and_other_files() {
+        let dir = cache_dir("prune");
+        let cache = ShaderCache::new(&dir).unwrap();
+        cache
+            .insert(1, &[ShaderCache::SPIRV_MAGIC], &Reflection::default())
+            .unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        cache.prune();
+        assert!(cache.get(1).is_some());
+        assert!(dir.join("notes.txt").exists());
+    }
+
+    #[test]
+    fn shared_dirs_are_refused() {
+        let dir = cache_dir("shared");
+        fs::create_dir_all(&dir).unwrap();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o777)).unwrap();
+        let err = ShaderCache::new(&dir).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+}
@@ -1,16 +1,77 @@
-use std::path::Path;
+use std::{
+    collections::HashMap,
+    hash::Hasher,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
 use color_eyre::Result;
 use naga::{
-    back::spv::{self, BindingMap},
+    back::spv,
     front::wgsl,
-    valid::{Capabilities, ValidationError, ValidationFlags, Validator},
+    valid::{Capabilities, ModuleInfo, ValidationError, ValidationFlags, Validator},
+    Module,
+};
+
+use super::{
+    diagnostic::Diagnostic,
+    embedded,
+    fallback::FallbackShader,
+    preprocessor::{PreprocessError, Preprocessor, ShaderDefines, ShaderSource},
+    reflection::{Reflection, ShaderLayout, WgslStruct},
+    shader_cache::{ShaderCache, StableHasher},
+    shader_roots::ShaderRoots,
+    spirv_options::get_options,
+    struct_layout::{LayoutMismatch, ShaderStruct, StructRegistry},
 };
+use crate::watcher::ShaderKey;
+
+/// Modification stamp of a file, used to validate cached modules
+type FileStamp = Option<(SystemTime, u64)>;
+
+fn file_stamp(path: &Path) -> FileStamp {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Versions of naga in the lock file, set by the build script
+const NAGA_VERSION: &str = env!("VOKSELIS_NAGA_VERSION");
+
+struct CachedModule {
+    words: Vec<u32>,
+    layout: ShaderLayout,
+    /// Files on disk the module was built from, embedded ones never change
+    files: Vec<(PathBuf, FileStamp)>,
+    /// Generation of the struct layouts the module was checked against
+    structs: u64,
+}
+
+impl CachedModule {
+    fn is_fresh(&self, structs: &StructRegistry) -> bool {
+        self.structs == structs.generation()
+            && self
+                .files
+                .iter()
+                .all(|(path, stamp)| stamp.is_some() && &file_stamp(path) == stamp)
+    }
+}
 
 pub struct ShaderCompiler {
+    roots: ShaderRoots,
+    preprocessor: Preprocessor,
+    dependencies: Vec<PathBuf>,
+    cache: HashMap<(PathBuf, ShaderDefines), CachedModule>,
+    structs: StructRegistry,
+    /// Shaders replaced by fallbacks since the last [`take_errors`](Self::take_errors)
+    errors: Vec<(ShaderKey, CompilerError)>,
+    disk_cache: Option<ShaderCache>,
+    /// Hash of the compiler settings, part of every disk cache key
+    disk_cache_salt: u64,
     parser: wgsl::Parser,
     validator: Validator,
     writer: spv::Writer,
+    /// Modules parsed and validated so far, cached ones skip both
+    validations: usize,
 }
 
 impl ShaderCompiler {
@@ -18,102 +79,487 @@ impl ShaderCompiler {
         Self::default()
     }
 
-    pub fn create_shader_module(&mut self, path: &Path) -> Result<Vec<u32>, CompilerError> {
-        let source = std::fs::read_to_string(&path)?;
-        let module = self
-            .parser
-            .parse(&source)
-            .map_err(|error| CompilerError::Compile { error, source })?;
-        let module_info = self.validator.validate(&module)?;
-        let mut words = vec![];
-        self.writer.write(&module, &module_info, None, &mut words)?;
-        Ok(words)
+    /// Compiler resolving shader paths against `roots`.
+    ///
+    /// Compiled shaders are kept in the [`ShaderCache`] configured by the environment.
+    pub fn with_roots(roots: ShaderRoots) -> Self {
+        Self::with_structs(roots, StructRegistry::default())
     }
-}
 
-impl Default for ShaderCompiler {
-    fn default() -> Self {
+    /// Compiler checking shaders against the layouts in `structs`, which
+    /// may be shared with other compilers.
+    pub fn with_structs(roots: ShaderRoots, structs: StructRegistry) -> Self {
         let parser = wgsl::Parser::new();
-        let validator = Validator::new(ValidationFlags::all(), Capabilities::all());
+        let flags = ValidationFlags::all();
+        let capabilities = Capabilities::all();
+        let validator = Validator::new(flags, capabilities);
         let options = get_options();
         let writer = spv::Writer::new(&options).unwrap();
+
+        let mut salt = StableHasher::default();
+        salt.write(NAGA_VERSION.as_bytes());
+        salt.write(options_fingerprint(&options).as_bytes());
+        salt.write(&[flags.bits()]);
+        salt.write(&capabilities.bits().to_le_bytes());
+
         Self {
+            preprocessor: Preprocessor::new(roots.clone()),
+            roots,
+            dependencies: vec![],
+            cache: HashMap::new(),
+            structs,
+            errors: vec![],
+            disk_cache: ShaderCache::from_env(),
+            disk_cache_salt: salt.finish(),
             parser,
             validator,
             writer,
+            validations: 0,
+        }
+    }
+
+    pub fn roots(&self) -> &ShaderRoots {
+        &self.roots
+    }
+
+    pub fn create_shader_module(&mut self, path: &Path) -> Result<Vec<u32>, CompilerError> {
+        self.create_shader_module_with_defines(path, &ShaderDefines::default())
+    }
+
+    /// Compiles the variant of the shader at `path` selected by `defines`.
+    ///
+    /// `path` is resolved against the [shader roots](ShaderRoots). Variants
+    /// are cached until one of the files they were built from changes.
+    ///
+    /// Modules bundled at build time or kept in the [`ShaderCache`] are
+    /// neither parsed nor validated again, only checked against the
+    /// registered struct layouts.
+    pub fn create_shader_module_with_defines(
+        &mut self,
+        path: &Path,
+        defines: &ShaderDefines,
+    ) -> Result<Vec<u32>, CompilerError> {
+        Ok(self.compile(path, defines)?.words.clone())
+    }
+
+    /// Checks every following shader that declares a struct named like
+    /// `T`'s WGSL counterpart against the layout of `T`.
+    ///
+    /// The layout reaches every compiler sharing the [`StructRegistry`],
+    /// including the ones compiling hot reloads.
+    pub fn register_struct<T: ShaderStruct>(&mut self) {
+        self.structs.register::<T>();
+    }
+
+    pub fn structs(&self) -> &StructRegistry {
+        &self.structs
+    }
+
+    /// Compiles the variant of the shader at `path` along with the bind group
+    /// layouts it uses.
+    pub fn create_reflected_module(
+        &mut self,
+        path: &Path,
+        defines: &ShaderDefines,
+    ) -> Result<(Vec<u32>, ShaderLayout), CompilerError> {
+        let words = self.create_shader_module_with_defines(path, defines)?;
+        let layout = self.reflect(path, defines)?;
+        Ok((words, layout))
+    }
+
+    /// Builds a pipeline with the variant of the shader at `path`, or with
+    /// `fallback` if it doesn't compile.
+    ///
+    /// The error is printed and kept until [`take_errors`](Self::take_errors).
+    /// Either way the pipeline can be registered with the [`Watcher`](crate::Watcher)
+    /// to pick up fixes of the shader.
+    pub fn pipeline_or_fallback<T>(
+        &mut self,
+        device: &wgpu::Device,
+        path: &Path,
+        defines: &ShaderDefines,
+        fallback: &FallbackShader,
+        build: impl FnOnce(&ShaderLayout, &wgpu::ShaderModule) -> T,
+    ) -> T {
+        match self.create_reflected_module(path, defines) {
+            Ok((words, layout)) => build(&layout, &create_module(device, path.to_str(), &words)),
+            Err(err) => {
+                eprintln!("{err}");
+                let key = ShaderKey::new(self.roots(), path, defines);
+                self.errors.push((key, err));
+                build(
+                    &ShaderLayout::default(),
+                    &fallback.create_module(device, self),
+                )
+            }
+        }
+    }
+
+    /// Errors of the shaders replaced by fallbacks so far.
+    pub fn take_errors(&mut self) -> Vec<(ShaderKey, CompilerError)> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Bind group layouts used by the variant of the shader at `path`.
+    pub fn reflect(
+        &mut self,
+        path: &Path,
+        defines: &ShaderDefines,
+    ) -> Result<ShaderLayout, CompilerError> {
+        Ok(self.compile(path, defines)?.layout.clone())
+    }
+
+    pub fn disk_cache(&self) -> Option<&ShaderCache> {
+        self.disk_cache.as_ref()
+    }
+
+    /// Replaces the disk cache, `None` disables it.
+    pub fn set_disk_cache(&mut self, disk_cache: Option<ShaderCache>) {
+        self.disk_cache = disk_cache;
+    }
+
+    fn compile(
+        &mut self,
+        path: &Path,
+        defines: &ShaderDefines,
+    ) -> Result<&CachedModule, CompilerError> {
+        let key = (path.to_path_buf(), defines.clone());
+        let cached = self.cache.get(&key).filter(|x| x.is_fresh(&self.structs));
+        if cached.is_some() {
+            let cached = &self.cache[&key];
+            self.dependencies = cached.files.iter().map(|(x, _)| x.clone()).collect();
+            return Ok(cached);
+        }
+
+        let (resolved, source) = self.preprocess(path, defines)?;
+        let structs = self.structs.generation();
+        let files: Vec<_> = self
+            .dependencies
+            .iter()
+            .filter(|x| !ShaderRoots::is_embedded(x))
+            .map(|x| (x.clone(), file_stamp(x)))
+            .collect();
+
+        // The bundle was compiled from the embedded sources, files on disk may
+        // differ from them, the shader itself as well as its includes. It was
+        // validated by the build script, release builds trust it.
+        let bundled = match resolved.strip_prefix(ShaderRoots::EMBEDDED) {
+            Ok(name) if files.is_empty() => embedded::compiled(name, defines),
+            _ => None,
+        };
+        let disk_key = self.disk_cache_key(&source.code);
+        let stored = match bundled {
+            Some((words, reflection)) => Some((words.to_vec(), reflection)),
+            None => self.disk_cache.as_ref().and_then(|x| x.get(disk_key)),
+        };
+        let (words, reflection) = match stored {
+            Some(stored) => stored,
+            None => {
+                let (module, module_info) = self.parse(source)?;
+                let reflection = Reflection::from_module(&module, &module_info);
+                let words = self.write_spirv(&module, &module_info)?;
+                if let Some(ref disk_cache) = self.disk_cache {
+                    if let Err(err) = disk_cache.insert(disk_key, &words, &reflection) {
+                        eprintln!("Failed to cache {}: {err}", path.display());
+                    }
+                }
+                (words, reflection)
+            }
+        };
+        // Layouts may have been registered since the module was compiled
+        self.check_structs(&resolved, &reflection.structs)?;
+        let layout = reflection.layout;
+        let cached = CachedModule {
+            words,
+            layout,
+            files,
+            structs,
+        };
+        self.cache.insert(key.clone(), cached);
+        Ok(&self.cache[&key])
+    }
+
+    /// Parses and validates the variant of the shader at `path` without
+    /// writing SPIR-V, for tools that translate it to other languages.
+    pub fn validate(
+        &mut self,
+        path: &Path,
+        defines: &ShaderDefines,
+    ) -> Result<(Module, ModuleInfo), CompilerError> {
+        let (resolved, source) = self.preprocess(path, defines)?;
+        self.validate_source(&resolved, source)
+    }
+
+    /// Resolves and expands the shader at `path`, returns the path it was found at.
+    fn preprocess(
+        &mut self,
+        path: &Path,
+        defines: &ShaderDefines,
+    ) -> Result<(PathBuf, ShaderSource), CompilerError> {
+        let resolved = self.roots().resolve(path).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("shader {} not found", path.display()),
+            )
+        })?;
+        let source = ShaderRoots::read(&resolved)?;
+        let source = self.preprocessor.process(&resolved, &source, defines)?;
+        self.dependencies = source.source_map.files().to_vec();
+        Ok((resolved, source))
+    }
+
+    /// Compiles shader code that doesn't come from a file.
+    ///
+    /// `path` names the code in errors, includes are still resolved
+    /// against the shader roots. The result is not cached.
+    pub fn create_shader_module_from_source(
+        &mut self,
+        path: &Path,
+        source: &str,
+    ) -> Result<Vec<u32>, CompilerError> {
+        let source = self
+            .preprocessor
+            .process(path, source, &ShaderDefines::default())?;
+        Ok(self.build(path, source)?.0)
+    }
+
+    fn build(
+        &mut self,
+        path: &Path,
+        source: ShaderSource,
+    ) -> Result<(Vec<u32>, ShaderLayout), CompilerError> {
+        let (module, module_info) = self.validate_source(path, source)?;
+        let words = self.write_spirv(&module, &module_info)?;
+        let layout = ShaderLayout::from_module(&module, &module_info);
+        Ok((words, layout))
+    }
+
+    /// SPIR-V of a module returned by [`validate`](Self::validate).
+    pub fn write_spirv(
+        &mut self,
+        module: &Module,
+        module_info: &ModuleInfo,
+    ) -> Result<Vec<u32>, CompilerError> {
+        let mut words = vec![];
+        self.writer.write(module, module_info, None, &mut words)?;
+        Ok(words)
+    }
+
+    fn validate_source(
+        &mut self,
+        path: &Path,
+        source: ShaderSource,
+    ) -> Result<(Module, ModuleInfo), CompilerError> {
+        let (module, module_info) = self.parse(source)?;
+        self.check_structs(path, &WgslStruct::declared_in(&module))?;
+        Ok((module, module_info))
+    }
+
+    fn parse(&mut self, source: ShaderSource) -> Result<(Module, ModuleInfo), CompilerError> {
+        self.validations += 1;
+        let module = self
+            .parser
+            .parse(&source.code)
+            .map_err(|error| CompilerError::Compile {
+                error,
+                source: Box::new(source.clone()),
+            })?;
+        let module_info =
+            self.validator
+                .validate(&module)
+                .map_err(|error| CompilerError::Validate {
+                    error,
+                    source: Box::new(source),
+                })?;
+        Ok((module, module_info))
+    }
+
+    fn check_structs(&self, path: &Path, structs: &[WgslStruct]) -> Result<(), CompilerError> {
+        let errors = self.structs.check(structs);
+        if !errors.is_empty() {
+            return Err(CompilerError::Layout {
+                path: path.to_path_buf(),
+                errors,
+            });
         }
+        Ok(())
+    }
+
+    /// Identifies the output of compiling `code` with the current settings.
+    ///
+    /// Registered struct layouts are not part of the key, they're checked
+    /// against the structs stored with the module on every hit.
+    fn disk_cache_key(&self, code: &str) -> u64 {
+        let mut hasher = StableHasher::default();
+        hasher.write(&self.disk_cache_salt.to_le_bytes());
+        hasher.write(code.as_bytes());
+        hasher.finish()
+    }
+
+    /// Files read by the last successfully preprocessed shader, the shader itself first.
+    pub fn dependencies(&self) -> &[PathBuf] {
+        &self.dependencies
     }
 }
 
-// https://github.com/gfx-rs/wgpu/blob/master/wgpu-hal/src/vulkan/adapter.rs#L1166
-fn get_options() -> spv::Options {
-    let capabilities = vec![
-        spv::Capability::Shader,
-        spv::Capability::Matrix,
-        spv::Capability::Sampled1D,
-        spv::Capability::Image1D,
-        spv::Capability::ImageQuery,
-        spv::Capability::DerivativeControl,
-        spv::Capability::SampledCubeArray,
-        spv::Capability::SampleRateShading,
-        //Note: this is requested always, no matter what the actual
-        // adapter supports. It's not the responsibility of SPV-out
-        // translation to handle the storage support for formats.
-        spv::Capability::StorageImageExtendedFormats,
-        spv::Capability::MultiView,
-        //TODO: fill out the rest
-    ];
-
-    let mut flags = spv::WriterFlags::empty();
-    flags.set(
-        spv::WriterFlags::DEBUG,
-        true,
-        // self.instance.flags.contains(crate::InstanceFlags::DEBUG),
-    );
-    flags.set(
-        spv::WriterFlags::LABEL_VARYINGS,
-        true, // self.phd_capabilities.properties.vendor_id != crate::auxil::db::qualcomm::VENDOR,
-    );
-    flags.set(
-        spv::WriterFlags::FORCE_POINT_SIZE,
-        //Note: we could technically disable this when we are compiling separate entry points,
-        // and we know exactly that the primitive topology is not `PointList`.
-        // But this requires cloning the `spv::Options` struct, which has heap allocations.
-        true, // could check `super::Workarounds::SEPARATE_ENTRY_POINTS`
-    );
-    spv::Options {
-        binding_map: BindingMap::new(),
-        lang_version: (1, 0),
-        flags,
-        capabilities: Some(capabilities.into_iter().collect()),
-        bounds_check_policies: naga::proc::BoundsCheckPolicies {
-            index: naga::proc::BoundsCheckPolicy::Unchecked,
-            buffer: naga::proc::BoundsCheckPolicy::Unchecked,
-            image: naga::proc::BoundsCheckPolicy::Unchecked,
-            binding_array: naga::proc::BoundsCheckPolicy::Unchecked,
-        },
+impl Default for ShaderCompiler {
+    fn default() -> Self {
+        Self::with_roots(ShaderRoots::default())
     }
 }
 
+/// How SPIR-V from the [`ShaderCompiler`] reaches the driver.
+///
+/// wgpu only accepts the WGSL syntax of the naga version it ships with, so
+/// backends without SPIR-V passthrough get their shaders in their own
+/// language through wgpu's SPIR-V frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderBackend {
+    /// Handed to the Vulkan driver as is
+    Passthrough,
+    /// Translated by wgpu to GLSL, HLSL or MSL depending on the adapter's backend
+    Translated,
+}
+
+impl ShaderBackend {
+    pub fn for_device(device: &wgpu::Device) -> Self {
+        if device
+            .features()
+            .contains(wgpu::Features::SPIRV_SHADER_PASSTHROUGH)
+        {
+            Self::Passthrough
+        } else {
+            Self::Translated
+        }
+    }
+
+    /// Only takes SPIR-V from a [`ShaderCompiler`], passthrough hands it to
+    /// the driver unchecked.
+    pub(crate) fn create_module(
+        self,
+        device: &wgpu::Device,
+        label: Option<&str>,
+        words: &[u32],
+    ) -> wgpu::ShaderModule {
+        match self {
+            // SAFETY: the words were written by naga for a module it validated,
+            // in this process, by the build script from the same embedded
+            // sources, or by an earlier run to the private disk cache, whose
+            // entries are checksummed. Nothing outside the crate reaches here.
+            Self::Passthrough => unsafe {
+                device.create_shader_module_spirv(&wgpu::ShaderModuleDescriptorSpirV {
+                    label,
+                    source: words.into(),
+                })
+            },
+            Self::Translated => device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label,
+                source: wgpu::ShaderSource::SpirV(words.into()),
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for ShaderBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Passthrough => write!(f, "SPIR-V passthrough"),
+            Self::Translated => write!(f, "SPIR-V translated by wgpu"),
+        }
+    }
+}
+
+/// Creates a module from SPIR-V of a [`ShaderCompiler`] with the device's [`ShaderBackend`].
+pub(crate) fn create_module(
+    device: &wgpu::Device,
+    label: Option<&str>,
+    words: &[u32],
+) -> wgpu::ShaderModule {
+    ShaderBackend::for_device(device).create_module(device, label, words)
+}
+
+/// Writer options in a form that doesn't depend on hash set ordering.
+fn options_fingerprint(options: &spv::Options) -> String {
+    let mut capabilities: Vec<_> = options
+        .capabilities
+        .iter()
+        .flatten()
+        .map(|x| format!("{:?}", x))
+        .collect();
+    capabilities.sort();
+    format!(
+        "{:?} {:?} {:?} {:?}",
+        options.lang_version, options.flags, options.bounds_check_policies, capabilities
+    )
+}
+
 pub enum CompilerError {
     Read(std::io::Error),
+    Preprocess(PreprocessError),
     Compile {
         error: wgsl::ParseError,
-        source: String,
+        source: Box<ShaderSource>,
+    },
+    Validate {
+        error: naga::WithSpan<ValidationError>,
+        source: Box<ShaderSource>,
+    },
+    Layout {
+        path: PathBuf,
+        errors: Vec<LayoutMismatch>,
     },
-    Validate(naga::WithSpan<ValidationError>),
     WriteSpirv(spv::Error),
 }
 
+impl CompilerError {
+    /// Parse and validation errors with locations in the original files.
+    pub fn diagnostic(&self) -> Option<Diagnostic> {
+        match self {
+            Self::Compile { error, source } => Some(Diagnostic::new(
+                error.message(),
+                error.labels(),
+                vec![],
+                source,
+            )),
+            Self::Validate { error, source } => {
+                let mut notes = vec![];
+                let mut inner = std::error::Error::source(error.as_inner());
+                while let Some(err) = inner {
+                    notes.push(err.to_string());
+                    inner = err.source();
+                }
+                let labels = error
+                    .spans()
+                    .filter_map(|(span, label)| Some((span.to_range()?, label.as_str())));
+                Some(Diagnostic::new(
+                    error.as_inner().to_string(),
+                    labels,
+                    notes,
+                    source,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Writes the error to stderr, diagnostics are colored unless `NO_COLOR` is set.
+    pub fn emit_to_stderr(&self) {
+        match self.diagnostic() {
+            Some(diagnostic) => diagnostic.emit_to_stderr(),
+            None => eprintln!("{self}"),
+        }
+    }
+}
+
 impl From<std::io::Error> for CompilerError {
     fn from(e: std::io::Error) -> Self {
         Self::Read(e)
     }
 }
 
-impl From<naga::WithSpan<ValidationError>> for CompilerError {
-    fn from(e: naga::WithSpan<ValidationError>) -> Self {
-        Self::Validate(e)
+impl From<PreprocessError> for CompilerError {
+    fn from(e: PreprocessError) -> Self {
+        Self::Preprocess(e)
     }
 }
 
@@ -127,12 +573,18 @@ impl std::fmt::Display for CompilerError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Read(err) => write!(f, "{}", err),
-            Self::WriteSpirv(err) => write!(f, "{}", err),
-            Self::Validate(err) => write!(f, "{}", err),
-            Self::Compile { error, source } => {
-                error.emit_to_stderr(source);
+            Self::Preprocess(err) => write!(f, "{}", err),
+            Self::Layout { path, errors } => {
+                for error in errors {
+                    writeln!(f, "{}: {}", path.display(), error)?;
+                }
                 Ok(())
             }
+            Self::WriteSpirv(err) => write!(f, "{}", err),
+            Self::Compile { .. } | Self::Validate { .. } => {
+                let diagnostic = self.diagnostic().map(|x| x.emit_to_string());
+                write!(f, "{}", diagnostic.unwrap_or_default())
+            }
         }
     }
 }
@@ -141,9 +593,18 @@ impl std::fmt::Debug for CompilerError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Read(err) => write!(f, "{}", err),
+            Self::Preprocess(err) => write!(f, "{}", err),
+            Self::Layout { path, errors } => {
+                for error in errors {
+                    writeln!(f, "{}: {}", path.display(), error)?;
+                }
+                Ok(())
+            }
             Self::WriteSpirv(err) => write!(f, "{}", err),
-            Self::Validate(err) => write!(f, "{}", err),
-            Self::Compile { error, source } => write!(f, "{}", error.emit_to_string(source)),
+            Self::Compile { .. } | Self::Validate { .. } => {
+                let diagnostic = self.diagnostic().map(|x| x.emit_to_string());
+                write!(f, "{}", diagnostic.unwrap_or_default())
+            }
         }
     }
 }
@@ -152,9 +613,96 @@ impl std::error::Error for CompilerError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self {
             Self::Read(ref e) => Some(e),
+            Self::Preprocess(ref e) => Some(e),
+            Self::Layout { ref errors, .. } => errors
+                .first()
+                .map(|e| e as &(dyn std::error::Error + 'static)),
             Self::Compile { error: ref e, .. } => Some(e),
-            Self::Validate(ref e) => Some(e),
+            Self::Validate { error: ref e, .. } => Some(e),
             Self::WriteSpirv(ref e) => Some(e),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    const SHADER: &str = "
+        struct Params {
+            scale: f32,
+        };
+
+        @group(0) @binding(0)
+        var<uniform> params: Params;
+
+        @compute @workgroup_size(1)
+        fn main() {
+            let scale = params.scale;
+        }
+    ";
+
+    #[repr(C)]
+    struct Params {
+        scale: f32,
+        offset: f32,
+    }
+
+    crate::shader_struct!(Params as "Params" { scale, offset });
+
+    /// Directory with `test.wgsl` in `shaders` and an empty cache in `cache`.
+    fn test_dir(test: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("vokselis-compiler-{}-{test}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("shaders")).unwrap();
+        fs::write(dir.join("shaders/test.wgsl"), SHADER).unwrap();
+        dir
+    }
+
+    fn compiler(dir: &Path) -> ShaderCompiler {
+        let mut compiler = ShaderCompiler::with_roots(ShaderRoots::new([dir.join("shaders")]));
+        compiler.set_disk_cache(Some(ShaderCache::new(dir.join("cache")).unwrap()));
+        compiler
+    }
+
+    #[test]
+    fn cached_modules_are_not_validated_again() {
+        let dir = test_dir("cached");
+        let path = Path::new("test.wgsl");
+        let defines = ShaderDefines::default();
+
+        let mut first = compiler(&dir);
+        let compiled = first.create_reflected_module(path, &defines).unwrap();
+        assert_eq!(first.validations, 1);
+        assert_eq!(compiled.1.groups[0].len(), 1);
+        first.create_reflected_module(path, &defines).unwrap();
+        assert_eq!(first.validations, 1);
+
+        // Another compiler finds the module in the disk cache
+        let mut second = compiler(&dir);
+        let cached = second.create_reflected_module(path, &defines).unwrap();
+        assert_eq!(second.validations, 0);
+        assert_eq!(cached, compiled);
+    }
+
+    #[test]
+    fn cached_modules_are_checked_against_new_struct_layouts() {
+        let dir = test_dir("structs");
+        let path = Path::new("test.wgsl");
+        compiler(&dir).create_shader_module(path).unwrap();
+
+        let mut second = compiler(&dir);
+        second.register_struct::<Params>();
+        let err = second.create_shader_module(path).unwrap_err();
+        assert_eq!(second.validations, 0);
+        match err {
+            CompilerError::Layout { errors, .. } => {
+                assert_eq!(errors[0].errors, ["`offset` has no WGSL member `offset`"]);
+            }
+            err => panic!("unexpected error: {err}"),
+        }
+    }
+}
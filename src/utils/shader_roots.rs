@@ -0,0 +1,238 @@
+use std::{
+    borrow::Cow,
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+};
+
+use super::{embedded, preprocessor::ShaderFiles};
+
+/// Directories shader paths are resolved against, in order of priority.
+///
+/// Paths that aren't found in any root are looked up in the shaders
+/// embedded in the binary, so an executable runs without the source tree.
+/// Embedded shaders resolve to paths under [`ShaderRoots::EMBEDDED`] and
+/// are never reloaded.
+#[derive(Debug, Clone)]
+pub struct ShaderRoots {
+    roots: Vec<PathBuf>,
+}
+
+impl ShaderRoots {
+    /// Environment variable with additional roots, separated like `PATH`
+    pub const ENV_VAR: &'static str = "VOKSELIS_SHADER_PATH";
+    /// Command line flag adding a root, may be repeated
+    pub const CLI_FLAG: &'static str = "--shaders";
+    /// Prefix of the paths of embedded shaders
+    pub const EMBEDDED: &'static str = "<embedded>";
+
+    pub fn new(roots: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        Self {
+            roots: roots.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// `cli` roots, then the ones from [`ENV_VAR`](Self::ENV_VAR), then the
+    /// [default](Self::default) ones.
+    pub fn from_env(cli: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        Self::with_overrides(cli, std::env::var_os(Self::ENV_VAR))
+    }
+
+    /// Roots passed with [`CLI_FLAG`](Self::CLI_FLAG) in the arguments of
+    /// a program, then the ones [from the environment](Self::from_env).
+    pub fn from_args(args: impl IntoIterator<Item = OsString>) -> Self {
+        Self::from_env(cli_roots(args.into_iter()))
+    }
+
+    fn with_overrides(
+        cli: impl IntoIterator<Item = impl Into<PathBuf>>,
+        env: Option<OsString>,
+    ) -> Self {
+        let mut roots = Self::new(cli);
+        if let Some(paths) = env {
+            roots
+                .roots
+                .extend(std::env::split_paths(&paths).filter(|x| !x.as_os_str().is_empty()));
+        }
+        roots.roots.extend(Self::default().roots);
+        roots
+    }
+
+    pub fn push(&mut self, root: impl Into<PathBuf>) {
+        self.roots.push(root.into());
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Canonical paths of the roots that exist, without duplicates.
+    pub fn existing(&self) -> Vec<PathBuf> {
+        let mut res: Vec<PathBuf> = vec![];
+        for root in self.roots.iter().filter_map(|x| x.canonicalize().ok()) {
+            if !res.contains(&root) {
+                res.push(root);
+            }
+        }
+        res
+    }
+
+    /// Finds the file `path` refers to.
+    ///
+    /// Relative paths are looked up in every root, then relative to the
+    /// working directory and finally among the embedded shaders.
+    pub fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        if let Ok(relative) = path.strip_prefix(Self::EMBEDDED) {
+            embedded::source(relative)?;
+            return Some(path.to_path_buf());
+        }
+        self.roots
+            .iter()
+            .map(|root| root.join(path))
+            .chain(std::iter::once(path.to_path_buf()))
+            .find(|x| x.is_file())
+            .or_else(|| {
+                embedded::source(path)?;
+                Some(Path::new(Self::EMBEDDED).join(path))
+            })
+    }
+
+    /// Reads a file returned by [`resolve`](Self::resolve).
+    pub fn read(path: &Path) -> io::Result<Cow<'static, str>> {
+        match path.strip_prefix(Self::EMBEDDED) {
+            Ok(relative) => embedded::source(relative)
+                .map(Cow::Borrowed)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("no embedded shader {}", relative.display()),
+                    )
+                }),
+            Err(_) => std::fs::read_to_string(path).map(Cow::Owned),
+        }
+    }
+
+    pub fn is_embedded(path: &Path) -> bool {
+        path.starts_with(Self::EMBEDDED)
+    }
+}
+
+impl ShaderFiles for ShaderRoots {
+    fn resolve(&self, include: &Path) -> Option<PathBuf> {
+        ShaderRoots::resolve(self, include)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Cow<'static, str>> {
+        ShaderRoots::read(path)
+    }
+}
+
+/// `shaders` in the working directory and next to the executable.
+impl Default for ShaderRoots {
+    fn default() -> Self {
+        let mut roots = Self::new([PathBuf::from("shaders")]);
+        if let Some(dir) = std::env::current_exe()
+            .ok()
+            .as_deref()
+            .and_then(Path::parent)
+        {
+            roots.push(dir.join("shaders"));
+        }
+        roots
+    }
+}
+
+impl std::fmt::Display for ShaderRoots {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for root in &self.roots {
+            match root.canonicalize() {
+                Ok(root) => writeln!(f, "\t{}", root.display())?,
+                Err(_) => writeln!(f, "\t{} (missing)", root.display())?,
+            }
+        }
+        write!(f, "\t{} ({} shaders)", Self::EMBEDDED, embedded::len())
+    }
+}
+
+/// Values of `--shaders <dir>` and `--shaders=<dir>` arguments.
+fn cli_roots(mut args: impl Iterator<Item = OsString>) -> Vec<PathBuf> {
+    let mut roots = vec![];
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy().into_owned();
+        if arg == ShaderRoots::CLI_FLAG {
+            roots.extend(args.next().map(PathBuf::from));
+        } else if let Some(root) = arg
+            .strip_prefix(ShaderRoots::CLI_FLAG)
+            .and_then(|x| x.strip_prefix('='))
+        {
+            roots.push(PathBuf::from(root));
+        }
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = OsString> {
+        args.iter()
+            .map(OsString::from)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn cli_flags_take_separate_and_inline_values() {
+        let roots = cli_roots(args(&[
+            "--shaders",
+            "a",
+            "-x",
+            "--shaders=b",
+            "c",
+            "--shaders",
+        ]));
+        assert_eq!(roots, [PathBuf::from("a"), PathBuf::from("b")]);
+    }
+
+    #[test]
+    fn cli_roots_come_before_env_and_default_roots() {
+        let env = std::env::join_paths(["env_a", "", "env_b"]).unwrap();
+        let roots = ShaderRoots::with_overrides(["cli"], Some(env));
+
+        let default = ShaderRoots::default();
+        let mut expected = vec![
+            PathBuf::from("cli"),
+            PathBuf::from("env_a"),
+            PathBuf::from("env_b"),
+        ];
+        expected.extend_from_slice(default.roots());
+        assert_eq!(roots.roots(), expected);
+        assert_eq!(default.roots()[0], Path::new("shaders"));
+    }
+
+    #[test]
+    fn files_in_roots_shadow_embedded_shaders() {
+        let name = Path::new("present.wgsl");
+        assert!(embedded::source(name).is_some());
+
+        let empty = ShaderRoots::new(Vec::<PathBuf>::new());
+        assert_eq!(
+            empty.resolve(name),
+            Some(Path::new(ShaderRoots::EMBEDDED).join(name))
+        );
+
+        let dir = std::env::temp_dir().join(format!("vokselis-roots-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("first")).unwrap();
+        std::fs::create_dir_all(dir.join("second")).unwrap();
+        std::fs::write(dir.join("second").join(name), "").unwrap();
+
+        let roots = ShaderRoots::new([dir.join("first"), dir.join("second")]);
+        assert_eq!(roots.resolve(name), Some(dir.join("second").join(name)));
+
+        std::fs::write(dir.join("first").join(name), "").unwrap();
+        assert_eq!(roots.resolve(name), Some(dir.join("first").join(name)));
+        assert!(ShaderRoots::read(&empty.resolve(name).unwrap()).is_ok());
+    }
+}
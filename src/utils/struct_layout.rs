@@ -0,0 +1,272 @@
+use std::sync::{Arc, Mutex};
+
+use super::reflection::WgslStruct;
+
+/// Memory layout of a `#[repr(C)]` type shared with shaders.
+///
+/// Implemented with the [`shader_struct!`](crate::shader_struct) macro.
+pub trait ShaderStruct {
+    fn layout() -> StructLayout;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructLayout {
+    /// Name of the matching WGSL struct
+    pub name: &'static str,
+    /// Name of the Rust type
+    pub type_name: &'static str,
+    pub size: usize,
+    /// Fields in declaration order, padding excluded
+    pub fields: Vec<FieldLayout>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldLayout {
+    pub name: &'static str,
+    /// Name of the matching WGSL member
+    pub member: &'static str,
+    pub offset: usize,
+    pub size: usize,
+}
+
+/// Implements [`ShaderStruct`] for a `#[repr(C)]` type.
+///
+/// Fields are matched with the WGSL struct members of the same name,
+/// `field as "member"` names a member that is called differently.
+/// Padding fields are left out.
+///
+/// ```ignore
+/// shader_struct!(CameraUniform as "Camera" {
+///     view_position as "view_pos", proj_view, inv_proj, prev_proj_view,
+///     target_position as "target_pos", jitter, prev_jitter,
+/// });
+/// ```
+#[macro_export]
+macro_rules! shader_struct {
+    (@member $field:ident $member:literal) => {
+        $member
+    };
+    (@member $field:ident) => {
+        stringify!($field)
+    };
+    ($ty:ty as $name:literal { $($field:ident $(as $member:literal)?),* $(,)? }) => {
+        impl $crate::ShaderStruct for $ty {
+            fn layout() -> $crate::StructLayout {
+                let uninit = ::std::mem::MaybeUninit::<$ty>::uninit();
+                let base = uninit.as_ptr();
+                $crate::StructLayout {
+                    name: $name,
+                    type_name: ::std::any::type_name::<$ty>(),
+                    size: ::std::mem::size_of::<$ty>(),
+                    fields: vec![$({
+                        // SAFETY: only the address of the field is taken, nothing is read
+                        let field = unsafe { ::std::ptr::addr_of!((*base).$field) };
+                        $crate::FieldLayout {
+                            name: stringify!($field),
+                            member: $crate::shader_struct!(@member $field $($member)?),
+                            offset: field as usize - base as usize,
+                            size: $crate::size_of_pointee(field),
+                        }
+                    }),*],
+                }
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+pub fn size_of_pointee<T>(_: *const T) -> usize {
+    std::mem::size_of::<T>()
+}
+
+impl StructLayout {
+    /// Compares the layout with the WGSL struct of the same name in `structs`.
+    ///
+    /// Members are matched by name and have to be at the same offset with
+    /// the same size. Returns `None` if there's no such struct. The Rust
+    /// type may be larger than the WGSL struct as long as the extra bytes
+    /// come after the last member.
+    pub fn check(&self, structs: &[WgslStruct]) -> Option<Result<(), LayoutMismatch>> {
+        let wgsl_struct = structs.iter().find(|x| x.name == self.name)?;
+
+        let mut errors = vec![];
+        if self.size < wgsl_struct.span as usize {
+            errors.push(format!(
+                "size is {} bytes, WGSL expects {}",
+                self.size, wgsl_struct.span
+            ));
+        }
+        for member in &wgsl_struct.members {
+            match self.fields.iter().find(|x| x.member == member.name) {
+                Some(field)
+                    if field.offset != member.offset as usize
+                        || field.size != member.size as usize =>
+                {
+                    errors.push(format!(
+                        "`{}` at offset {} ({} bytes), WGSL `{}` at offset {} ({} bytes)",
+                        field.name,
+                        field.offset,
+                        field.size,
+                        member.name,
+                        member.offset,
+                        member.size
+                    ))
+                }
+                Some(_) => {}
+                None => errors.push(format!(
+                    "no field for WGSL `{}` at offset {} ({} bytes)",
+                    member.name, member.offset, member.size
+                )),
+            }
+        }
+        for field in &self.fields {
+            if !wgsl_struct.members.iter().any(|x| x.name == field.member) {
+                errors.push(format!(
+                    "`{}` has no WGSL member `{}`",
+                    field.name, field.member
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Some(Ok(()))
+        } else {
+            Some(Err(LayoutMismatch {
+                name: self.name,
+                type_name: self.type_name,
+                errors,
+            }))
+        }
+    }
+}
+
+/// Struct layouts shared by every compiler created with the registry.
+///
+/// Each registration bumps the generation, so compilers know which of
+/// their modules were checked against outdated layouts.
+#[derive(Debug, Clone, Default)]
+pub struct StructRegistry {
+    inner: Arc<Mutex<(u64, Vec<StructLayout>)>>,
+}
+
+impl StructRegistry {
+    /// Checks every following shader that declares a struct named like
+    /// `T`'s WGSL counterpart against the layout of `T`.
+    pub fn register<T: ShaderStruct>(&self) {
+        let layout = T::layout();
+        let mut inner = self.inner.lock().unwrap();
+        let (generation, layouts) = &mut *inner;
+        layouts.retain(|x| x.name != layout.name);
+        layouts.push(layout);
+        *generation += 1;
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.inner.lock().unwrap().0
+    }
+
+    pub fn layouts(&self) -> Vec<StructLayout> {
+        self.inner.lock().unwrap().1.clone()
+    }
+
+    /// Mismatches of all registered layouts with a struct in `structs`.
+    pub fn check(&self, structs: &[WgslStruct]) -> Vec<LayoutMismatch> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .1
+            .iter()
+            .filter_map(|layout| layout.check(structs)?.err())
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct LayoutMismatch {
+    pub name: &'static str,
+    pub type_name: &'static str,
+    pub errors: Vec<String>,
+}
+
+impl std::fmt::Display for LayoutMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "layout of `{}` doesn't match WGSL struct `{}`:",
+            self.type_name, self.name
+        )?;
+        for error in &self.errors {
+            write!(f, "\n  - {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LayoutMismatch {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    struct Light {
+        position: [f32; 3],
+        intensity: f32,
+        color: [f32; 4],
+    }
+
+    crate::shader_struct!(Light as "Light" {
+        position as "pos",
+        intensity,
+        color,
+    });
+
+    fn check(wgsl: &str) -> Option<Result<(), LayoutMismatch>> {
+        let module = naga::front::wgsl::parse_str(wgsl).unwrap();
+        Light::layout().check(&WgslStruct::declared_in(&module))
+    }
+
+    #[test]
+    fn members_are_matched_by_name() {
+        let res = check("struct Light { pos: vec3<f32>, intensity: f32, color: vec4<f32> };");
+        assert!(matches!(res, Some(Ok(()))));
+        assert!(check("struct Other { x: f32 };").is_none());
+    }
+
+    #[test]
+    fn renamed_members_are_reported() {
+        let err = check("struct Light { pos: vec3<f32>, power: f32, color: vec4<f32> };")
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(
+            err.errors,
+            [
+                "no field for WGSL `power` at offset 12 (4 bytes)",
+                "`intensity` has no WGSL member `intensity`",
+            ]
+        );
+    }
+
+    #[test]
+    fn swapped_members_are_reported() {
+        let err = check("struct Light { pos: vec3<f32>, color: vec4<f32>, intensity: f32 };")
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(
+            err.errors,
+            [
+                "size is 32 bytes, WGSL expects 48",
+                "`intensity` at offset 12 (4 bytes), WGSL `intensity` at offset 32 (4 bytes)",
+            ]
+        );
+    }
+
+    #[test]
+    fn registration_bumps_the_generation() {
+        let registry = StructRegistry::default();
+        let shared = registry.clone();
+        registry.register::<Light>();
+        registry.register::<Light>();
+        assert_eq!(shared.generation(), 2);
+        assert_eq!(shared.layouts(), [Light::layout()]);
+    }
+}
@@ -0,0 +1,122 @@
+use std::path::Path;
+
+use color_eyre::eyre::{bail, eyre, Result};
+
+/// Color and opacity by volume sample, as RGBA at evenly spaced samples from 0 to 1.
+#[derive(Debug, Clone)]
+pub struct TransferFunction {
+    pub colors: Vec<[u8; 4]>,
+}
+
+impl TransferFunction {
+    /// Number of entries in [`colors`](Self::colors)
+    pub const RESOLUTION: usize = 256;
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read(path)?;
+        Self::parse(path, &contents)
+    }
+
+    /// Parses `contents` of the `.tf` file at `path`.
+    ///
+    /// Every line is a control point `sample r g b a` with all numbers in
+    /// `0..=1` and samples increasing, lines starting with `#` are comments.
+    /// Colors are interpolated linearly between control points and held
+    /// past the first and the last one.
+    pub fn parse(path: &Path, contents: &[u8]) -> Result<Self> {
+        if path.extension().and_then(|x| x.to_str()) != Some("tf") {
+            bail!("{}: unsupported transfer function format", path.display());
+        }
+        let text = std::str::from_utf8(contents)?;
+        let points = control_points(text).map_err(|err| eyre!("{}:{err}", path.display()))?;
+        if points.is_empty() {
+            bail!("{}: no control points", path.display());
+        }
+
+        let colors = (0..Self::RESOLUTION)
+            .map(|i| {
+                let t = i as f32 / (Self::RESOLUTION - 1) as f32;
+                let next = points.partition_point(|(sample, _)| *sample < t);
+                let color = match (next.checked_sub(1), points.get(next)) {
+                    (Some(prev), Some(&(t1, c1))) => {
+                        let (t0, c0) = points[prev];
+                        c0.lerp(c1, (t - t0) / (t1 - t0))
+                    }
+                    (Some(prev), None) => points[prev].1,
+                    (None, _) => points[0].1,
+                };
+                (color * 255.).round().to_array().map(|x| x as u8)
+            })
+            .collect();
+        Ok(Self { colors })
+    }
+}
+
+/// Control points sorted by sample, errors start with the line number.
+fn control_points(text: &str) -> Result<Vec<(f32, glam::Vec4)>> {
+    let mut points: Vec<(f32, glam::Vec4)> = vec![];
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = || eyre!("{}: expected `sample r g b a` in 0..=1", line_number + 1);
+        let numbers: Vec<_> = line.split_whitespace().map(str::parse::<f32>).collect();
+        let (sample, color) = match numbers[..] {
+            [Ok(t), Ok(r), Ok(g), Ok(b), Ok(a)] => (t, glam::Vec4::new(r, g, b, a)),
+            _ => return Err(err()),
+        };
+        if !(0.0..=1.0).contains(&sample) || color.min_element() < 0. || color.max_element() > 1. {
+            return Err(err());
+        }
+        if let Some((last, _)) = points.last() {
+            if sample <= *last {
+                bail!("{}: samples must be increasing", line_number + 1);
+            }
+        }
+        points.push((sample, color));
+    }
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<TransferFunction> {
+        TransferFunction::parse(Path::new("test.tf"), text.as_bytes())
+    }
+
+    #[test]
+    fn interpolates_between_control_points() {
+        let tf = parse("# ramp\n0 0 0 0 0\n\n1 1 0.4 0 1\n").unwrap();
+        assert_eq!(tf.colors.len(), TransferFunction::RESOLUTION);
+        assert_eq!(tf.colors[0], [0, 0, 0, 0]);
+        assert_eq!(tf.colors[51], [51, 20, 0, 51]);
+        assert_eq!(tf.colors[255], [255, 102, 0, 255]);
+    }
+
+    #[test]
+    fn holds_colors_past_the_ends() {
+        let tf = parse("0.5 1 0 0 1").unwrap();
+        assert!(tf.colors.iter().all(|&x| x == [255, 0, 0, 255]));
+
+        let tf = parse("0.2 0 0 0 1\n0.6 1 1 1 1").unwrap();
+        assert_eq!(tf.colors[0], [0, 0, 0, 255]);
+        assert_eq!(tf.colors[71], [50, 50, 50, 255]);
+        assert_eq!(tf.colors[255], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let message = |text| parse(text).unwrap_err().to_string();
+        assert_eq!(message(""), "test.tf: no control points");
+        assert!(message("0 1 1 1").starts_with("test.tf:1: expected"));
+        assert!(message("# comment\n0 2 0 0 1").starts_with("test.tf:2: expected"));
+        assert!(message("0.5 0 0 0 1\n0.5 1 1 1 1").ends_with("samples must be increasing"));
+        let err = TransferFunction::parse(Path::new("test.txt"), b"0 0 0 0 1").unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("unsupported transfer function format"));
+    }
+}
@@ -0,0 +1,231 @@
+use std::path::Path;
+
+use color_eyre::eyre::{bail, eyre, Result};
+
+/// Scalar volume with one byte per voxel, x varies fastest.
+#[derive(Debug, Clone)]
+pub struct Volume {
+    pub size: [u32; 3],
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sample {
+    U8,
+    U16 { big_endian: bool },
+}
+
+impl Volume {
+    /// Loads a volume in the format given by the extension of `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read(path)?;
+        Self::parse(path, &contents)
+    }
+
+    /// Parses `contents` of the file at `path`, the format is given by its extension.
+    ///
+    /// - `.raw` volumes carry their size and sample type in the file name,
+    ///   like `bonsai_256x256x256_uint8.raw`.
+    /// - `.nrrd` volumes need an attached header and raw encoding.
+    ///
+    /// 16-bit samples are reduced to their high byte.
+    pub fn parse(path: &Path, contents: &[u8]) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|x| x.to_str())
+            .unwrap_or_default();
+        let (size, sample, data) = match extension {
+            "raw" => {
+                let name = path
+                    .file_stem()
+                    .and_then(|x| x.to_str())
+                    .unwrap_or_default();
+                let (size, sample) = raw_name(name).ok_or_else(|| {
+                    eyre!(
+                        "{}: expected a name like `volume_256x256x256_uint8.raw`",
+                        path.display()
+                    )
+                })?;
+                (size, sample, contents)
+            }
+            "nrrd" => nrrd(contents).map_err(|err| eyre!("{}: {err}", path.display()))?,
+            _ => bail!("{}: unsupported volume format", path.display()),
+        };
+
+        let voxels = size.iter().map(|&x| x as usize).product::<usize>();
+        let sample_size = match sample {
+            Sample::U8 => 1,
+            Sample::U16 { .. } => 2,
+        };
+        if data.len() != voxels * sample_size {
+            bail!(
+                "{}: expected {} bytes for a {}x{}x{} volume, found {}",
+                path.display(),
+                voxels * sample_size,
+                size[0],
+                size[1],
+                size[2],
+                data.len()
+            );
+        }
+        let data = match sample {
+            Sample::U8 => data.to_vec(),
+            Sample::U16 { big_endian } => data
+                .chunks_exact(2)
+                .map(|x| if big_endian { x[0] } else { x[1] })
+                .collect(),
+        };
+        Ok(Self { size, data })
+    }
+}
+
+/// Size and sample type from the `WxHxD` and type parts of a file name.
+fn raw_name(name: &str) -> Option<([u32; 3], Sample)> {
+    let mut size = None;
+    let mut sample = None;
+    for part in name.split('_') {
+        let dims: Vec<_> = part.split('x').map(|x| x.parse::<u32>().ok()).collect();
+        if let [Some(x), Some(y), Some(z)] = dims[..] {
+            size = Some([x, y, z]);
+        }
+        sample = match part {
+            "uint8" => Some(Sample::U8),
+            "uint16" => Some(Sample::U16 { big_endian: false }),
+            _ => sample,
+        };
+    }
+    Some((size?, sample?))
+}
+
+/// Splits an NRRD file into its size, sample type and data.
+fn nrrd(contents: &[u8]) -> Result<([u32; 3], Sample, &[u8])> {
+    if !contents.starts_with(b"NRRD000") {
+        bail!("missing NRRD magic");
+    }
+    let header_end = contents
+        .windows(2)
+        .position(|x| x == b"\n\n")
+        .ok_or_else(|| eyre!("header isn't terminated by an empty line"))?;
+    let header = std::str::from_utf8(&contents[..header_end])?;
+
+    let mut size = None;
+    let mut sample = None;
+    let mut big_endian = false;
+    for line in header.lines().skip(1).filter(|x| !x.starts_with('#')) {
+        // `key:=value` lines are key/value pairs that don't affect the data
+        let (field, value) = match line.split_once(": ") {
+            Some(field) if !line.contains(":=") => field,
+            _ => continue,
+        };
+        let value = value.trim();
+        match field {
+            "dimension" if value != "3" => bail!("expected 3 dimensions, found {value}"),
+            "sizes" => {
+                let sizes: Vec<_> = value.split_whitespace().map(str::parse::<u32>).collect();
+                match sizes[..] {
+                    [Ok(x), Ok(y), Ok(z)] => size = Some([x, y, z]),
+                    _ => bail!("malformed sizes `{value}`"),
+                }
+            }
+            "type" => {
+                sample = Some(match value {
+                    "uchar" | "unsigned char" | "uint8" | "uint8_t" => Sample::U8,
+                    "ushort" | "unsigned short" | "unsigned short int" | "uint16" | "uint16_t" => {
+                        Sample::U16 { big_endian: false }
+                    }
+                    _ => bail!("unsupported type `{value}`"),
+                })
+            }
+            "encoding" if value != "raw" => bail!("unsupported encoding `{value}`"),
+            "endian" => big_endian = value == "big",
+            "data file" | "datafile" => bail!("detached data files are not supported"),
+            _ => {}
+        }
+    }
+
+    let size = size.ok_or_else(|| eyre!("missing sizes"))?;
+    let sample = match sample.ok_or_else(|| eyre!("missing type"))? {
+        Sample::U16 { .. } => Sample::U16 { big_endian },
+        sample => sample,
+    };
+    Ok((size, sample, &contents[header_end + 2..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_name_reads_size_and_sample_type() {
+        assert_eq!(
+            raw_name("bonsai_256x128x64_uint8"),
+            Some(([256, 128, 64], Sample::U8))
+        );
+        assert_eq!(
+            raw_name("uint16_2x2x2_scan"),
+            Some(([2, 2, 2], Sample::U16 { big_endian: false }))
+        );
+        assert_eq!(raw_name("bonsai_256x256_uint8"), None);
+        assert_eq!(raw_name("bonsai_256x256x256"), None);
+        assert_eq!(raw_name("bonsai_256x256x256_float"), None);
+    }
+
+    #[test]
+    fn parses_raw_volumes() {
+        let volume = Volume::parse(Path::new("a_2x1x2_uint8.raw"), &[1, 2, 3, 4]).unwrap();
+        assert_eq!(volume.size, [2, 1, 2]);
+        assert_eq!(volume.data, [1, 2, 3, 4]);
+
+        // Little endian, only the high byte is kept
+        let volume = Volume::parse(Path::new("a_2x1x1_uint16.raw"), &[1, 2, 3, 4]).unwrap();
+        assert_eq!(volume.data, [2, 4]);
+
+        let err = Volume::parse(Path::new("a_2x2x2_uint8.raw"), &[0; 7]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "a_2x2x2_uint8.raw: expected 8 bytes for a 2x2x2 volume, found 7"
+        );
+        assert!(Volume::parse(Path::new("a.raw"), &[0]).is_err());
+        assert!(Volume::parse(Path::new("a_1x1x1_uint8.vol"), &[0]).is_err());
+    }
+
+    #[test]
+    fn parses_nrrd_headers() {
+        let header = "NRRD0004\n\
+            # comment: ignored\n\
+            type: unsigned short\n\
+            dimension: 3\n\
+            sizes: 2 1 1\n\
+            spacings:=1 1 1\n\
+            endian: big\n\
+            encoding: raw\n\n";
+        let contents = [header.as_bytes(), &[1, 2, 3, 4]].concat();
+        let volume = Volume::parse(Path::new("a.nrrd"), &contents).unwrap();
+        assert_eq!(volume.size, [2, 1, 1]);
+        assert_eq!(volume.data, [1, 3]);
+
+        let nrrd_error = |header: &str| {
+            let contents = [header.as_bytes(), &[0]].concat();
+            nrrd(&contents).unwrap_err().to_string()
+        };
+        assert_eq!(nrrd_error("NRRD0004\ntype: uint8\n\n"), "missing sizes");
+        assert_eq!(nrrd_error("NRRD0004\nsizes: 1 1 1\n\n"), "missing type");
+        assert_eq!(nrrd_error("P5\n\n"), "missing NRRD magic");
+        assert_eq!(
+            nrrd_error("NRRD0004\ndimension: 2\n\n"),
+            "expected 3 dimensions, found 2"
+        );
+        assert_eq!(
+            nrrd_error("NRRD0004\nencoding: gzip\n\n"),
+            "unsupported encoding `gzip`"
+        );
+        assert_eq!(
+            nrrd_error("NRRD0004\ndata file: a.raw\n\n"),
+            "detached data files are not supported"
+        );
+        assert_eq!(
+            nrrd_error("NRRD0004\nsizes: 1 1 1\n"),
+            "header isn't terminated by an empty line"
+        );
+    }
+}
@@ -1,44 +1,198 @@
 use color_eyre::eyre::Result;
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
 use notify::{
-    event::{AccessKind, AccessMode},
+    event::{AccessKind, AccessMode, ModifyKind},
     Config, EventKind, Watcher as WatcherTrait,
 };
-use winit::event_loop::EventLoop;
+use winit::event_loop::{EventLoop, EventLoopProxy};
 
 use std::{
+    cell::RefCell,
+    collections::{BTreeSet, HashMap, HashSet},
     ffi::OsStr,
     path::{Path, PathBuf},
     rc::Rc,
-    sync::Arc,
+    sync::{Arc, Mutex, Weak},
+    thread,
+    time::{Duration, Instant},
 };
 
 use crate::{
-    context::PipelineHandle,
-    utils::{shader_compiler::ShaderCompiler, ContiniousHashMap},
+    context::{AssetHandle, PipelineHandle},
+    utils::{
+        preprocessor::{Preprocessor, ShaderDefines},
+        reflection::ShaderLayout,
+        shader_compiler::{self, CompilerError, ShaderCompiler},
+        shader_roots::ShaderRoots,
+        struct_layout::StructRegistry,
+        ContiniousHashMap,
+    },
 };
-use crate::{shader_compiler::CompilerError, SHADER_FOLDER};
+
+/// Time a file has to stay unchanged before the shaders built from it are recompiled
+const DEBOUNCE: Duration = Duration::from_millis(100);
+const MAX_COMPILE_WORKERS: usize = 4;
+/// Release builds use the shaders bundled at build time and don't watch files
+pub const HOT_RELOAD: bool = cfg!(debug_assertions);
 
 pub trait ReloadablePipeline {
-    fn reload(&mut self, device: &wgpu::Device, module: &wgpu::ShaderModule);
+    /// Rebuilds the pipeline from the new `module` and the bind group
+    /// layouts reflected from it.
+    fn reload(&mut self, device: &wgpu::Device, module: &wgpu::ShaderModule, layout: &ShaderLayout);
+}
+
+/// Resource loaded from a file, such as a volume, that is reloaded when the file changes.
+pub trait ReloadableAsset {
+    /// Replaces the asset with the new `contents` of its file.
+    ///
+    /// Implementations pick the format by the extension of `path`. On error
+    /// the asset is left as it was.
+    fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &Path,
+        contents: &[u8],
+    ) -> Result<()>;
+}
+
+/// Identifies a shader variant: the canonical path and the defines it is compiled with.
+///
+/// Shaders only found among the embedded ones keep their
+/// [embedded path](ShaderRoots::EMBEDDED) and are never reloaded.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShaderKey {
+    pub path: PathBuf,
+    pub defines: ShaderDefines,
+}
+
+impl ShaderKey {
+    /// Key of the variant of the shader at `path` as found in `roots`.
+    pub fn new(roots: &ShaderRoots, path: &Path, defines: &ShaderDefines) -> Self {
+        let path = roots.resolve(path).unwrap_or_else(|| path.to_path_buf());
+        Self {
+            path: path.canonicalize().unwrap_or(path),
+            defines: defines.clone(),
+        }
+    }
+}
+
+/// Sent to the event loop after a watched file changed.
+#[derive(Debug)]
+pub enum WatcherEvent {
+    Reloaded(ShaderKey, wgpu::ShaderModule, ShaderLayout),
+    Failed(ShaderKey, CompilerError),
+    /// New contents of a registered asset file, by canonical path
+    AssetChanged(PathBuf, Vec<u8>),
+}
+
+/// Tracks which registered shader variants include which files.
+///
+/// All paths are canonical.
+#[derive(Debug, Default)]
+pub struct IncludeGraph {
+    includes: HashMap<ShaderKey, Vec<PathBuf>>,
+    dependents: HashMap<PathBuf, HashSet<ShaderKey>>,
+}
+
+impl IncludeGraph {
+    pub fn contains(&self, shader: &ShaderKey) -> bool {
+        self.includes.contains_key(shader)
+    }
+
+    /// Replaces the set of files `shader` is built from.
+    pub fn update(&mut self, shader: ShaderKey, files: impl IntoIterator<Item = PathBuf>) {
+        if let Some(old) = self.includes.remove(&shader) {
+            for file in old {
+                if let Some(dependents) = self.dependents.get_mut(&file) {
+                    dependents.remove(&shader);
+                }
+            }
+        }
+        let files: Vec<_> = files.into_iter().filter(|x| x != &shader.path).collect();
+        for file in &files {
+            self.dependents
+                .entry(file.clone())
+                .or_default()
+                .insert(shader.clone());
+        }
+        self.includes.insert(shader, files);
+    }
+
+    /// Registered shader variants that have to be rebuilt after `file` changed.
+    pub fn affected<'a>(&'a self, file: &'a Path) -> impl Iterator<Item = &'a ShaderKey> {
+        let own = self.includes.keys().filter(move |x| x.path == file);
+        own.chain(self.dependents.get(file).into_iter().flatten())
+    }
 }
 
 pub struct Watcher {
-    _watcher: notify::RecommendedWatcher,
-    pub hash_dump: ContiniousHashMap<PathBuf, Rc<dyn ReloadablePipeline>>,
+    watcher: Option<notify::RecommendedWatcher>,
+    pub hash_dump: ContiniousHashMap<ShaderKey, Rc<RefCell<dyn ReloadablePipeline>>>,
+    pub assets: ContiniousHashMap<PathBuf, Rc<RefCell<dyn ReloadableAsset>>>,
+    include_graph: Arc<Mutex<IncludeGraph>>,
+    /// Canonical paths of the registered assets
+    asset_paths: Arc<Mutex<HashSet<PathBuf>>>,
+    /// Shader roots are watched recursively, folders of assets are not
+    watched: Vec<(PathBuf, notify::RecursiveMode)>,
+    roots: ShaderRoots,
+    preprocessor: Preprocessor,
 }
 
 impl Watcher {
+    /// Watches every existing directory of `roots` for shader changes.
+    ///
+    /// Changed shaders are checked against the layouts in `structs`.
+    /// Does nothing without [HOT_RELOAD], neither for shaders nor for assets.
     pub fn new(
         device: Arc<wgpu::Device>,
-        event_loop: &EventLoop<(PathBuf, wgpu::ShaderModule)>,
+        event_loop: &EventLoop<WatcherEvent>,
+        roots: &ShaderRoots,
+        structs: &StructRegistry,
     ) -> Result<Self> {
-        let mut watcher = notify::recommended_watcher(watch_callback(device, event_loop))?;
+        let include_graph = Arc::new(Mutex::new(IncludeGraph::default()));
+        let asset_paths = Arc::new(Mutex::new(HashSet::new()));
+        let preprocessor = Preprocessor::new(roots.clone());
+        if !HOT_RELOAD {
+            return Ok(Self {
+                watcher: None,
+                hash_dump: ContiniousHashMap::new(),
+                assets: ContiniousHashMap::new(),
+                include_graph,
+                asset_paths,
+                watched: vec![],
+                roots: roots.clone(),
+                preprocessor,
+            });
+        }
+
+        let queue = CompileQueue::new(device, event_loop, include_graph.clone(), roots, structs);
+        let asset_queue = AssetQueue {
+            paths: asset_paths.clone(),
+            proxy: event_loop.create_proxy(),
+        };
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        thread::Builder::new()
+            .name("shader-debouncer".into())
+            .spawn(move || debounce(receiver, queue, asset_queue))?;
+
+        let mut watcher = notify::recommended_watcher(watch_callback(sender, asset_paths.clone()))?;
         watcher.configure(Config::PreciseEvents(true))?;
-        watcher.watch(Path::new(SHADER_FOLDER), notify::RecursiveMode::Recursive)?;
+        let mut watched = vec![];
+        for root in roots.existing() {
+            watcher.watch(&root, notify::RecursiveMode::Recursive)?;
+            watched.push((root, notify::RecursiveMode::Recursive));
+        }
 
         Ok(Self {
-            _watcher: watcher,
+            watcher: Some(watcher),
             hash_dump: ContiniousHashMap::new(),
+            assets: ContiniousHashMap::new(),
+            include_graph,
+            asset_paths,
+            watched,
+            roots: roots.clone(),
+            preprocessor,
         })
     }
 
@@ -47,59 +201,112 @@ impl Watcher {
         path: &impl AsRef<Path>,
         pipeline: T,
     ) -> PipelineHandle<T> {
-        let pipeline_ref = Rc::new(pipeline);
-        self.hash_dump
-            .push_value(path.as_ref().canonicalize().unwrap(), pipeline_ref.clone());
+        self.register_variant(path, &ShaderDefines::default(), pipeline)
+    }
+
+    /// Registers a pipeline built from the variant of the shader selected by `defines`.
+    ///
+    /// The pipeline is only reloaded with modules compiled with the same defines.
+    pub fn register_variant<T: ReloadablePipeline + 'static>(
+        &mut self,
+        path: &impl AsRef<Path>,
+        defines: &ShaderDefines,
+        pipeline: T,
+    ) -> PipelineHandle<T> {
+        let key = ShaderKey::new(&self.roots, path.as_ref(), defines);
+        let mut include_graph = self.include_graph.lock().unwrap();
+        if HOT_RELOAD && !include_graph.contains(&key) {
+            let dependencies = shader_dependencies(&self.preprocessor, &key);
+            include_graph.update(key.clone(), dependencies);
+        }
+        drop(include_graph);
+
+        let pipeline_ref = Rc::new(RefCell::new(pipeline));
+        self.hash_dump.push_value(key, pipeline_ref.clone());
         pipeline_ref
     }
+
+    /// Registers an asset that is reloaded whenever the file at `path` changes.
+    ///
+    /// `path` is relative to the working directory and doesn't have to exist yet.
+    pub fn register_asset<T: ReloadableAsset + 'static>(
+        &mut self,
+        path: &impl AsRef<Path>,
+        asset: T,
+    ) -> AssetHandle<T> {
+        let asset_ref = Rc::new(RefCell::new(asset));
+        self.register_asset_handle(path, asset_ref.clone());
+        asset_ref
+    }
+
+    /// Registers an already shared asset, so one asset can follow several files.
+    pub fn register_asset_handle<T: ReloadableAsset + 'static>(
+        &mut self,
+        path: &impl AsRef<Path>,
+        asset: AssetHandle<T>,
+    ) {
+        let path = canonical_asset_path(path.as_ref());
+        if let (Some(watcher), Some(dir)) = (self.watcher.as_mut(), path.parent()) {
+            let covered = self.watched.iter().any(|(x, mode)| match mode {
+                notify::RecursiveMode::Recursive => dir.starts_with(x),
+                notify::RecursiveMode::NonRecursive => dir == x,
+            });
+            if !covered {
+                match watcher.watch(dir, notify::RecursiveMode::NonRecursive) {
+                    Ok(()) => self
+                        .watched
+                        .push((dir.to_path_buf(), notify::RecursiveMode::NonRecursive)),
+                    Err(err) => eprintln!("Failed to watch {}: {err}", dir.display()),
+                }
+            }
+        }
+        self.asset_paths.lock().unwrap().insert(path.clone());
+        self.assets.push_value(path, asset);
+    }
 }
 
+/// Canonical path of a file that may not exist, as long as its folder does.
+pub(crate) fn canonical_asset_path(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+    let parent = match path.parent() {
+        Some(parent) if parent.as_os_str().is_empty() => Path::new("."),
+        Some(parent) => parent,
+        None => return path.to_path_buf(),
+    };
+    match (parent.canonicalize(), path.file_name()) {
+        (Ok(parent), Some(name)) => parent.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Canonical paths of the files on disk included by the shader variant.
+fn shader_dependencies(preprocessor: &Preprocessor, key: &ShaderKey) -> Vec<PathBuf> {
+    let source = match ShaderRoots::read(&key.path) {
+        Ok(source) => source,
+        Err(_) => return vec![],
+    };
+    match preprocessor.process(&key.path, &source, &key.defines) {
+        Ok(source) => canonical_files(source.source_map.files()),
+        Err(_) => vec![],
+    }
+}
+
+fn canonical_files(files: &[PathBuf]) -> Vec<PathBuf> {
+    files.iter().filter_map(|x| x.canonicalize().ok()).collect()
+}
+
+/// Forwards canonical paths of saved shader and registered asset files.
 fn watch_callback(
-    device: Arc<wgpu::Device>,
-    event_loop: &EventLoop<(PathBuf, wgpu::ShaderModule)>,
+    sender: Sender<PathBuf>,
+    asset_paths: Arc<Mutex<HashSet<PathBuf>>>,
 ) -> impl FnMut(notify::Result<notify::Event>) {
-    let proxy = event_loop.create_proxy();
-    let device = Arc::downgrade(&device);
-    let mut shader_compiler = ShaderCompiler::new();
     move |event| match event {
-        Ok(res) => {
-            if let notify::event::Event {
-                kind: EventKind::Access(AccessKind::Close(AccessMode::Write)),
-                paths,
-                ..
-            } = res
-            {
-                for path in paths
-                    .into_iter()
-                    .filter(|p| p.extension() == Some(OsStr::new("wgsl")))
-                {
-                    match shader_compiler.create_shader_module(&path) {
-                        Ok(x) => {
-                            let device_ref = device.upgrade().unwrap();
-                            let module = unsafe {
-                                device_ref.create_shader_module_spirv(
-                                    &wgpu::ShaderModuleDescriptorSpirV {
-                                        label: path.to_str(),
-                                        source: x.into(),
-                                    },
-                                )
-                            };
-                            proxy
-                                .send_event((path, module))
-                                .expect("Event Loop have been dropped");
-                            crate::utils::green_blink();
-                        }
-                        Err(err) => match err {
-                            CompilerError::Compile { error, source } => {
-                                let file_name = match path.file_name().and_then(|x| x.to_str()) {
-                                    Some(name) => name,
-                                    None => "wgsl",
-                                };
-                                error.emit_to_stderr_with_path(&source, file_name);
-                            }
-                            _ => eprintln!("{err}"),
-                        },
-                    };
+        Ok(event) => {
+            for path in event.paths.iter().filter_map(|p| p.canonicalize().ok()) {
+                if is_reload_event(&event.kind, &path, &asset_paths.lock().unwrap()) {
+                    let _ = sender.send(path);
                 }
             }
         }
@@ -108,3 +315,343 @@ fn watch_callback(
         }
     }
 }
+
+/// Whether `kind` means the file at the canonical `path` was saved and
+/// it is a shader or one of the registered assets.
+fn is_reload_event(kind: &EventKind, path: &Path, asset_paths: &HashSet<PathBuf>) -> bool {
+    // Editors that write to a temporary file and rename it over
+    // the shader never close the shader for writing
+    let saved = matches!(
+        kind,
+        EventKind::Access(AccessKind::Close(AccessMode::Write))
+            | EventKind::Modify(ModifyKind::Name(_))
+    );
+    saved && (path.extension() == Some(OsStr::new("wgsl")) || asset_paths.contains(path))
+}
+
+/// Files waiting for their changes to settle.
+#[derive(Debug, Default)]
+struct Debouncer {
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl Debouncer {
+    /// Moves the deadline of `path` to [DEBOUNCE] after `now`.
+    fn push(&mut self, path: PathBuf, now: Instant) {
+        self.pending.insert(path, now + DEBOUNCE);
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.pending.values().min().copied()
+    }
+
+    /// Removes the files that stayed unchanged until `now`, sorted.
+    fn take_ready(&mut self, now: Instant) -> Vec<PathBuf> {
+        let mut ready: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(path, _)| path.clone())
+            .collect();
+        ready.sort();
+        for path in &ready {
+            self.pending.remove(path);
+        }
+        ready
+    }
+}
+
+/// Submits files to the compile and the asset queue once they stayed unchanged for [DEBOUNCE].
+///
+/// Each queue skips the files it doesn't know, a shader may be registered as an asset as well.
+fn debounce(receiver: Receiver<PathBuf>, queue: CompileQueue, asset_queue: AssetQueue) {
+    let mut debouncer = Debouncer::default();
+    loop {
+        let received = match debouncer.next_deadline() {
+            Some(deadline) => {
+                receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(path) => debouncer.push(path, Instant::now()),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        let ready = debouncer.take_ready(Instant::now());
+        if !ready.is_empty() {
+            queue.submit(&ready);
+            asset_queue.submit(&ready);
+        }
+    }
+}
+
+/// Reads changed asset files and hands their contents to the event loop.
+struct AssetQueue {
+    paths: Arc<Mutex<HashSet<PathBuf>>>,
+    proxy: EventLoopProxy<WatcherEvent>,
+}
+
+impl AssetQueue {
+    fn submit(&self, files: &[PathBuf]) {
+        for path in files {
+            if !self.paths.lock().unwrap().contains(path) {
+                continue;
+            }
+            match std::fs::read(path) {
+                Ok(contents) => {
+                    let _ = self
+                        .proxy
+                        .send_event(WatcherEvent::AssetChanged(path.clone(), contents));
+                }
+                Err(err) => eprintln!("Failed to reload {}: {err}", path.display()),
+            }
+        }
+    }
+}
+
+/// Latest submitted generation of each shader variant.
+#[derive(Debug, Default)]
+struct Generations(HashMap<ShaderKey, u64>);
+
+impl Generations {
+    /// Supersedes the pending jobs of `key`, returns the generation of the new one.
+    fn bump(&mut self, key: &ShaderKey) -> u64 {
+        let generation = self.0.entry(key.clone()).or_default();
+        *generation += 1;
+        *generation
+    }
+
+    fn is_current(&self, key: &ShaderKey, generation: u64) -> bool {
+        self.0.get(key) == Some(&generation)
+    }
+}
+
+/// Registered shader variants built from any of `files`, each once.
+fn affected_variants(include_graph: &IncludeGraph, files: &[PathBuf]) -> BTreeSet<ShaderKey> {
+    files
+        .iter()
+        .flat_map(|file| include_graph.affected(file))
+        .cloned()
+        .collect()
+}
+
+/// Compiles shader variants on a pool of worker threads.
+///
+/// Every submission bumps the generation of the affected variants. Workers
+/// skip jobs and throw away results whose generation was superseded, so only
+/// the latest version of a shader reaches the event loop.
+struct CompileQueue {
+    jobs: Sender<(ShaderKey, u64)>,
+    generations: Arc<Mutex<Generations>>,
+    include_graph: Arc<Mutex<IncludeGraph>>,
+}
+
+impl CompileQueue {
+    fn new(
+        device: Arc<wgpu::Device>,
+        event_loop: &EventLoop<WatcherEvent>,
+        include_graph: Arc<Mutex<IncludeGraph>>,
+        roots: &ShaderRoots,
+        structs: &StructRegistry,
+    ) -> Self {
+        let (jobs, receiver) = crossbeam_channel::unbounded();
+        let generations = Arc::new(Mutex::new(Generations::default()));
+        let workers = thread::available_parallelism()
+            .map_or(1, |x| x.get())
+            .min(MAX_COMPILE_WORKERS);
+        for i in 0..workers {
+            let worker = CompileWorker {
+                jobs: receiver.clone(),
+                device: Arc::downgrade(&device),
+                proxy: event_loop.create_proxy(),
+                generations: generations.clone(),
+                include_graph: include_graph.clone(),
+                shader_compiler: ShaderCompiler::with_structs(roots.clone(), structs.clone()),
+            };
+            thread::Builder::new()
+                .name(format!("shader-compiler-{i}"))
+                .spawn(move || worker.run())
+                .expect("Failed to spawn shader compiler thread");
+        }
+
+        Self {
+            jobs,
+            generations,
+            include_graph,
+        }
+    }
+
+    /// Schedules every registered shader variant built from `files`.
+    fn submit(&self, files: &[PathBuf]) {
+        // Every affected shader is compiled once, even if several
+        // of its files changed at the same time
+        let targets = affected_variants(&self.include_graph.lock().unwrap(), files);
+        let mut generations = self.generations.lock().unwrap();
+        for key in targets {
+            let generation = generations.bump(&key);
+            let _ = self.jobs.send((key, generation));
+        }
+    }
+}
+
+struct CompileWorker {
+    jobs: Receiver<(ShaderKey, u64)>,
+    device: Weak<wgpu::Device>,
+    proxy: EventLoopProxy<WatcherEvent>,
+    generations: Arc<Mutex<Generations>>,
+    include_graph: Arc<Mutex<IncludeGraph>>,
+    shader_compiler: ShaderCompiler,
+}
+
+impl CompileWorker {
+    fn is_current(&self, key: &ShaderKey, generation: u64) -> bool {
+        self.generations.lock().unwrap().is_current(key, generation)
+    }
+
+    fn run(mut self) {
+        for (key, generation) in self.jobs.iter() {
+            if !self.is_current(&key, generation) {
+                continue;
+            }
+            let res = self
+                .shader_compiler
+                .create_reflected_module(&key.path, &key.defines);
+            if !self.is_current(&key, generation) {
+                continue;
+            }
+            let dependencies = canonical_files(self.shader_compiler.dependencies());
+            if dependencies.first() == Some(&key.path) {
+                self.include_graph
+                    .lock()
+                    .unwrap()
+                    .update(key.clone(), dependencies);
+            }
+
+            let device = match self.device.upgrade() {
+                Some(device) => device,
+                None => return,
+            };
+            let event = match res {
+                Ok((words, layout)) => {
+                    let module = shader_compiler::create_module(&device, key.path.to_str(), &words);
+                    WatcherEvent::Reloaded(key.clone(), module, layout)
+                }
+                Err(err) => {
+                    err.emit_to_stderr();
+                    WatcherEvent::Failed(key.clone(), err)
+                }
+            };
+
+            // Checked under the lock so a newer result can't be overtaken
+            let generations = self.generations.lock().unwrap();
+            if !generations.is_current(&key, generation) {
+                continue;
+            }
+            if let WatcherEvent::Reloaded(..) = event {
+                crate::utils::green_blink();
+            }
+            if self.proxy.send_event(event).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use notify::event::{DataChange, RenameMode};
+
+    use super::*;
+
+    fn key(path: &str) -> ShaderKey {
+        ShaderKey {
+            path: PathBuf::from(path),
+            defines: ShaderDefines::default(),
+        }
+    }
+
+    #[test]
+    fn bursts_of_events_compile_once() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::default();
+        let shader = PathBuf::from("/shaders/a.wgsl");
+        let include = PathBuf::from("/shaders/common/b.wgsl");
+        for i in 0..3 {
+            debouncer.push(shader.clone(), start + i * DEBOUNCE / 2);
+        }
+        debouncer.push(include.clone(), start + DEBOUNCE / 2);
+        assert_eq!(debouncer.take_ready(start + DEBOUNCE), [] as [PathBuf; 0]);
+
+        // The burst on `a.wgsl` ended with its last event
+        let ready = debouncer.take_ready(start + 2 * DEBOUNCE);
+        assert_eq!(ready, [shader.clone(), include.clone()]);
+        assert_eq!(debouncer.next_deadline(), None);
+
+        let mut include_graph = IncludeGraph::default();
+        include_graph.update(key("/shaders/a.wgsl"), [shader.clone(), include]);
+        include_graph.update(key("/shaders/c.wgsl"), [PathBuf::from("/shaders/c.wgsl")]);
+        let targets: Vec<_> = affected_variants(&include_graph, &ready)
+            .into_iter()
+            .collect();
+        assert_eq!(targets, [key("/shaders/a.wgsl")]);
+    }
+
+    #[test]
+    fn stale_generations_are_dropped() {
+        let mut generations = Generations::default();
+        let a = key("a.wgsl");
+        let first = generations.bump(&a);
+        let second = generations.bump(&a);
+        assert!(!generations.is_current(&a, first));
+        assert!(generations.is_current(&a, second));
+
+        // Other variants keep their own generation
+        let b = key("b.wgsl");
+        assert!(!generations.is_current(&b, second));
+        let only = generations.bump(&b);
+        assert!(generations.is_current(&b, only));
+        assert!(generations.is_current(&a, second));
+    }
+
+    #[test]
+    fn renames_of_shaders_and_assets_trigger_reloads() {
+        let assets = HashSet::from([PathBuf::from("/assets/bonsai.tf")]);
+        let rename = EventKind::Modify(ModifyKind::Name(RenameMode::To));
+        let closed = EventKind::Access(AccessKind::Close(AccessMode::Write));
+        let modified = EventKind::Modify(ModifyKind::Data(DataChange::Content));
+
+        assert!(is_reload_event(
+            &rename,
+            Path::new("/shaders/a.wgsl"),
+            &assets
+        ));
+        assert!(is_reload_event(
+            &closed,
+            Path::new("/shaders/a.wgsl"),
+            &assets
+        ));
+        assert!(is_reload_event(
+            &rename,
+            Path::new("/assets/bonsai.tf"),
+            &assets
+        ));
+        // Writes are only picked up once the file is closed
+        assert!(!is_reload_event(
+            &modified,
+            Path::new("/shaders/a.wgsl"),
+            &assets
+        ));
+        assert!(!is_reload_event(
+            &rename,
+            Path::new("/shaders/a.wgsl.swp"),
+            &assets
+        ));
+        assert!(!is_reload_event(
+            &rename,
+            Path::new("/assets/other.tf"),
+            &assets
+        ));
+    }
+}